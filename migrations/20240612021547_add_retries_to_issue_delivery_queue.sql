@@ -0,0 +1,3 @@
+-- Track delivery attempts so failed sends can be retried with backoff
+ALTER TABLE issue_delivery_queue ADD COLUMN n_retries INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE issue_delivery_queue ADD COLUMN execute_after timestamptz NOT NULL DEFAULT now();
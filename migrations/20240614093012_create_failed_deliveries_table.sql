@@ -0,0 +1,10 @@
+-- Create failed_deliveries table
+CREATE TABLE failed_deliveries (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    last_error TEXT NOT NULL,
+    n_attempts INTEGER NOT NULL,
+    failed_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
@@ -0,0 +1,10 @@
+-- Create newsletter_delivery table to log the outcome of every delivery
+CREATE TABLE newsletter_delivery (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    status TEXT NOT NULL,
+    sent_at timestamptz NULL,
+    provider_message_id TEXT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
@@ -0,0 +1,8 @@
+-- Allow newsletter issues to be scheduled for later publishing
+BEGIN;
+    ALTER TABLE newsletter_issues
+        ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz;
+    ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+    ALTER TABLE newsletter_issues ADD COLUMN status TEXT NOT NULL DEFAULT 'published';
+    ALTER TABLE newsletter_issues ADD COLUMN scheduled_for timestamptz NULL;
+COMMIT;
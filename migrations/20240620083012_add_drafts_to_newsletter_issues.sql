@@ -0,0 +1,2 @@
+-- Drafts are edited before publishing, track when they were last saved
+ALTER TABLE newsletter_issues ADD COLUMN updated_at timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,15 @@
+-- Create api_keys table
+CREATE TABLE api_keys (
+    api_key_id uuid PRIMARY KEY,
+    user_id uuid NOT NULL
+        REFERENCES users (user_id),
+    name TEXT NOT NULL,
+    -- Leading characters of the key, shown to tell keys apart
+    key_prefix TEXT NOT NULL,
+    -- Hex-encoded SHA-256 hash of the key, the key itself is never stored
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    last_used_at timestamptz NULL,
+    revoked_at timestamptz NULL
+);
@@ -0,0 +1,18 @@
+-- Add role, creation and deactivation timestamps to users
+ALTER TABLE users ADD COLUMN role TEXT NULL;
+-- Existing users, e.g. the seeded admin, keep full access
+UPDATE users SET role = 'owner';
+ALTER TABLE users ALTER COLUMN role SET NOT NULL;
+
+ALTER TABLE users ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+ALTER TABLE users ADD COLUMN deactivated_at timestamptz NULL;
+
+-- Deleting a user also deletes the data tied to them
+ALTER TABLE idempotency
+    DROP CONSTRAINT idempotency_user_id_fkey,
+    ADD CONSTRAINT idempotency_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE;
+ALTER TABLE api_keys
+    DROP CONSTRAINT api_keys_user_id_fkey,
+    ADD CONSTRAINT api_keys_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users (user_id) ON DELETE CASCADE;
@@ -0,0 +1,12 @@
+-- Users need an email address to receive password reset links
+ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
+
+-- Create password_reset_tokens table
+CREATE TABLE password_reset_tokens (
+    -- Hex-encoded SHA-256 hash of the token, the token itself is never stored
+    token_hash TEXT PRIMARY KEY,
+    user_id uuid NOT NULL
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    expires_at timestamptz NOT NULL
+);
@@ -0,0 +1,22 @@
+-- Create two_factor_credentials table
+CREATE TABLE two_factor_credentials (
+    user_id uuid PRIMARY KEY
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    -- Base32-encoded TOTP secret
+    secret TEXT NOT NULL,
+    -- NULL while the enrollment waits for the first code to be verified
+    confirmed_at timestamptz NULL,
+    -- Latest TOTP period a code was accepted for, so that codes cannot be replayed
+    last_used_step BIGINT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+-- Create two_factor_recovery_codes table
+CREATE TABLE two_factor_recovery_codes (
+    user_id uuid NOT NULL
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    -- Hex-encoded SHA-256 hash of the code, the code itself is never stored
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,4 @@
+-- Confirmation tokens expire, so remember when each one was issued.
+-- Existing tokens count as issued now, rather than expiring all at once.
+ALTER TABLE subscription_tokens ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+CREATE INDEX subscription_tokens_subscriber_id_idx ON subscription_tokens (subscriber_id);
@@ -0,0 +1,10 @@
+-- Create subscription_email_changes table
+CREATE TABLE subscription_email_changes (
+    -- A subscriber has at most one pending change, requesting another one replaces it
+    subscriber_id uuid PRIMARY KEY
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    -- Sent to the new address, which only takes effect once the token comes back
+    verification_token TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
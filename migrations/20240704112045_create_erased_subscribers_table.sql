@@ -0,0 +1,7 @@
+-- Tombstones of subscribers erased on request, so that their email is not imported again.
+-- Only a salted hash of the email is kept.
+CREATE TABLE erased_subscribers (
+    email_hash TEXT NOT NULL,
+    erased_at timestamptz NOT NULL,
+    PRIMARY KEY (email_hash)
+);
@@ -0,0 +1,38 @@
+-- Subscribers join mailing lists, each subscription to a list having its own status.
+-- Everything that existed so far belongs to the default list, which keeps the app's original
+-- single list working as before. The status of `subscriptions` now belongs to the subscriber's
+-- email address: confirmed once any list subscription is, unsubscribed once they leave them all.
+BEGIN;
+    CREATE TABLE lists (
+        list_id uuid NOT NULL,
+        slug TEXT NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        created_at timestamptz NOT NULL DEFAULT now(),
+        PRIMARY KEY (list_id)
+    );
+    INSERT INTO lists (list_id, slug, name)
+    VALUES ('01908a3c-5f00-7000-8000-000000000001', 'newsletter', 'Newsletter');
+
+    CREATE TABLE list_subscriptions (
+        list_id uuid NOT NULL
+            REFERENCES lists (list_id),
+        subscriber_id uuid NOT NULL
+            REFERENCES subscriptions (id) ON DELETE CASCADE,
+        status TEXT NOT NULL,
+        subscribed_at timestamptz NOT NULL,
+        PRIMARY KEY (list_id, subscriber_id)
+    );
+    INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+    SELECT '01908a3c-5f00-7000-8000-000000000001', id, status, subscribed_at
+    FROM subscriptions;
+
+    -- Confirmation links confirm the subscription to a single list
+    ALTER TABLE subscription_tokens ADD COLUMN list_id uuid REFERENCES lists (list_id);
+    UPDATE subscription_tokens SET list_id = '01908a3c-5f00-7000-8000-000000000001';
+    ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
+
+    -- The list an issue is sent to, chosen when it is published, so drafts have none yet
+    ALTER TABLE newsletter_issues ADD COLUMN list_id uuid NULL REFERENCES lists (list_id);
+    UPDATE newsletter_issues SET list_id = '01908a3c-5f00-7000-8000-000000000001'
+    WHERE status <> 'draft';
+COMMIT;
@@ -0,0 +1,19 @@
+-- Free-form tags on subscribers, and the segment of a list each newsletter issue is sent to.
+-- An issue goes to the confirmed subscribers of its list that have all of `segment_tags`, none
+-- of `segment_excluded_tags`, and subscribed within the optional range. Empty tag arrays and
+-- NULL bounds do not filter anything, so existing issues keep going to the whole list.
+BEGIN;
+    CREATE TABLE subscriber_tags (
+        subscriber_id uuid NOT NULL
+            REFERENCES subscriptions (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (subscriber_id, tag)
+    );
+    CREATE INDEX subscriber_tags_tag_idx ON subscriber_tags (tag);
+
+    ALTER TABLE newsletter_issues
+        ADD COLUMN segment_tags TEXT[] NOT NULL DEFAULT '{}',
+        ADD COLUMN segment_excluded_tags TEXT[] NOT NULL DEFAULT '{}',
+        ADD COLUMN segment_subscribed_after timestamptz NULL,
+        ADD COLUMN segment_subscribed_before timestamptz NULL;
+COMMIT;
@@ -0,0 +1,11 @@
+-- Confirmation emails waiting to be sent by the background worker, so that large imports
+-- survive restarts. The link is read from `subscription_tokens` when the email is sent.
+CREATE TABLE confirmation_email_queue (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id uuid NOT NULL
+        REFERENCES lists (list_id) ON DELETE CASCADE,
+    n_retries INTEGER NOT NULL DEFAULT 0,
+    execute_after timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (subscriber_id, list_id)
+);
@@ -1,5 +1,9 @@
 mod credentials;
+mod csrf;
 mod middleware;
+mod two_factor;
 
 pub use credentials::*;
+pub use csrf::*;
 pub use middleware::*;
+pub use two_factor::*;
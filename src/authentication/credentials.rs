@@ -89,7 +89,7 @@ async fn get_stored_credentials(
 ) -> Result<Option<(Uuid, SecretString)>, anyhow::Error> {
     let row: Option<_> = sqlx::query!(
         r#"SELECT user_id, password_hash FROM users
-        WHERE username = $1"#,
+        WHERE username = $1 AND deactivated_at IS NULL"#,
         username,
     )
     .fetch_optional(pool)
@@ -125,7 +125,7 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
+pub fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         argon2::Algorithm::Argon2id,
@@ -0,0 +1,124 @@
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequest, FromRequestParts, Multipart, Request},
+    http::{header, request::Parts, HeaderName, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    domain::CsrfToken,
+    session_state::TypedSession,
+    utils::{e500, InternalServerError},
+};
+
+/// Header accepted in place of the form field, for requests that do not submit a form.
+pub const CSRF_TOKEN_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+
+/// Limit on the body of login and password reset forms, which anyone can submit.
+pub const MAX_LOGIN_FORM_SIZE: usize = 16 * 1024;
+
+/// Limit on the body of subscriber CSV imports, the largest forms we accept.
+/// Other admin forms keep the default limit of axum.
+pub const MAX_IMPORT_SIZE: usize = 16 * 1024 * 1024;
+
+/// Extracts the CSRF token of the session, generating it on first use, so that pages can embed
+/// it into their forms.
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Sync + Send,
+{
+    type Rejection = InternalServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = TypedSession::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, e)| e500(e))?;
+        session.get_or_insert_csrf_token().await.map_err(e500)
+    }
+}
+
+#[derive(Deserialize)]
+struct CsrfFormData {
+    csrf_token: Option<String>,
+}
+
+/// Rejects POST requests that do not carry the session's CSRF token, either in the
+/// `csrf_token` form field or the `X-CSRF-Token` header.
+///
+/// The body is buffered up to the limit set by a `DefaultBodyLimit` layer around this middleware,
+/// and only once the session is known to have a token.
+pub async fn reject_invalid_csrf_tokens(
+    session: TypedSession,
+    req: Request,
+    next: Next,
+) -> Result<Response, InternalServerError> {
+    if req.method() != Method::POST {
+        return Ok(next.run(req).await);
+    }
+
+    let Some(expected) = session.get_csrf_token().await.map_err(e500)? else {
+        return Ok(invalid_csrf_token());
+    };
+    let (parts, body) = req.into_parts();
+    let (submitted, body) = match parts.headers.get(CSRF_TOKEN_HEADER) {
+        Some(value) => (value.to_str().ok().map(ToString::to_string), body),
+        None => {
+            let request = Request::from_parts(parts.clone(), body);
+            let body = match Bytes::from_request(request, &()).await {
+                Ok(body) => body,
+                Err(rejection) => return Ok(rejection.into_response()),
+            };
+            let submitted = if has_content_type(&parts, "application/x-www-form-urlencoded") {
+                serde_urlencoded::from_bytes::<CsrfFormData>(&body)
+                    .ok()
+                    .and_then(|data| data.csrf_token)
+            } else if has_content_type(&parts, "multipart/form-data") {
+                csrf_token_from_multipart(&parts, body.clone()).await
+            } else {
+                None
+            };
+            // The handler still needs the body we consumed
+            (submitted, Body::from(body))
+        }
+    };
+
+    match submitted {
+        Some(submitted) if expected.matches(&submitted) => {
+            Ok(next.run(Request::from_parts(parts, body)).await)
+        }
+        _ => Ok(invalid_csrf_token()),
+    }
+}
+
+fn invalid_csrf_token() -> Response {
+    (StatusCode::FORBIDDEN, "Invalid CSRF token").into_response()
+}
+
+fn has_content_type(parts: &Parts, content_type: &str) -> bool {
+    parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(content_type))
+}
+
+/// Reads the `csrf_token` field of a multipart form, which our forms put before any file.
+async fn csrf_token_from_multipart(parts: &Parts, body: Bytes) -> Option<String> {
+    let content_type = parts.headers.get(header::CONTENT_TYPE)?.clone();
+    let req = Request::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .ok()?;
+    let mut multipart = Multipart::from_request(req, &()).await.ok()?;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("csrf_token") {
+            return field.text().await.ok();
+        }
+    }
+
+    None
+}
@@ -1,14 +1,24 @@
 use std::ops::Deref;
 
+use anyhow::Context;
 use axum::{
-    extract::Request,
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
+    Extension,
 };
 use uuid::Uuid;
 
 use crate::{
+    database::{
+        api_key_db,
+        user_db::{self, ActiveUser},
+    },
+    domain::{ApiKey, ApiKeyScope, UserRole},
+    routes::ApiError,
     session_state::TypedSession,
+    startup::AppState,
     utils::{e500, InternalServerError},
 };
 
@@ -41,3 +51,125 @@ pub async fn reject_anonymous_users(
         None => Ok(Redirect::to("/login").into_response()),
     }
 }
+
+/// Looks up what the logged-in user is allowed to do and makes it available as an `ActiveUser` extension.
+/// Users that were deactivated or deleted since logging in are logged out.
+/// Must be layered inside `reject_anonymous_users`.
+pub async fn reject_deactivated_users(
+    State(AppState { db_pool, .. }): State<AppState>,
+    session: TypedSession,
+    Extension(user_id): Extension<UserId>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, InternalServerError> {
+    match user_db::get_active_user(&db_pool, *user_id).await? {
+        Some(active_user) => {
+            req.extensions_mut().insert(active_user);
+            Ok(next.run(req).await)
+        }
+        None => {
+            session.logout().await;
+            Ok(Redirect::to("/login").into_response())
+        }
+    }
+}
+
+/// Rejects users that are not at least editors.
+/// Must be layered inside `reject_deactivated_users`.
+pub async fn require_editor(
+    Extension(active_user): Extension<ActiveUser>,
+    req: Request,
+    next: Next,
+) -> Response {
+    require_role(active_user, UserRole::Editor, req, next).await
+}
+
+/// Rejects users that are not owners.
+/// Must be layered inside `reject_deactivated_users`.
+pub async fn require_owner(
+    Extension(active_user): Extension<ActiveUser>,
+    req: Request,
+    next: Next,
+) -> Response {
+    require_role(active_user, UserRole::Owner, req, next).await
+}
+
+/// Both editors and owners are able to mail the whole list,
+/// so they also need two-factor authentication enabled.
+async fn require_role(
+    active_user: ActiveUser,
+    required: UserRole,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !active_user.role.includes(required) {
+        (
+            StatusCode::FORBIDDEN,
+            format!("You need the {} role to do this", required),
+        )
+            .into_response()
+    } else if !active_user.two_factor_enabled {
+        (
+            StatusCode::FORBIDDEN,
+            "You need to enable two-factor authentication to do this",
+        )
+            .into_response()
+    } else {
+        next.run(req).await
+    }
+}
+
+/// Scopes granted to the API key that authenticated the request.
+#[derive(Clone, Debug)]
+pub struct ApiKeyScopes(Vec<ApiKeyScope>);
+
+impl ApiKeyScopes {
+    /// Rejects the request unless the API key was granted `scope`.
+    pub fn require(&self, scope: ApiKeyScope) -> Result<(), ApiError> {
+        if self.0.contains(&scope) {
+            Ok(())
+        } else {
+            Err(ApiError::forbidden(format!(
+                "API key is missing the {} scope",
+                scope
+            )))
+        }
+    }
+}
+
+pub async fn reject_invalid_api_keys(
+    State(AppState { db_pool, .. }): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    // Malformed keys cannot match any stored key, so skip the database
+    let Some(api_key) = bearer_token(req.headers()).and_then(|t| ApiKey::parse(t).ok()) else {
+        return Err(ApiError::unauthorized());
+    };
+
+    let grant = api_key_db::authenticate_api_key(&db_pool, &api_key)
+        .await
+        .context("Failed to authenticate API key")
+        .map_err(e500)?
+        .ok_or_else(ApiError::unauthorized)?;
+    let scopes = grant
+        .scopes
+        .into_iter()
+        .map(ApiKeyScope::try_from)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(e500)?;
+
+    req.extensions_mut().insert(UserId(grant.user_id));
+    req.extensions_mut().insert(ApiKeyScopes(scopes));
+    Ok(next.run(req).await)
+}
+
+/// Extracts the token from an `Authorization: Bearer <token>` header.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
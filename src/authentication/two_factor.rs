@@ -0,0 +1,45 @@
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    database::two_factor_db,
+    domain::{RecoveryCode, TotpCode, TotpSecret},
+};
+
+/// Checks the second factor of a user with two-factor authentication enabled,
+/// which is either a code from their authenticator app or one of their recovery codes.
+/// Either kind of code is only accepted once.
+#[tracing::instrument(name = "Verify second factor", skip(pool, code))]
+pub async fn verify_second_factor(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &str,
+) -> Result<bool, anyhow::Error> {
+    if let Ok(code) = TotpCode::parse(code) {
+        let Some(credential) = two_factor_db::get_two_factor_credential(pool, user_id)
+            .await
+            .context("Failed to retrieve two-factor credential")?
+            .filter(|c| c.confirmed_at.is_some())
+        else {
+            return Ok(false);
+        };
+        let secret = TotpSecret::parse_base32(&credential.secret)
+            .context("Stored two-factor secret is invalid")?;
+
+        return match secret.verify(&code, Utc::now()) {
+            Some(step) => two_factor_db::record_used_step(pool, user_id, step)
+                .await
+                .context("Failed to record used authentication code"),
+            None => Ok(false),
+        };
+    }
+
+    match RecoveryCode::parse(code) {
+        Ok(code) => two_factor_db::use_recovery_code(pool, user_id, &code)
+            .await
+            .context("Failed to use recovery code"),
+        Err(_) => Ok(false),
+    }
+}
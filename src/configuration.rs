@@ -16,6 +16,10 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: SecretString,
+    pub worker: WorkerSettings,
+    pub login_rate_limit: LoginRateLimitSettings,
+    pub subscribe_rate_limit: SubscribeRateLimitSettings,
+    pub subscriptions: SubscriptionSettings,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -56,6 +60,10 @@ pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub port: u16,
     pub base_url: String,
+    // Key used to sign links that act on behalf of a subscriber, e.g. unsubscribe links
+    pub hmac_secret: SecretString,
+    // Header in which a reverse proxy forwards the client IP, the peer address is used if unset
+    pub client_ip_header: Option<String>,
 }
 
 impl ApplicationSettings {
@@ -71,10 +79,32 @@ impl ApplicationSettings {
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct EmailClientSettings {
-    pub base_url: String,
     pub sender_email: String,
-    pub authorization_token: SecretString,
     pub timeout_ms: u64,
+    pub transport: EmailTransportSettings,
+}
+
+/// Backend used to deliver emails, selected with the `type` field.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EmailTransportSettings {
+    Postmark {
+        base_url: String,
+        authorization_token: SecretString,
+    },
+    Smtp {
+        host: String,
+        #[serde(deserialize_with = "deserialize_number_from_string")]
+        port: u16,
+        // Refuse to send anything before the connection is upgraded to TLS
+        starttls: bool,
+        username: Option<String>,
+        password: Option<SecretString>,
+    },
+    // Write emails into a local directory, for development only
+    File {
+        directory: String,
+    },
 }
 
 impl EmailClientSettings {
@@ -82,15 +112,71 @@ impl EmailClientSettings {
         Email::parse(&self.sender_email)
     }
 
-    pub fn url(&self) -> Result<Url, ParseUrlError> {
-        Url::parse(&self.base_url)
-    }
-
     pub fn timeout(&self) -> std::time::Duration {
         std::time::Duration::from_millis(self.timeout_ms)
     }
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct WorkerSettings {
+    // Number of times a failed delivery is retried before it is dropped from the queue
+    pub max_retries: u16,
+    // Delay before the first retry, doubled on every subsequent attempt
+    pub retry_base_delay_ms: u64,
+    // Maximum number of queued deliveries dequeued and sent together
+    pub batch_size: u16,
+    // Number of worker tasks processing the delivery queue in parallel
+    pub concurrency: u16,
+}
+
+impl WorkerSettings {
+    pub fn retry_base_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.retry_base_delay_ms)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoginRateLimitSettings {
+    // Failed logins allowed for a username before it is locked out, also applied to failed
+    // second factor codes per user
+    pub max_failures_per_username: u32,
+    // Failed logins allowed from a single IP address, across all usernames
+    pub max_failures_per_ip: u32,
+    // Lockout once a limit is reached, doubled on every subsequent failure
+    pub base_lockout_seconds: u64,
+    // Upper bound of the lockout, failures are also forgotten after this long without any new ones
+    pub max_lockout_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubscribeRateLimitSettings {
+    // Subscription attempts allowed for an email address per window
+    pub max_attempts_per_email: u32,
+    // Subscription attempts allowed from a single IP address per window, across all emails
+    pub max_attempts_per_ip: u32,
+    pub window_seconds: u64,
+    // Minimum time before a pending subscriber is sent another confirmation email
+    pub confirmation_cooldown_seconds: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SubscriptionSettings {
+    // How long the link in a confirmation email stays valid
+    pub confirmation_token_ttl_hours: i64,
+    // Pending subscribers who have not confirmed after this long are deleted
+    pub purge_unconfirmed_after_days: i64,
+}
+
+impl SubscriptionSettings {
+    pub fn confirmation_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.confirmation_token_ttl_hours)
+    }
+
+    pub fn purge_unconfirmed_after(&self) -> chrono::Duration {
+        chrono::Duration::days(self.purge_unconfirmed_after_days)
+    }
+}
+
 pub fn get_environment() -> Environment {
     // Default to `local` if unspecified.
     std::env::var(APP_ENVIRONMENT_ENV_VAR)
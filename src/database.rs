@@ -1 +1,6 @@
+pub mod api_key_db;
+pub mod list_db;
+pub mod newsletter_db;
+pub mod subscriber_db;
+pub mod two_factor_db;
 pub mod user_db;
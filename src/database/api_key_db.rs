@@ -0,0 +1,67 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{ApiKey, ApiKeyScope, UserRole};
+
+/// User and scopes an API key was issued with.
+pub struct ApiKeyGrant {
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+#[tracing::instrument(name = "Insert API key", skip(pool, api_key))]
+pub async fn insert_api_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    name: &str,
+    api_key: &ApiKey,
+    scopes: &[ApiKeyScope],
+) -> Result<Uuid, sqlx::Error> {
+    let api_key_id = Uuid::new_v4();
+    let scopes: Vec<_> = scopes.iter().map(ApiKeyScope::to_string).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO api_keys (api_key_id, user_id, name, key_prefix, key_hash, scopes)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        api_key_id,
+        user_id,
+        name,
+        api_key.display_prefix(),
+        api_key.hash(),
+        &scopes,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(api_key_id)
+}
+
+/// Looks up an API key that has not been revoked, recording that it has just been used.
+/// Only owners can create API keys, so keys stop working once their user is no longer one.
+#[tracing::instrument(name = "Authenticate API key", skip_all)]
+pub async fn authenticate_api_key(
+    pool: &PgPool,
+    api_key: &ApiKey,
+) -> Result<Option<ApiKeyGrant>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeyGrant,
+        r#"
+        UPDATE api_keys
+        SET last_used_at = now()
+        WHERE
+            key_hash = $1 AND
+            revoked_at IS NULL AND
+            -- Keys stop working once their user is deactivated or loses the owner role
+            user_id IN (
+                SELECT user_id FROM users WHERE deactivated_at IS NULL AND role = $2
+            )
+        RETURNING user_id, scopes
+        "#,
+        api_key.hash(),
+        UserRole::Owner.to_string()
+    )
+    .fetch_optional(pool)
+    .await
+}
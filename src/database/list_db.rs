@@ -0,0 +1,352 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::{NoContext, Timestamp, Uuid};
+
+use crate::domain::{ListSlug, SubscriptionStatus};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MailingList {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+}
+
+/// A list along with how many subscribers it has, for the admin pages.
+#[derive(Debug, Serialize)]
+pub struct ListSummary {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub n_confirmed: i64,
+    pub n_pending: i64,
+}
+
+/// Returns every list, oldest first, so that the default list comes first.
+#[tracing::instrument(name = "Get lists", skip(pool))]
+pub async fn get_lists(pool: &PgPool) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        "SELECT list_id, slug, name FROM lists ORDER BY created_at, slug"
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get list summaries", skip(pool))]
+pub async fn get_list_summaries(pool: &PgPool) -> Result<Vec<ListSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        ListSummary,
+        r#"
+        SELECT
+            l.list_id,
+            l.slug,
+            l.name,
+            count(*) FILTER (WHERE ls.status = $1) AS "n_confirmed!",
+            count(*) FILTER (WHERE ls.status = $2) AS "n_pending!"
+        FROM lists l
+        LEFT JOIN list_subscriptions ls USING (list_id)
+        GROUP BY l.list_id
+        ORDER BY l.created_at, l.slug
+        "#,
+        SubscriptionStatus::Confirmed.to_string(),
+        SubscriptionStatus::PendingConfirmation.to_string()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get list by slug", skip(pool))]
+pub async fn get_list_by_slug(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        "SELECT list_id, slug, name FROM lists WHERE slug = $1",
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get list", skip(pool))]
+pub async fn get_list(pool: &PgPool, list_id: Uuid) -> Result<Option<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        "SELECT list_id, slug, name FROM lists WHERE list_id = $1",
+        list_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns `None` if a list with the same slug already exists.
+#[tracing::instrument(name = "Insert list", skip(pool))]
+pub async fn insert_list(
+    pool: &PgPool,
+    slug: &ListSlug,
+    name: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let list_id = Uuid::new_v7(Timestamp::now(NoContext));
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO lists (list_id, slug, name)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (slug) DO NOTHING
+        "#,
+        list_id,
+        slug.as_ref(),
+        name
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok((inserted > 0).then_some(list_id))
+}
+
+/// Subscribes the subscriber to the list with the given status, replacing the status and
+/// subscription date of an earlier subscription to the same list.
+#[tracing::instrument(name = "Upsert list subscription", skip(transaction))]
+pub async fn upsert_list_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+    status: &SubscriptionStatus,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+        SET status = EXCLUDED.status, subscribed_at = EXCLUDED.subscribed_at
+        "#,
+        list_id,
+        subscriber_id,
+        status.to_string()
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Subscribes all the given subscribers to the list at once, leaving existing subscriptions
+/// to the list and the status of the subscribers' email addresses untouched.
+/// Returns the ids of the subscribers that were added to the list.
+#[tracing::instrument(name = "Insert list subscriptions", skip(transaction, subscriber_ids))]
+pub async fn insert_list_subscriptions(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_ids: &[Uuid],
+    status: &SubscriptionStatus,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let added: Vec<_> = sqlx::query!(
+        r#"
+        INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+        SELECT $1, subscriber_id, $3, now()
+        FROM UNNEST($2::uuid[]) AS subscriber_id
+        ON CONFLICT (list_id, subscriber_id) DO NOTHING
+        RETURNING subscriber_id
+        "#,
+        list_id,
+        subscriber_ids,
+        status.to_string()
+    )
+    .fetch_all(&mut **transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.subscriber_id)
+    .collect();
+
+    Ok(added)
+}
+
+/// Returns the status of the subscriber on the list, or `None` if they never subscribed to it.
+#[tracing::instrument(name = "Get list subscription status", skip(transaction))]
+pub async fn get_list_subscription_status(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let result = sqlx::query!(
+        "SELECT status FROM list_subscriptions WHERE list_id = $1 AND subscriber_id = $2",
+        list_id,
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    Ok(result.map(|r| r.status))
+}
+
+/// Confirms the subscription to the list, along with the subscriber's email address.
+#[tracing::instrument(name = "Confirm list subscription", skip(transaction))]
+pub async fn confirm_list_subscription(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE list_subscriptions SET status = $1 WHERE list_id = $2 AND subscriber_id = $3",
+        SubscriptionStatus::Confirmed.to_string(),
+        list_id,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE id = $2",
+        SubscriptionStatus::Confirmed.to_string(),
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Subscribes the subscriber to the list without asking for confirmation, for subscribers
+/// opting in from their preferences, which they reached through a link sent to their address.
+#[tracing::instrument(name = "Resubscribe to list", skip(transaction))]
+pub async fn resubscribe_to_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    upsert_list_subscription(
+        transaction,
+        list_id,
+        subscriber_id,
+        &SubscriptionStatus::Confirmed,
+    )
+    .await?;
+    sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE id = $2",
+        SubscriptionStatus::Confirmed.to_string(),
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Unsubscribes the subscriber from the list, and their email address along with it once no
+/// confirmed list is left. Returns `false` if the subscriber never subscribed to the list.
+#[tracing::instrument(name = "Unsubscribe from list", skip(transaction))]
+pub async fn unsubscribe_from_list(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        "UPDATE list_subscriptions SET status = $1 WHERE list_id = $2 AND subscriber_id = $3",
+        SubscriptionStatus::Unsubscribed.to_string(),
+        list_id,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions s SET status = $1
+        WHERE s.id = $2 AND NOT EXISTS (
+            SELECT 1 FROM list_subscriptions ls
+            WHERE ls.subscriber_id = s.id AND ls.status = $3
+        )
+        "#,
+        SubscriptionStatus::Unsubscribed.to_string(),
+        subscriber_id,
+        SubscriptionStatus::Confirmed.to_string()
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(true)
+}
+
+/// Returns the lists the subscriber still has to confirm their subscription to.
+#[tracing::instrument(name = "Get pending lists of subscriber", skip(pool))]
+pub async fn get_pending_lists(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"
+        SELECT l.list_id, l.slug, l.name
+        FROM list_subscriptions ls
+        JOIN lists l USING (list_id)
+        WHERE ls.subscriber_id = $1 AND ls.status = $2
+        ORDER BY l.created_at, l.slug
+        "#,
+        subscriber_id,
+        SubscriptionStatus::PendingConfirmation.to_string()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// A list as shown in the preference center, with the subscriber's status on it, if any.
+#[derive(Debug, Serialize)]
+pub struct ListPreference {
+    pub list_id: Uuid,
+    pub name: String,
+    pub status: Option<String>,
+}
+
+/// Returns every list along with the subscriber's status on it, for them to join or leave.
+#[tracing::instrument(name = "Get list preferences of subscriber", skip(pool))]
+pub async fn get_list_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListPreference>, sqlx::Error> {
+    sqlx::query_as!(
+        ListPreference,
+        r#"
+        SELECT l.list_id, l.name, ls.status AS "status?"
+        FROM lists l
+        LEFT JOIN list_subscriptions ls ON ls.list_id = l.list_id AND ls.subscriber_id = $1
+        ORDER BY l.created_at, l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// A list the subscriber joined, as included in their data export.
+#[derive(Debug, Serialize)]
+pub struct ListMembership {
+    pub slug: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Get list memberships of subscriber", skip(pool))]
+pub async fn get_list_memberships(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListMembership>, sqlx::Error> {
+    sqlx::query_as!(
+        ListMembership,
+        r#"
+        SELECT l.slug, l.name, ls.status, ls.subscribed_at
+        FROM list_subscriptions ls
+        JOIN lists l USING (list_id)
+        WHERE ls.subscriber_id = $1
+        ORDER BY l.created_at, l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,194 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::{NewsletterIssueStatus, ScheduledTime, Segment, SubscriptionStatus};
+
+/// Channel on which delivery workers are notified about newly queued tasks.
+pub const DELIVERY_QUEUE_CHANNEL: &str = "issue_delivery_queue";
+
+#[tracing::instrument(name = "Insert newsletter issue", skip_all)]
+pub async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    segment: &Segment,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    scheduled_for: Option<ScheduledTime>,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let status = match scheduled_for {
+        Some(_) => NewsletterIssueStatus::Scheduled,
+        None => NewsletterIssueStatus::Published,
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            status,
+            scheduled_for,
+            published_at,
+            list_id,
+            segment_tags,
+            segment_excluded_tags,
+            segment_subscribed_after,
+            segment_subscribed_before
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, CASE WHEN $6::timestamptz IS NULL THEN now() END,
+            $7, $8, $9, $10, $11
+        )
+        "#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content,
+        status.to_string(),
+        scheduled_for.as_ref().map(ScheduledTime::as_datetime),
+        list_id,
+        &segment.tag_strings(),
+        &segment.excluded_tag_strings(),
+        segment.subscribed_after,
+        segment.subscribed_before,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// Queues the issue for every confirmed subscriber in the segment of the list it was published
+/// to. The segment is filtered the same way as in `count_recipients`.
+#[tracing::instrument(name = "Enqueue delivery tasks", skip_all)]
+pub async fn enqueue_delivery_tasks(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        SELECT $1, s.email
+        FROM newsletter_issues ni
+        JOIN list_subscriptions ls ON ls.list_id = ni.list_id
+        JOIN subscriptions s ON s.id = ls.subscriber_id
+        WHERE
+            ni.newsletter_issue_id = $1 AND
+            ls.status = $2 AND
+            ARRAY(SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id)
+                @> ni.segment_tags AND
+            NOT ARRAY(SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id)
+                && ni.segment_excluded_tags AND
+            (ni.segment_subscribed_after IS NULL OR
+                s.subscribed_at >= ni.segment_subscribed_after) AND
+            (ni.segment_subscribed_before IS NULL OR
+                s.subscribed_at < ni.segment_subscribed_before)
+        "#,
+        newsletter_issue_id,
+        SubscriptionStatus::Confirmed.to_string()
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    notify_delivery_workers(transaction).await
+}
+
+/// Counts the confirmed subscribers of the list in the segment, i.e. how many would receive an
+/// issue published to it right now.
+#[tracing::instrument(name = "Count segment recipients", skip(pool))]
+pub async fn count_recipients(
+    pool: &PgPool,
+    list_id: Uuid,
+    segment: &Segment,
+) -> Result<i64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!"
+        FROM list_subscriptions ls
+        JOIN subscriptions s ON s.id = ls.subscriber_id
+        WHERE
+            ls.list_id = $1 AND
+            ls.status = $2 AND
+            ARRAY(SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id) @> $3 AND
+            NOT ARRAY(SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id) && $4 AND
+            ($5::timestamptz IS NULL OR s.subscribed_at >= $5) AND
+            ($6::timestamptz IS NULL OR s.subscribed_at < $6)
+        "#,
+        list_id,
+        SubscriptionStatus::Confirmed.to_string(),
+        &segment.tag_strings(),
+        &segment.excluded_tag_strings(),
+        segment.subscribed_after,
+        segment.subscribed_before,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result.count)
+}
+
+/// Wakes idle delivery workers once the transaction commits.
+#[tracing::instrument(name = "Notify delivery workers", skip_all)]
+pub async fn notify_delivery_workers(
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("SELECT pg_notify($1, '')", DELIVERY_QUEUE_CHANNEL)
+        .execute(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+/// Moves a draft to the published or scheduled state, sending it to the segment of the list.
+/// Returns `false` if the issue is not a draft, e.g. because it was already published.
+#[tracing::instrument(name = "Publish newsletter draft", skip(transaction))]
+pub async fn publish_draft(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    list_id: Uuid,
+    segment: &Segment,
+    scheduled_for: Option<ScheduledTime>,
+) -> Result<bool, sqlx::Error> {
+    let status = match scheduled_for {
+        Some(_) => NewsletterIssueStatus::Scheduled,
+        None => NewsletterIssueStatus::Published,
+    };
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            status = $3,
+            scheduled_for = $4,
+            published_at = CASE WHEN $4::timestamptz IS NULL THEN now() END,
+            list_id = $5,
+            segment_tags = $6,
+            segment_excluded_tags = $7,
+            segment_subscribed_after = $8,
+            segment_subscribed_before = $9
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = $2
+        "#,
+        newsletter_issue_id,
+        NewsletterIssueStatus::Draft.to_string(),
+        status.to_string(),
+        scheduled_for.as_ref().map(ScheduledTime::as_datetime),
+        list_id,
+        &segment.tag_strings(),
+        &segment.excluded_tag_strings(),
+        segment.subscribed_after,
+        segment.subscribed_before,
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use secrecy::SecretString;
+use sqlx::{Postgres, Transaction};
+use uuid::{NoContext, Timestamp, Uuid};
+
+use crate::domain::{
+    ErasedEmailHash, NewSubscriber, SubscriberTag, SubscriptionStatus, SubscriptionToken,
+};
+
+/// Inserts the subscribers with the given status, skipping those whose email is already
+/// subscribed. Returns the id and email of every subscriber that was inserted.
+#[tracing::instrument(name = "Insert subscribers", skip_all, fields(n_subscribers = subscribers.len()))]
+pub async fn insert_subscribers(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscribers: &[NewSubscriber],
+    status: &SubscriptionStatus,
+) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let ids: Vec<_> = subscribers
+        .iter()
+        .map(|_| Uuid::new_v7(Timestamp::now(NoContext)))
+        .collect();
+    let names: Vec<_> = subscribers
+        .iter()
+        .map(|s| s.name.as_ref().to_string())
+        .collect();
+    let emails: Vec<_> = subscribers
+        .iter()
+        .map(|s| s.email.as_ref().to_string())
+        .collect();
+
+    let rows = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, name, email, subscribed_at, status)
+        SELECT id, name, email, $4, $5
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS new (id, name, email)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id, email
+        "#,
+        &ids,
+        &names,
+        &emails,
+        Utc::now(),
+        status.to_string(),
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.id, r.email)).collect())
+}
+
+/// Returns the id and email of every subscriber with one of the emails
+/// whose email address is confirmed.
+#[tracing::instrument(name = "Get confirmed subscribers by email", skip_all, fields(n_emails = emails.len()))]
+pub async fn get_confirmed_subscriber_ids_by_email(
+    transaction: &mut Transaction<'_, Postgres>,
+    emails: &[String],
+) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT id, email FROM subscriptions WHERE email = ANY($1) AND status = $2",
+        emails,
+        SubscriptionStatus::Confirmed.to_string()
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| (r.id, r.email)).collect())
+}
+
+/// Queues confirmation emails for the subscribers of the list, to be sent by the delivery worker
+/// with the latest token of each subscriber.
+#[tracing::instrument(
+    name = "Enqueue confirmation emails",
+    skip_all,
+    fields(n_subscribers = subscriber_ids.len())
+)]
+pub async fn enqueue_confirmation_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO confirmation_email_queue (subscriber_id, list_id)
+        SELECT subscriber_id, $2
+        FROM UNNEST($1::uuid[]) AS subscriber_id
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_ids,
+        list_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Store subscription tokens", skip_all, fields(n_tokens = tokens.len()))]
+pub async fn store_tokens(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    tokens: &[(Uuid, SubscriptionToken)],
+) -> Result<(), sqlx::Error> {
+    let subscriber_ids: Vec<_> = tokens.iter().map(|(id, _)| *id).collect();
+    let tokens: Vec<_> = tokens.iter().map(|(_, t)| t.as_str().to_string()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        SELECT token, subscriber_id, $3
+        FROM UNNEST($1::text[], $2::uuid[]) AS new (token, subscriber_id)
+        "#,
+        &tokens,
+        &subscriber_ids,
+        list_id,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Adds the tags to the subscribers, keeping the tags they already have.
+#[tracing::instrument(name = "Insert subscriber tags", skip_all)]
+pub async fn insert_subscriber_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    tags: &[(Uuid, SubscriberTag)],
+) -> Result<(), sqlx::Error> {
+    let subscriber_ids: Vec<_> = tags.iter().map(|(id, _)| *id).collect();
+    let tags: Vec<_> = tags.iter().map(|(_, t)| t.as_ref().to_string()).collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag)
+        SELECT subscriber_id, tag
+        FROM UNNEST($1::uuid[], $2::text[]) AS new (subscriber_id, tag)
+        ON CONFLICT DO NOTHING
+        "#,
+        &subscriber_ids,
+        &tags,
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Replaces the tags of the subscriber. Returns `false` if the subscriber does not exist.
+#[tracing::instrument(name = "Set subscriber tags", skip(transaction))]
+pub async fn set_subscriber_tags(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    tags: &[SubscriberTag],
+) -> Result<bool, sqlx::Error> {
+    let exists = sqlx::query!(
+        "SELECT id FROM subscriptions WHERE id = $1 FOR UPDATE",
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    .is_some();
+    if !exists {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        "DELETE FROM subscriber_tags WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    let tagged: Vec<_> = tags.iter().map(|t| (subscriber_id, t.clone())).collect();
+    insert_subscriber_tags(transaction, &tagged).await?;
+
+    Ok(true)
+}
+
+/// Erases the subscriber along with their tokens, the newsletter deliveries still queued for
+/// them and the logs of past deliveries, leaving only a hash of their email behind.
+/// Returns the email of the erased subscriber, or `None` if they did not exist.
+#[tracing::instrument(name = "Delete subscriber", skip(transaction, hmac_secret))]
+pub async fn delete_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    hmac_secret: &SecretString,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    // Pending email changes are deleted along with the subscription
+    let Some(deleted) = sqlx::query!(
+        "DELETE FROM subscriptions WHERE id = $1 RETURNING email",
+        subscriber_id
+    )
+    .fetch_optional(&mut **transaction)
+    .await?
+    else {
+        return Ok(None);
+    };
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_email = $1",
+        deleted.email
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM newsletter_delivery WHERE subscriber_email = $1",
+        deleted.email
+    )
+    .execute(&mut **transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM failed_deliveries WHERE subscriber_email = $1",
+        deleted.email
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    let email_hash = ErasedEmailHash::compute(&deleted.email, hmac_secret);
+    sqlx::query!(
+        r#"
+        INSERT INTO erased_subscribers (email_hash, erased_at)
+        VALUES ($1, now())
+        ON CONFLICT (email_hash) DO UPDATE SET erased_at = EXCLUDED.erased_at
+        "#,
+        email_hash.as_str()
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(Some(deleted.email))
+}
+
+/// Returns which of the subscribers were erased before, by the email hashes left behind.
+#[tracing::instrument(name = "Get erased subscribers", skip_all)]
+pub async fn get_erased_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscribers: &[NewSubscriber],
+    hmac_secret: &SecretString,
+) -> Result<HashSet<String>, sqlx::Error> {
+    let mut emails_by_hash: HashMap<_, _> = subscribers
+        .iter()
+        .map(|s| {
+            let hash = ErasedEmailHash::compute(s.email.as_ref(), hmac_secret);
+            (hash.as_str().to_string(), s.email.as_ref().to_string())
+        })
+        .collect();
+    let hashes: Vec<_> = emails_by_hash.keys().cloned().collect();
+
+    let rows = sqlx::query!(
+        "SELECT email_hash FROM erased_subscribers WHERE email_hash = ANY($1)",
+        &hashes
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| emails_by_hash.remove(&r.email_hash))
+        .collect())
+}
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::domain::{RecoveryCode, TotpSecret};
+
+pub struct TwoFactorCredential {
+    pub secret: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(name = "Get two-factor credential", skip(pool))]
+pub async fn get_two_factor_credential(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<TwoFactorCredential>, sqlx::Error> {
+    sqlx::query_as!(
+        TwoFactorCredential,
+        r#"
+        SELECT secret, confirmed_at
+        FROM two_factor_credentials
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Check if two-factor authentication is enabled", skip(pool))]
+pub async fn is_two_factor_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM two_factor_credentials
+        WHERE
+            user_id = $1 AND
+            confirmed_at IS NOT NULL
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+/// Stores a secret awaiting its first code, replacing any earlier unconfirmed secret.
+/// Does nothing if two-factor authentication is already enabled.
+#[tracing::instrument(name = "Store pending two-factor secret", skip(pool, secret))]
+pub async fn store_pending_secret(
+    pool: &PgPool,
+    user_id: Uuid,
+    secret: &TotpSecret,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO two_factor_credentials (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+        SET secret = EXCLUDED.secret, created_at = now()
+        WHERE two_factor_credentials.confirmed_at IS NULL
+        "#,
+        user_id,
+        secret.expose_base32()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Enables two-factor authentication once the first code was verified.
+/// Returns `false` if it was already enabled.
+#[tracing::instrument(name = "Confirm two-factor authentication", skip(transaction))]
+pub async fn confirm_two_factor(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    step: i64,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE two_factor_credentials
+        SET
+            confirmed_at = now(),
+            last_used_step = $2
+        WHERE
+            user_id = $1 AND
+            confirmed_at IS NULL
+        "#,
+        user_id,
+        step
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+/// Records that a code for the period was accepted.
+/// Returns `false` if a code for this or a later period was already used.
+#[tracing::instrument(name = "Record used TOTP step", skip(pool))]
+pub async fn record_used_step(
+    pool: &PgPool,
+    user_id: Uuid,
+    step: i64,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE two_factor_credentials
+        SET last_used_step = $2
+        WHERE
+            user_id = $1 AND
+            confirmed_at IS NOT NULL AND
+            (last_used_step IS NULL OR last_used_step < $2)
+        "#,
+        user_id,
+        step
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Replace recovery codes", skip(transaction, codes))]
+pub async fn replace_recovery_codes(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    codes: &[RecoveryCode],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM two_factor_recovery_codes WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    let code_hashes: Vec<String> = codes.iter().map(RecoveryCode::hash).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO two_factor_recovery_codes (user_id, code_hash)
+        SELECT $1, * FROM UNNEST($2::text[])
+        "#,
+        user_id,
+        &code_hashes
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(())
+}
+
+/// Marks the recovery code as used. Returns `false` if it is unknown or was already used.
+#[tracing::instrument(name = "Use recovery code", skip(pool, code))]
+pub async fn use_recovery_code(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: &RecoveryCode,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE two_factor_recovery_codes
+        SET used_at = now()
+        WHERE
+            user_id = $1 AND
+            code_hash = $2 AND
+            used_at IS NULL
+        "#,
+        user_id,
+        code.hash()
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Count unused recovery codes", skip(pool))]
+pub async fn count_unused_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM two_factor_recovery_codes
+        WHERE
+            user_id = $1 AND
+            used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.count)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn delete_two_factor(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        "DELETE FROM two_factor_recovery_codes WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM two_factor_credentials WHERE user_id = $1",
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use anyhow::Context;
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::domain::{Email, UserRole};
+
 #[tracing::instrument(name = "Get username", skip(db_pool))]
 pub async fn get_username(db_pool: &PgPool, user_id: Uuid) -> Result<String, anyhow::Error> {
     let row = sqlx::query!(
@@ -17,3 +20,89 @@ pub async fn get_username(db_pool: &PgPool, user_id: Uuid) -> Result<String, any
     .context("Failed to perform a query to retrieve a username")?;
     Ok(row.username)
 }
+
+/// Returns the email of the user, or `None` if they have not set one.
+#[tracing::instrument(name = "Get user email", skip(db_pool))]
+pub async fn get_user_email(
+    db_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve a user email")?;
+    Ok(row.email)
+}
+
+/// What a logged-in user is allowed to do.
+#[derive(Debug, Clone, Copy)]
+pub struct ActiveUser {
+    pub role: UserRole,
+    pub two_factor_enabled: bool,
+}
+
+/// Returns the role and two-factor status of the user,
+/// or `None` if the user was deactivated or deleted.
+#[tracing::instrument(name = "Get active user", skip(db_pool))]
+pub async fn get_active_user(
+    db_pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<ActiveUser>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            u.role,
+            t.confirmed_at IS NOT NULL AS "two_factor_enabled!"
+        FROM users u
+        LEFT JOIN two_factor_credentials t USING (user_id)
+        WHERE
+            u.user_id = $1 AND
+            u.deactivated_at IS NULL
+        "#,
+        user_id,
+    )
+    .fetch_optional(db_pool)
+    .await
+    .context("Failed to perform a query to retrieve a user role")?;
+
+    row.map(|r| {
+        Ok(ActiveUser {
+            role: UserRole::try_from(r.role).context("Stored user role is invalid")?,
+            two_factor_enabled: r.two_factor_enabled,
+        })
+    })
+    .transpose()
+}
+
+#[tracing::instrument(name = "Insert user", skip(db_pool, password_hash))]
+pub async fn insert_user(
+    db_pool: &PgPool,
+    username: &str,
+    email: &Email,
+    password_hash: &SecretString,
+    role: UserRole,
+) -> Result<Uuid, sqlx::Error> {
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, email, password_hash, role)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username,
+        email.as_ref(),
+        password_hash.expose_secret(),
+        role.to_string()
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(user_id)
+}
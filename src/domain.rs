@@ -1,9 +1,27 @@
+mod api_key;
+mod csrf_token;
+mod delivery;
 mod email;
+mod list_slug;
 mod name;
+mod newsletter;
+mod password_reset;
+mod segment;
 mod subscription;
+mod two_factor;
 mod url;
+mod user_role;
 
+pub use api_key::*;
+pub use csrf_token::*;
+pub use delivery::*;
 pub use email::*;
+pub use list_slug::*;
 pub use name::*;
+pub use newsletter::*;
+pub use password_reset::*;
+pub use segment::*;
 pub use subscription::*;
+pub use two_factor::*;
 pub use url::*;
+pub use user_role::*;
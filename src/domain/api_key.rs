@@ -0,0 +1,169 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, thiserror::Error)]
+pub struct ParseApiKeyScopeError(String);
+
+impl AsRef<str> for ParseApiKeyScopeError {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ParseApiKeyScopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Permission granted to an API key.
+#[derive(Debug, Clone, Copy, strum_macros::Display, PartialEq)]
+pub enum ApiKeyScope {
+    #[strum(serialize = "subscribers:read")]
+    SubscribersRead,
+    #[strum(serialize = "subscribers:write")]
+    SubscribersWrite,
+    #[strum(serialize = "newsletters:read")]
+    NewslettersRead,
+    #[strum(serialize = "newsletters:write")]
+    NewslettersWrite,
+}
+
+impl ApiKeyScope {
+    pub const ALL: [ApiKeyScope; 4] = [
+        Self::SubscribersRead,
+        Self::SubscribersWrite,
+        Self::NewslettersRead,
+        Self::NewslettersWrite,
+    ];
+}
+
+impl TryFrom<String> for ApiKeyScope {
+    type Error = ParseApiKeyScopeError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.to_string() == s.to_lowercase())
+            .ok_or_else(|| ParseApiKeyScopeError(format!("{} is not a valid API key scope", s)))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseApiKeyError {
+    #[error("API key has an unknown prefix")]
+    InvalidPrefix,
+
+    #[error("invalid API key length")]
+    InvalidLength,
+
+    #[error("API key not alphanumeric")]
+    NotAlphanumeric,
+}
+
+/// Secret that machine clients present as a bearer token to use the JSON API.
+/// Only its hash is stored, so a key cannot be shown again once it has been minted.
+pub struct ApiKey(SecretString);
+
+impl ApiKey {
+    const PREFIX: &'static str = "z2p_";
+    const RANDOM_LENGTH: usize = 40;
+    const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+    /// Returns an instance of `ApiKey` if the input has the shape of a key we could have minted.
+    /// It returns `ParseApiKeyError` otherwise.
+    pub fn parse(s: &str) -> Result<Self, ParseApiKeyError> {
+        let random_part = s
+            .strip_prefix(Self::PREFIX)
+            .ok_or(ParseApiKeyError::InvalidPrefix)?;
+
+        if !random_part.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ParseApiKeyError::NotAlphanumeric);
+        }
+
+        if random_part.len() != Self::RANDOM_LENGTH {
+            return Err(ParseApiKeyError::InvalidLength);
+        }
+
+        Ok(Self(SecretString::new(s.to_string())))
+    }
+
+    /// Generate a new key made of a fixed prefix and 40 random case-sensitive alphanumeric characters.
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+        let random_part: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(Self::RANDOM_LENGTH)
+            .collect();
+        Self(SecretString::new(format!(
+            "{}{}",
+            Self::PREFIX,
+            random_part
+        )))
+    }
+
+    /// Hex-encoded SHA-256 hash of the key.
+    /// Unlike passwords, keys are long and random, so a fast hash is enough to protect them.
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+
+    /// Leading characters of the key, which are safe to display to tell keys apart.
+    pub fn display_prefix(&self) -> &str {
+        &self.0.expose_secret()[..Self::DISPLAY_PREFIX_LENGTH]
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        self.0.expose_secret()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_key_can_be_parsed() {
+        let key = ApiKey::generate();
+        let parsed = ApiKey::parse(key.expose_secret()).unwrap();
+        assert_eq!(parsed.hash(), key.hash());
+    }
+
+    #[test]
+    fn key_with_unknown_prefix_is_rejected() {
+        let key = format!("abc_{}", "a".repeat(40));
+        assert!(matches!(
+            ApiKey::parse(&key),
+            Err(ParseApiKeyError::InvalidPrefix)
+        ));
+    }
+
+    #[test]
+    fn key_with_wrong_length_is_rejected() {
+        assert!(matches!(
+            ApiKey::parse("z2p_tooshort"),
+            Err(ParseApiKeyError::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn display_prefix_does_not_reveal_the_key() {
+        let key = ApiKey::generate();
+        assert!(key.expose_secret().starts_with(key.display_prefix()));
+        assert!(key.display_prefix().len() < key.expose_secret().len() / 2);
+    }
+
+    #[test]
+    fn api_key_scope_round_trips_through_string() {
+        for scope in ApiKeyScope::ALL {
+            assert_eq!(ApiKeyScope::try_from(scope.to_string()).unwrap(), scope);
+        }
+    }
+
+    #[test]
+    fn unknown_api_key_scope_is_rejected() {
+        assert!(ApiKeyScope::try_from("everything".to_string()).is_err());
+    }
+}
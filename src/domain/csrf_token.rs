@@ -0,0 +1,56 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+/// Per-session token embedded in every form, so that state-changing requests can only be
+/// submitted from pages served by us and not by another site riding on the session cookie.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    const TOKEN_LENGTH: usize = 32;
+
+    /// Generate a random 32-characters-long case-sensitive token.
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+        Self(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(char::from)
+                .take(Self::TOKEN_LENGTH)
+                .collect(),
+        )
+    }
+
+    /// Wraps a token previously generated and stored in the session.
+    pub fn from_session(s: String) -> Self {
+        Self(s)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Compares the submitted token in constant time, so that it cannot be guessed from timings.
+    pub fn matches(&self, submitted: &str) -> bool {
+        let (expected, submitted) = (self.0.as_bytes(), submitted.as_bytes());
+        expected.len() == submitted.len()
+            && expected
+                .iter()
+                .zip(submitted)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CsrfToken;
+
+    #[test]
+    fn token_matches_itself_only() {
+        let token = CsrfToken::generate();
+        assert!(token.matches(token.as_str()));
+        assert!(!token.matches(CsrfToken::generate().as_str()));
+        assert!(!token.matches(&token.as_str()[1..]));
+        assert!(!token.matches(""));
+    }
+}
@@ -0,0 +1,57 @@
+#[derive(Debug, thiserror::Error)]
+pub struct ParseDeliveryStatusError(String);
+
+impl AsRef<str> for ParseDeliveryStatusError {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ParseDeliveryStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Final outcome of delivering a newsletter issue to a single subscriber.
+#[derive(Debug, strum_macros::Display, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum DeliveryStatus {
+    Sent,
+    Failed,
+}
+
+impl TryFrom<String> for DeliveryStatus {
+    type Error = ParseDeliveryStatusError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "sent" => Ok(Self::Sent),
+            "failed" => Ok(Self::Failed),
+            other => Err(ParseDeliveryStatusError(format!(
+                "{} is not a valid delivery status",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delivery_status_round_trips_through_string() {
+        for status in [DeliveryStatus::Sent, DeliveryStatus::Failed] {
+            assert_eq!(
+                DeliveryStatus::try_from(status.to_string()).unwrap(),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_delivery_status_is_rejected() {
+        assert!(DeliveryStatus::try_from("pending".to_string()).is_err());
+    }
+}
@@ -51,7 +51,7 @@ impl AsRef<str> for Email {
 }
 
 impl ValidateEmail for Email {
-    fn as_email_string(&self) -> Option<std::borrow::Cow<str>> {
+    fn as_email_string(&self) -> Option<std::borrow::Cow<'_, str>> {
         Some(std::borrow::Cow::Borrowed(self.as_ref()))
     }
 }
@@ -0,0 +1,81 @@
+#[derive(Debug, thiserror::Error)]
+pub struct ParseListSlugError(String);
+
+impl AsRef<str> for ParseListSlugError {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ParseListSlugError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Short name identifying a mailing list in forms and URLs, e.g. `security-advisories`.
+#[derive(Debug, Clone)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    const MAX_LENGTH: usize = 64;
+
+    /// Slug of the list every subscriber joined before there were several lists.
+    pub const DEFAULT: &'static str = "newsletter";
+
+    /// Returns an instance of `ListSlug` if the input is made of lowercase ASCII letters, digits
+    /// and inner hyphens. It returns `ParseListSlugError` otherwise.
+    pub fn parse(s: &str) -> Result<ListSlug, ParseListSlugError> {
+        let is_valid = !s.is_empty()
+            && s.len() <= Self::MAX_LENGTH
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !s.starts_with('-')
+            && !s.ends_with('-');
+
+        if is_valid {
+            Ok(Self(s.to_string()))
+        } else {
+            Err(ParseListSlugError(format!(
+                "{} is not a valid list slug, use lowercase letters, digits and hyphens",
+                s
+            )))
+        }
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ListSlug;
+
+    #[test]
+    fn default_slug_is_valid() {
+        assert!(ListSlug::parse(ListSlug::DEFAULT).is_ok());
+    }
+
+    #[test]
+    fn hyphenated_lowercase_slug_is_valid() {
+        assert!(ListSlug::parse("security-advisories-2").is_ok());
+    }
+
+    #[test]
+    fn invalid_slugs_are_rejected() {
+        for slug in [
+            "",
+            "Product",
+            "product updates",
+            "-product",
+            "product-",
+            "prödukt",
+        ] {
+            assert!(ListSlug::parse(slug).is_err(), "{} was accepted", slug);
+        }
+        assert!(ListSlug::parse(&"a".repeat(65)).is_err());
+    }
+}
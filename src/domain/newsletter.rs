@@ -0,0 +1,142 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+#[derive(Debug, thiserror::Error)]
+pub struct ParseNewsletterIssueStatusError(String);
+
+impl AsRef<str> for ParseNewsletterIssueStatusError {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ParseNewsletterIssueStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+#[derive(Debug, strum_macros::Display, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum NewsletterIssueStatus {
+    Draft,
+    Scheduled,
+    Published,
+    Cancelled,
+}
+
+impl TryFrom<String> for NewsletterIssueStatus {
+    type Error = ParseNewsletterIssueStatusError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "draft" => Ok(Self::Draft),
+            "scheduled" => Ok(Self::Scheduled),
+            "published" => Ok(Self::Published),
+            "cancelled" => Ok(Self::Cancelled),
+            other => Err(ParseNewsletterIssueStatusError(format!(
+                "{} is not a valid newsletter issue status",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseScheduledTimeError {
+    #[error("{0} is not a valid date and time")]
+    InvalidFormat(String),
+
+    #[error("Scheduled time must be in the future")]
+    NotInFuture,
+}
+
+/// Point in time at which a newsletter issue should be published.
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledTime(DateTime<Utc>);
+
+impl ScheduledTime {
+    const DATETIME_LOCAL_FORMAT: &'static str = "%Y-%m-%dT%H:%M";
+
+    /// Returns an instance of `ScheduledTime` if the input is a future point in time.
+    /// Accepts either an RFC 3339 timestamp or the `YYYY-MM-DDTHH:MM` format submitted by
+    /// HTML `datetime-local` inputs, which is interpreted as UTC.
+    /// It returns `ParseScheduledTimeError` otherwise.
+    pub fn parse(s: &str) -> Result<Self, ParseScheduledTimeError> {
+        let s = s.trim();
+        let time = DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDateTime::parse_from_str(s, Self::DATETIME_LOCAL_FORMAT).map(|t| t.and_utc())
+            })
+            .map_err(|_| ParseScheduledTimeError::InvalidFormat(s.to_string()))?;
+
+        if time <= Utc::now() {
+            return Err(ParseScheduledTimeError::NotInFuture);
+        }
+
+        Ok(Self(time))
+    }
+
+    pub fn as_datetime(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ScheduledTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.format("%Y-%m-%d %H:%M UTC").fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    #[test]
+    fn future_rfc3339_time_is_parsed_successfully() {
+        let time = (Utc::now() + Duration::days(1)).to_rfc3339();
+        assert!(ScheduledTime::parse(&time).is_ok());
+    }
+
+    #[test]
+    fn future_datetime_local_time_is_parsed_successfully() {
+        let time = (Utc::now() + Duration::days(1))
+            .format("%Y-%m-%dT%H:%M")
+            .to_string();
+        assert!(ScheduledTime::parse(&time).is_ok());
+    }
+
+    #[test]
+    fn past_time_is_rejected() {
+        let time = (Utc::now() - Duration::minutes(1)).to_rfc3339();
+        assert!(matches!(
+            ScheduledTime::parse(&time),
+            Err(ParseScheduledTimeError::NotInFuture)
+        ));
+    }
+
+    #[test]
+    fn invalid_time_is_rejected() {
+        assert!(matches!(
+            ScheduledTime::parse("next tuesday"),
+            Err(ParseScheduledTimeError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn newsletter_issue_status_round_trips_through_string() {
+        for status in [
+            NewsletterIssueStatus::Draft,
+            NewsletterIssueStatus::Scheduled,
+            NewsletterIssueStatus::Published,
+            NewsletterIssueStatus::Cancelled,
+        ] {
+            assert_eq!(
+                NewsletterIssueStatus::try_from(status.to_string()).unwrap(),
+                status
+            );
+        }
+    }
+}
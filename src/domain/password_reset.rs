@@ -0,0 +1,84 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParsePasswordResetTokenError {
+    #[error("invalid token length")]
+    InvalidLength,
+
+    #[error("token not alphanumeric")]
+    NotAlphanumeric,
+}
+
+/// Single-use token emailed to a user so they can set a new password.
+/// Only its hash is stored, so a leaked database cannot be used to take over accounts.
+pub struct PasswordResetToken(SecretString);
+
+impl PasswordResetToken {
+    const TOKEN_LENGTH: usize = 32;
+
+    /// Returns an instance of `PasswordResetToken` if the input looks like a generated token.
+    /// It returns `ParsePasswordResetTokenError` otherwise.
+    pub fn parse(s: &str) -> Result<Self, ParsePasswordResetTokenError> {
+        if !s.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ParsePasswordResetTokenError::NotAlphanumeric);
+        }
+
+        if s.len() != Self::TOKEN_LENGTH {
+            return Err(ParsePasswordResetTokenError::InvalidLength);
+        }
+
+        Ok(Self(SecretString::new(s.to_string())))
+    }
+
+    /// Generate a random 32-characters-long case-sensitive token.
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+        Self(SecretString::new(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(char::from)
+                .take(Self::TOKEN_LENGTH)
+                .collect(),
+        ))
+    }
+
+    /// Hex-encoded SHA-256 hash of the token, which is what gets stored.
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+
+    pub fn expose_secret(&self) -> &str {
+        self.0.expose_secret()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_token_can_be_parsed() {
+        let token = PasswordResetToken::generate();
+        let parsed = PasswordResetToken::parse(token.expose_secret()).unwrap();
+        assert_eq!(parsed.hash(), token.hash());
+    }
+
+    #[test]
+    fn token_with_wrong_length_is_rejected() {
+        assert!(matches!(
+            PasswordResetToken::parse("abc"),
+            Err(ParsePasswordResetTokenError::InvalidLength)
+        ));
+    }
+
+    #[test]
+    fn non_alphanumeric_token_is_rejected() {
+        let token = format!("{}!", "a".repeat(31));
+        assert!(matches!(
+            PasswordResetToken::parse(&token),
+            Err(ParsePasswordResetTokenError::NotAlphanumeric)
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+use chrono::{DateTime, NaiveDate, Utc};
+
+#[derive(Debug, thiserror::Error)]
+pub struct ParseSubscriberTagError(String);
+
+impl AsRef<str> for ParseSubscriberTagError {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ParseSubscriberTagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Free-form label attached to subscribers by admins, e.g. `beta-tester`.
+/// Tags are compared case-insensitively, so they are stored in lowercase.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    const MAX_LENGTH: usize = 50;
+
+    /// Returns an instance of `SubscriberTag` if the trimmed input is not empty, not too long,
+    /// and has no commas, which separate tags, nor control characters.
+    /// It returns `ParseSubscriberTagError` otherwise.
+    pub fn parse(s: &str) -> Result<SubscriberTag, ParseSubscriberTagError> {
+        let s = s.trim().to_lowercase();
+        let is_valid = !s.is_empty()
+            && s.chars().count() <= Self::MAX_LENGTH
+            && !s.chars().any(|c| c == ',' || c.is_control());
+
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(ParseSubscriberTagError(format!(
+                "{} is not a valid tag, use at most {} characters and no commas",
+                s,
+                Self::MAX_LENGTH
+            )))
+        }
+    }
+
+    /// Parses comma-separated tags, e.g. `beta-tester, vip`, ignoring empty ones and duplicates.
+    pub fn parse_list(s: &str) -> Result<Vec<SubscriberTag>, ParseSubscriberTagError> {
+        let mut tags = s
+            .split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(Self::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        tags.sort();
+        tags.dedup();
+        Ok(tags)
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseSegmentError {
+    #[error(transparent)]
+    InvalidTag(#[from] ParseSubscriberTagError),
+
+    #[error("{0} is not a valid date")]
+    InvalidDate(String),
+
+    #[error("The subscription date range is empty")]
+    EmptyRange,
+}
+
+/// The part of a list a newsletter issue is sent to. The default segment is the whole list.
+#[derive(Debug, Clone, Default)]
+pub struct Segment {
+    /// Subscribers must have every one of these tags.
+    pub tags: Vec<SubscriberTag>,
+    /// Subscribers must have none of these tags.
+    pub excluded_tags: Vec<SubscriberTag>,
+    /// Inclusive lower bound of when the subscriber subscribed.
+    pub subscribed_after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound of when the subscriber subscribed.
+    pub subscribed_before: Option<DateTime<Utc>>,
+}
+
+impl Segment {
+    const DATE_FORMAT: &'static str = "%Y-%m-%d";
+
+    /// Returns an instance of `Segment` from comma-separated tags and dates, as submitted by
+    /// forms. Dates are either RFC 3339 timestamps or `YYYY-MM-DD` dates, taken as midnight UTC,
+    /// and empty inputs do not filter anything.
+    /// It returns `ParseSegmentError` otherwise.
+    pub fn parse(
+        tags: &str,
+        excluded_tags: &str,
+        subscribed_after: &str,
+        subscribed_before: &str,
+    ) -> Result<Self, ParseSegmentError> {
+        let segment = Self {
+            tags: SubscriberTag::parse_list(tags)?,
+            excluded_tags: SubscriberTag::parse_list(excluded_tags)?,
+            subscribed_after: Self::parse_date(subscribed_after)?,
+            subscribed_before: Self::parse_date(subscribed_before)?,
+        };
+        if let (Some(after), Some(before)) = (segment.subscribed_after, segment.subscribed_before) {
+            if after >= before {
+                return Err(ParseSegmentError::EmptyRange);
+            }
+        }
+
+        Ok(segment)
+    }
+
+    fn parse_date(s: &str) -> Result<Option<DateTime<Utc>>, ParseSegmentError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(None);
+        }
+        DateTime::parse_from_rfc3339(s)
+            .map(|t| t.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(s, Self::DATE_FORMAT)
+                    .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc())
+            })
+            .map(Some)
+            .map_err(|_| ParseSegmentError::InvalidDate(s.to_string()))
+    }
+
+    pub fn tag_strings(&self) -> Vec<String> {
+        self.tags.iter().map(|t| t.as_ref().to_string()).collect()
+    }
+
+    pub fn excluded_tag_strings(&self) -> Vec<String> {
+        self.excluded_tags
+            .iter()
+            .map(|t| t.as_ref().to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn tags_are_trimmed_lowercased_and_deduplicated() {
+        let tags = SubscriberTag::parse_list(" Beta-Tester, vip,, beta-tester ").unwrap();
+        let tags: Vec<_> = tags.iter().map(AsRef::as_ref).collect();
+        assert_eq!(tags, vec!["beta-tester", "vip"]);
+    }
+
+    #[test]
+    fn invalid_tags_are_rejected() {
+        for tag in ["", "   ", "line\nbreak"] {
+            assert!(SubscriberTag::parse(tag).is_err(), "{:?} was accepted", tag);
+        }
+        assert!(SubscriberTag::parse(&"a".repeat(51)).is_err());
+    }
+
+    #[test]
+    fn empty_segment_is_the_whole_list() {
+        let segment = Segment::parse("", "", "", "").unwrap();
+        assert!(segment.tags.is_empty());
+        assert!(segment.excluded_tags.is_empty());
+        assert!(segment.subscribed_after.is_none());
+        assert!(segment.subscribed_before.is_none());
+    }
+
+    #[test]
+    fn dates_are_parsed_as_midnight_utc() {
+        let segment = Segment::parse("", "", "", "2024-01-01").unwrap();
+        assert_eq!(
+            segment.subscribed_before,
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn invalid_segments_are_rejected() {
+        assert!(matches!(
+            Segment::parse("", "", "last year", ""),
+            Err(ParseSegmentError::InvalidDate(_))
+        ));
+        assert!(matches!(
+            Segment::parse("", "", "2024-02-01", "2024-01-01"),
+            Err(ParseSegmentError::EmptyRange)
+        ));
+    }
+}
@@ -1,5 +1,17 @@
+use hmac::{Hmac, Mac};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use super::{Email, Name};
+
+/// Validated details of someone joining the newsletter.
+pub struct NewSubscriber {
+    pub name: Name,
+    pub email: Email,
+}
 
 #[derive(Debug, thiserror::Error)]
 pub struct ParseSubscriptionStatusError(String);
@@ -16,11 +28,20 @@ impl std::fmt::Display for ParseSubscriptionStatusError {
     }
 }
 
-#[derive(strum_macros::Display, PartialEq)]
+#[derive(Debug, strum_macros::Display, PartialEq)]
 #[strum(serialize_all = "snake_case")]
 pub enum SubscriptionStatus {
     PendingConfirmation,
     Confirmed,
+    Unsubscribed,
+}
+
+impl SubscriptionStatus {
+    pub const ALL: [SubscriptionStatus; 3] = [
+        Self::PendingConfirmation,
+        Self::Confirmed,
+        Self::Unsubscribed,
+    ];
 }
 
 impl TryFrom<String> for SubscriptionStatus {
@@ -30,6 +51,7 @@ impl TryFrom<String> for SubscriptionStatus {
         match s.to_lowercase().as_str() {
             "pending_confirmation" => Ok(Self::PendingConfirmation),
             "confirmed" => Ok(Self::Confirmed),
+            "unsubscribed" => Ok(Self::Unsubscribed),
             other => Err(ParseSubscriptionStatusError(format!(
                 "{} is not a valid subscription status",
                 other
@@ -82,6 +104,148 @@ impl SubscriptionToken {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum ParseSignedTokenError {
+    #[error("invalid token length")]
+    InvalidLength,
+
+    #[error("token not hexadecimal")]
+    NotHexadecimal,
+}
+
+/// HMAC-SHA256 signature of a subscriber id and the list they are leaving, allowing the
+/// subscriber to unsubscribe without logging in while preventing anyone else from forging the link.
+/// Links sent before lists existed sign no list and unsubscribe from every list.
+pub struct UnsubscribeToken(String);
+
+impl UnsubscribeToken {
+    /// Returns an instance of `UnsubscribeToken` if the input looks like a hex-encoded signature.
+    /// It returns `ParseSignedTokenError` otherwise.
+    /// The token still needs to be checked against a subscriber with `verify`.
+    pub fn parse(s: &str) -> Result<Self, ParseSignedTokenError> {
+        parse_signature(s).map(Self)
+    }
+
+    /// Signs the subscriber id and list id with the given secret.
+    pub fn generate(
+        subscriber_id: Uuid,
+        list_id: Option<Uuid>,
+        hmac_secret: &SecretString,
+    ) -> Self {
+        Self(sign(Self::mac(subscriber_id, list_id, hmac_secret)))
+    }
+
+    /// Checks in constant time that the token was generated for the subscriber id and list id.
+    pub fn verify(
+        &self,
+        subscriber_id: Uuid,
+        list_id: Option<Uuid>,
+        hmac_secret: &SecretString,
+    ) -> bool {
+        verify_signature(Self::mac(subscriber_id, list_id, hmac_secret), &self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn mac(subscriber_id: Uuid, list_id: Option<Uuid>, hmac_secret: &SecretString) -> Hmac<Sha256> {
+        let mut mac = new_mac(hmac_secret);
+        mac.update(subscriber_id.as_bytes());
+        if let Some(list_id) = list_id {
+            mac.update(list_id.as_bytes());
+        }
+        mac
+    }
+}
+
+/// HMAC-SHA256 signature of a subscriber id, allowing the subscriber to manage their
+/// preferences without logging in. It is signed differently from `UnsubscribeToken`,
+/// so that an unsubscribe link cannot be turned into a preferences link.
+pub struct PreferencesToken(String);
+
+impl PreferencesToken {
+    const PURPOSE: &'static [u8] = b"preferences";
+
+    /// Returns an instance of `PreferencesToken` if the input looks like a hex-encoded signature.
+    /// It returns `ParseSignedTokenError` otherwise.
+    /// The token still needs to be checked against a subscriber with `verify`.
+    pub fn parse(s: &str) -> Result<Self, ParseSignedTokenError> {
+        parse_signature(s).map(Self)
+    }
+
+    /// Signs the subscriber id with the given secret.
+    pub fn generate(subscriber_id: Uuid, hmac_secret: &SecretString) -> Self {
+        Self(sign(Self::mac(subscriber_id, hmac_secret)))
+    }
+
+    /// Checks in constant time that the token was generated for the subscriber id.
+    pub fn verify(&self, subscriber_id: Uuid, hmac_secret: &SecretString) -> bool {
+        verify_signature(Self::mac(subscriber_id, hmac_secret), &self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn mac(subscriber_id: Uuid, hmac_secret: &SecretString) -> Hmac<Sha256> {
+        let mut mac = new_mac(hmac_secret);
+        mac.update(Self::PURPOSE);
+        mac.update(subscriber_id.as_bytes());
+        mac
+    }
+}
+
+/// Salted hash of the email of an erased subscriber, kept so that the address can be recognised
+/// without storing it. Emails are compared case-insensitively.
+pub struct ErasedEmailHash(String);
+
+impl ErasedEmailHash {
+    const PURPOSE: &'static [u8] = b"erasure";
+
+    pub fn compute(email: &str, hmac_secret: &SecretString) -> Self {
+        let mut mac = new_mac(hmac_secret);
+        mac.update(Self::PURPOSE);
+        mac.update(email.to_lowercase().as_bytes());
+        Self(sign(mac))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Length of a hex-encoded HMAC-SHA256 signature.
+const SIGNATURE_LENGTH: usize = 64;
+
+fn parse_signature(s: &str) -> Result<String, ParseSignedTokenError> {
+    if !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseSignedTokenError::NotHexadecimal);
+    }
+
+    if s.len() != SIGNATURE_LENGTH {
+        return Err(ParseSignedTokenError::InvalidLength);
+    }
+
+    Ok(s.to_lowercase())
+}
+
+fn new_mac(hmac_secret: &SecretString) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take key of any size")
+}
+
+fn sign(mac: Hmac<Sha256>) -> String {
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn verify_signature(mac: Hmac<Sha256>, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    mac.verify_slice(&signature).is_ok()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -101,4 +265,70 @@ mod test {
     fn valid_subscription_token_is_parsed_successfully() {
         assert!(SubscriptionToken::parse("vC8nGu4tq3DwcXu5rhLXa0Y7S").is_ok());
     }
+
+    fn hmac_secret() -> SecretString {
+        SecretString::new("secret".into())
+    }
+
+    #[test]
+    fn generated_unsubscribe_token_is_verified_for_same_subscriber() {
+        let subscriber_id = Uuid::new_v4();
+        let list_id = Some(Uuid::new_v4());
+        let token = UnsubscribeToken::generate(subscriber_id, list_id, &hmac_secret());
+        let token = UnsubscribeToken::parse(token.as_str()).unwrap();
+        assert!(token.verify(subscriber_id, list_id, &hmac_secret()));
+    }
+
+    #[test]
+    fn unsubscribe_token_is_rejected_for_other_subscriber() {
+        let token = UnsubscribeToken::generate(Uuid::new_v4(), None, &hmac_secret());
+        assert!(!token.verify(Uuid::new_v4(), None, &hmac_secret()));
+    }
+
+    #[test]
+    fn unsubscribe_token_is_rejected_for_other_list() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(subscriber_id, Some(Uuid::new_v4()), &hmac_secret());
+        assert!(!token.verify(subscriber_id, Some(Uuid::new_v4()), &hmac_secret()));
+        assert!(!token.verify(subscriber_id, None, &hmac_secret()));
+    }
+
+    #[test]
+    fn unsubscribe_token_is_rejected_for_other_secret() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(subscriber_id, None, &hmac_secret());
+        assert!(!token.verify(subscriber_id, None, &SecretString::new("other".into())));
+    }
+
+    #[test]
+    fn unsubscribe_token_is_not_a_valid_preferences_token() {
+        let subscriber_id = Uuid::new_v4();
+        let token = UnsubscribeToken::generate(subscriber_id, None, &hmac_secret());
+        let token = PreferencesToken::parse(token.as_str()).unwrap();
+        assert!(!token.verify(subscriber_id, &hmac_secret()));
+    }
+
+    #[test]
+    fn unsubscribe_token_that_is_not_hexadecimal_is_rejected() {
+        assert!(UnsubscribeToken::parse(&"z".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn unsubscribe_token_that_is_invalid_length_is_rejected() {
+        assert!(UnsubscribeToken::parse("abcdef").is_err());
+    }
+
+    #[test]
+    fn erased_email_hash_ignores_case_but_not_secret() {
+        let hash = ErasedEmailHash::compute("Ursula@Example.com", &hmac_secret());
+        assert_eq!(
+            hash.as_str(),
+            ErasedEmailHash::compute("ursula@example.com", &hmac_secret()).as_str()
+        );
+        assert_ne!(
+            hash.as_str(),
+            ErasedEmailHash::compute("ursula@example.com", &SecretString::new("other".into()))
+                .as_str()
+        );
+    }
 }
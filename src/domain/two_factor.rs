@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng, RngCore};
+use secrecy::{ExposeSecret, Secret, SecretString};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0} is not a valid base32 TOTP secret")]
+pub struct ParseTotpSecretError(String);
+
+/// Shared secret of a time-based one-time password (TOTP, RFC 6238) authenticator,
+/// producing 6-digit codes that change every 30 seconds.
+pub struct TotpSecret(Secret<Vec<u8>>);
+
+impl Clone for TotpSecret {
+    fn clone(&self) -> Self {
+        Self(Secret::new(self.0.expose_secret().clone()))
+    }
+}
+
+impl std::fmt::Debug for TotpSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TotpSecret([REDACTED])")
+    }
+}
+
+impl TotpSecret {
+    const SECRET_LENGTH: usize = 20;
+    const PERIOD_SECONDS: i64 = 30;
+    const DIGITS: u32 = 6;
+    const BASE32_ALPHABET: &'static [u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    /// Generate a random 160-bit secret, as recommended by RFC 4226.
+    pub fn generate() -> Self {
+        let mut bytes = vec![0; Self::SECRET_LENGTH];
+        thread_rng().fill_bytes(&mut bytes);
+        Self(Secret::new(bytes))
+    }
+
+    /// Returns an instance of `TotpSecret` from its unpadded base32 encoding.
+    /// It returns `ParseTotpSecretError` otherwise.
+    pub fn parse_base32(s: &str) -> Result<Self, ParseTotpSecretError> {
+        let mut bytes = Vec::with_capacity(s.len() * 5 / 8);
+        let (mut buffer, mut n_bits) = (0u32, 0);
+        for c in s.bytes() {
+            let value = Self::BASE32_ALPHABET
+                .iter()
+                .position(|&a| a == c.to_ascii_uppercase())
+                .ok_or_else(|| ParseTotpSecretError(s.to_string()))?;
+            buffer = (buffer << 5) | value as u32;
+            n_bits += 5;
+            if n_bits >= 8 {
+                n_bits -= 8;
+                bytes.push((buffer >> n_bits) as u8);
+            }
+        }
+
+        Ok(Self(Secret::new(bytes)))
+    }
+
+    /// Unpadded base32 encoding of the secret, which is how authenticator apps expect it.
+    pub fn expose_base32(&self) -> String {
+        let mut encoded = String::new();
+        let (mut buffer, mut n_bits) = (0u32, 0);
+        for &byte in self.0.expose_secret() {
+            buffer = (buffer << 8) | byte as u32;
+            n_bits += 8;
+            while n_bits >= 5 {
+                n_bits -= 5;
+                encoded.push(Self::BASE32_ALPHABET[((buffer >> n_bits) & 0x1f) as usize] as char);
+            }
+        }
+        if n_bits > 0 {
+            encoded.push(Self::BASE32_ALPHABET[((buffer << (5 - n_bits)) & 0x1f) as usize] as char);
+        }
+
+        encoded
+    }
+
+    /// URI to enroll the secret in an authenticator app, usually shown as a QR code.
+    pub fn otpauth_uri(&self, issuer: &str, account: &str) -> String {
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            urlencoding::encode(issuer),
+            urlencoding::encode(account),
+            self.expose_base32(),
+            urlencoding::encode(issuer),
+            Self::DIGITS,
+            Self::PERIOD_SECONDS
+        )
+    }
+
+    /// Index of the 30-second period containing `time`.
+    pub fn step_at(time: DateTime<Utc>) -> i64 {
+        time.timestamp().div_euclid(Self::PERIOD_SECONDS)
+    }
+
+    /// Code generated for the given period (HOTP, RFC 4226).
+    pub fn code_at(&self, step: i64) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(self.0.expose_secret())
+            .expect("HMAC can take key of any size");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        // Dynamic truncation
+        let offset = (hash[hash.len() - 1] & 0xf) as usize;
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fff_ffff;
+        format!(
+            "{:0width$}",
+            binary % 10u32.pow(Self::DIGITS),
+            width = Self::DIGITS as usize
+        )
+    }
+
+    /// Returns the period the code was generated for, if it matches the period containing `time`
+    /// or one of its neighbours, to allow for clock drift. It returns `None` otherwise.
+    pub fn verify(&self, code: &TotpCode, time: DateTime<Utc>) -> Option<i64> {
+        let step = Self::step_at(time);
+        (step - 1..=step + 1).find(|&s| self.code_at(s) == code.as_ref())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Authentication code must be 6 digits")]
+pub struct ParseTotpCodeError;
+
+/// Code entered by the user from their authenticator app.
+pub struct TotpCode(String);
+
+impl TotpCode {
+    /// Returns an instance of `TotpCode` if the input is 6 digits, ignoring whitespace.
+    /// It returns `ParseTotpCodeError` otherwise.
+    pub fn parse(s: &str) -> Result<Self, ParseTotpCodeError> {
+        let code: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+        if code.len() != TotpSecret::DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ParseTotpCodeError);
+        }
+
+        Ok(Self(code))
+    }
+}
+
+impl AsRef<str> for TotpCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Recovery code must be 10 letters or digits")]
+pub struct ParseRecoveryCodeError;
+
+/// Single-use code that lets a user log in without their authenticator app.
+/// Only its hash is stored.
+pub struct RecoveryCode(SecretString);
+
+impl RecoveryCode {
+    const CODE_LENGTH: usize = 10;
+    /// Number of codes handed out when two-factor authentication is enabled.
+    pub const BATCH_SIZE: usize = 10;
+
+    /// Returns an instance of `RecoveryCode`, ignoring case, dashes and whitespace.
+    /// It returns `ParseRecoveryCodeError` otherwise.
+    pub fn parse(s: &str) -> Result<Self, ParseRecoveryCodeError> {
+        let code: String = s
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if code.len() != Self::CODE_LENGTH || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(ParseRecoveryCodeError);
+        }
+
+        Ok(Self(SecretString::new(code)))
+    }
+
+    /// Generate a random 10-characters-long case-insensitive code.
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+        Self(SecretString::new(
+            std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .take(Self::CODE_LENGTH)
+                .collect(),
+        ))
+    }
+
+    /// Hex-encoded SHA-256 hash of the code, which is what gets stored.
+    pub fn hash(&self) -> String {
+        hex::encode(Sha256::digest(self.0.expose_secret().as_bytes()))
+    }
+
+    /// The code split in two halves, e.g. `abcde-12345`, to make it easier to copy down.
+    pub fn expose_formatted(&self) -> String {
+        let code = self.0.expose_secret();
+        format!("{}-{}", &code[..5], &code[5..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    // Test vectors from RFC 6238, truncated to 6 digits
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret(Secret::new(b"12345678901234567890".to_vec()))
+    }
+
+    #[test]
+    fn codes_match_rfc_6238_test_vectors() {
+        let secret = rfc_secret();
+        for (timestamp, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            let time = Utc.timestamp_opt(timestamp, 0).unwrap();
+            assert_eq!(secret.code_at(TotpSecret::step_at(time)), code);
+        }
+    }
+
+    #[test]
+    fn code_from_neighbouring_period_is_accepted() {
+        let secret = rfc_secret();
+        let time = Utc.timestamp_opt(1111111109, 0).unwrap();
+        let step = TotpSecret::step_at(time);
+        let code = TotpCode::parse(&secret.code_at(step - 1)).unwrap();
+        assert_eq!(secret.verify(&code, time), Some(step - 1));
+    }
+
+    #[test]
+    fn code_from_distant_period_is_rejected() {
+        let secret = rfc_secret();
+        let time = Utc.timestamp_opt(1111111109, 0).unwrap();
+        let code = TotpCode::parse(&secret.code_at(TotpSecret::step_at(time) - 2)).unwrap();
+        assert_eq!(secret.verify(&code, time), None);
+    }
+
+    #[test]
+    fn secret_round_trips_through_base32() {
+        assert_eq!(
+            rfc_secret().expose_base32(),
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"
+        );
+        let secret = TotpSecret::generate();
+        let parsed = TotpSecret::parse_base32(&secret.expose_base32()).unwrap();
+        assert_eq!(parsed.code_at(42), secret.code_at(42));
+    }
+
+    #[test]
+    fn invalid_base32_secret_is_rejected() {
+        assert!(TotpSecret::parse_base32("NOT-BASE32!").is_err());
+    }
+
+    #[test]
+    fn otpauth_uri_contains_secret_and_issuer() {
+        let secret = rfc_secret();
+        let uri = secret.otpauth_uri("Zero2Prod", "admin");
+        assert!(uri.starts_with("otpauth://totp/Zero2Prod:admin?"));
+        assert!(uri.contains("secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ"));
+    }
+
+    #[test]
+    fn totp_code_must_be_six_digits() {
+        assert!(TotpCode::parse("123 456").is_ok());
+        assert!(TotpCode::parse("12345").is_err());
+        assert!(TotpCode::parse("12345a").is_err());
+    }
+
+    #[test]
+    fn recovery_code_is_parsed_leniently() {
+        let code = RecoveryCode::generate();
+        let parsed = RecoveryCode::parse(&code.expose_formatted().to_uppercase()).unwrap();
+        assert_eq!(parsed.hash(), code.hash());
+        assert!(RecoveryCode::parse("too-short").is_err());
+    }
+}
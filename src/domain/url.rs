@@ -41,6 +41,10 @@ impl Url {
         self.as_ref()
     }
 
+    pub fn scheme(&self) -> &str {
+        self.0.scheme()
+    }
+
     pub fn host_str(&self) -> Option<&str> {
         self.0.host_str()
     }
@@ -0,0 +1,78 @@
+#[derive(Debug, thiserror::Error)]
+pub struct ParseUserRoleError(String);
+
+impl AsRef<str> for ParseUserRoleError {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ParseUserRoleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ref())
+    }
+}
+
+/// Role of an admin user. Variants are ordered from least to most privileged,
+/// and every role is granted everything the roles below it can do.
+#[derive(Debug, Clone, Copy, strum_macros::Display, PartialEq, Eq, PartialOrd, Ord)]
+#[strum(serialize_all = "snake_case")]
+pub enum UserRole {
+    /// Can see newsletter issues, drafts, deliveries and subscribers.
+    Viewer,
+    /// Can also write, publish and schedule newsletters, and manage subscribers.
+    Editor,
+    /// Can also manage users and API keys.
+    Owner,
+}
+
+impl UserRole {
+    pub const ALL: [UserRole; 3] = [Self::Viewer, Self::Editor, Self::Owner];
+
+    /// Returns `true` if this role grants everything `required` does.
+    pub fn includes(&self, required: UserRole) -> bool {
+        *self >= required
+    }
+}
+
+impl TryFrom<String> for UserRole {
+    type Error = ParseUserRoleError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Self::Viewer),
+            "editor" => Ok(Self::Editor),
+            "owner" => Ok(Self::Owner),
+            other => Err(ParseUserRoleError(format!(
+                "{} is not a valid user role",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UserRole;
+
+    #[test]
+    fn user_role_round_trips_through_string() {
+        for role in UserRole::ALL {
+            assert_eq!(UserRole::try_from(role.to_string()).unwrap(), role);
+        }
+    }
+
+    #[test]
+    fn unknown_user_role_is_rejected() {
+        assert!(UserRole::try_from("admin".to_string()).is_err());
+    }
+
+    #[test]
+    fn higher_roles_include_lower_roles() {
+        assert!(UserRole::Owner.includes(UserRole::Editor));
+        assert!(UserRole::Editor.includes(UserRole::Editor));
+        assert!(UserRole::Editor.includes(UserRole::Viewer));
+        assert!(!UserRole::Viewer.includes(UserRole::Editor));
+        assert!(!UserRole::Editor.includes(UserRole::Owner));
+    }
+}
@@ -1,85 +1,115 @@
-use reqwest::Client;
-use secrecy::{ExposeSecret, SecretString};
-use serde::Serialize;
+mod file;
+mod postmark;
+mod smtp;
+
+use std::sync::Arc;
+
 use thiserror::Error;
 
+pub use file::*;
+pub use postmark::*;
+pub use smtp::*;
+
 use crate::{
-    configuration::EmailClientSettings,
+    configuration::{EmailClientSettings, EmailTransportSettings},
     domain::{Email, ParseEmailError, ParseUrlError, Url},
 };
 
-pub struct EmailClient {
-    sender: Email,
-    http_client: Client,
-    base_url: Url,
-    authorization_token: SecretString,
-    timeout: std::time::Duration,
+/// Email to be sent to a single recipient, independent of the transport used.
+pub struct EmailMessage<'a> {
+    pub recipient: &'a Email,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub unsubscribe_link: Option<&'a Url>,
+}
+
+impl EmailMessage<'_> {
+    /// Extra headers to attach to the email.
+    /// Emails with an unsubscribe link get RFC 8058 one-click unsubscribe headers.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        match self.unsubscribe_link {
+            Some(link) => vec![
+                ("List-Unsubscribe", format!("<{}>", link)),
+                (
+                    "List-Unsubscribe-Post",
+                    "List-Unsubscribe=One-Click".to_string(),
+                ),
+            ],
+            None => vec![],
+        }
+    }
 }
 
-#[derive(Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct SendEmailRequest<'a> {
-    from: &'a str,
-    to: &'a str,
-    subject: &'a str,
-    html_body: &'a str,
-    text_body: &'a str,
+/// Details returned by the email provider after accepting a message.
+#[derive(Debug, Default)]
+pub struct EmailReceipt {
+    pub message_id: Option<String>,
 }
 
 #[derive(Debug, Error)]
 pub enum SendEmailError {
     #[error(transparent)]
     Request(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
+    #[error(transparent)]
+    InvalidAddress(#[from] lettre::address::AddressError),
+
+    #[error(transparent)]
+    InvalidMessage(#[from] lettre::error::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Serialize(#[from] serde_json::Error),
+
+    #[error("Email provider rejected the message with error code {code}: {message}")]
+    Rejected { code: i64, message: String },
+
+    #[error("Email provider returned {actual} results for a batch of {expected} messages")]
+    BatchResultMismatch { expected: usize, actual: usize },
+
+    #[error("Failed to send the batch containing this message")]
+    BatchFailed(#[source] Arc<SendEmailError>),
 }
 
-impl EmailClient {
-    pub fn new(
-        base_url: Url,
-        sender: Email,
-        authorization_token: SecretString,
-        timeout: std::time::Duration,
-    ) -> Self {
-        Self {
-            http_client: Client::new(),
-            base_url,
-            sender,
-            authorization_token,
-            timeout,
-        }
-    }
+/// Backend that delivers emails, e.g. an email provider API or an SMTP relay.
+#[async_trait::async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<EmailReceipt, SendEmailError>;
 
-    pub async fn send_email(
+    async fn send_email(
         &self,
         recipient: &Email,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), SendEmailError> {
-        let url = self.base_url.join("email").unwrap(); // safely unwrap since it's proper url
-        let request_body = SendEmailRequest {
-            from: self.sender.as_ref(),
-            to: recipient.as_ref(),
+    ) -> Result<EmailReceipt, SendEmailError> {
+        self.send(&EmailMessage {
+            recipient,
             subject,
-            html_body: html_content,
-            text_body: text_content,
-        };
-
-        let _ = self
-            .http_client
-            .post(url.to_string())
-            // Add Postmark token
-            .header(
-                "X-Postmark-Server-Token",
-                self.authorization_token.expose_secret(),
-            )
-            .json(&request_body)
-            .timeout(self.timeout)
-            .send()
-            .await?
-            // Return error status code
-            .error_for_status()?;
-
-        Ok(())
+            html_content,
+            text_content,
+            unsubscribe_link: None,
+        })
+        .await
+    }
+
+    /// Sends several emails at once, returning one result per message in the same order.
+    /// Transports without a batch API send the messages one after the other.
+    async fn send_batch(
+        &self,
+        messages: &[EmailMessage<'_>],
+    ) -> Vec<Result<EmailReceipt, SendEmailError>> {
+        let mut results = Vec::with_capacity(messages.len());
+        for message in messages {
+            results.push(self.send(message).await);
+        }
+        results
     }
 }
 
@@ -90,153 +120,49 @@ pub enum EmailClientError {
 
     #[error(transparent)]
     ParseUrl(#[from] ParseUrlError),
-}
 
-impl TryFrom<EmailClientSettings> for EmailClient {
-    type Error = EmailClientError;
+    #[error(transparent)]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
 
-    fn try_from(settings: EmailClientSettings) -> Result<Self, Self::Error> {
-        let sender = settings.sender()?;
-        let base_url = settings.url()?;
-        let timeout = settings.timeout();
+/// Builds the transport selected in the settings.
+pub fn build_email_transport(
+    settings: EmailClientSettings,
+) -> Result<Arc<dyn EmailTransport>, EmailClientError> {
+    let sender = settings.sender()?;
+    let timeout = settings.timeout();
 
-        Ok(EmailClient::new(
+    let transport: Arc<dyn EmailTransport> = match settings.transport {
+        EmailTransportSettings::Postmark {
             base_url,
+            authorization_token,
+        } => Arc::new(PostmarkTransport::new(
+            Url::parse(&base_url)?,
             sender,
-            settings.authorization_token,
+            authorization_token,
             timeout,
-        ))
-    }
-}
-
-#[cfg(test)]
-mod test {
-    use fake::faker::internet::en::SafeEmail;
-    use fake::faker::lorem::en::{Paragraph, Sentence};
-    use fake::{Fake, Faker};
-    use secrecy::Secret;
-    use wiremock::matchers;
-    use wiremock::{Mock, MockServer, ResponseTemplate};
-
-    use crate::domain::{Email, Url};
-    use crate::email_client::{EmailClient, SendEmailError};
-
-    struct SendEmailBodyMatcher;
-
-    impl wiremock::Match for SendEmailBodyMatcher {
-        fn matches(&self, request: &wiremock::Request) -> bool {
-            // Try to parse body as JSON value
-            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
-            if let Ok(body) = result {
-                body.get("From").is_some()
-                    && body.get("To").is_some()
-                    && body.get("Subject").is_some()
-                    && body.get("HtmlBody").is_some()
-                    && body.get("TextBody").is_some()
-            } else {
-                false
-            }
+        )),
+        EmailTransportSettings::Smtp {
+            host,
+            port,
+            starttls,
+            username,
+            password,
+        } => {
+            let credentials = username.zip(password);
+            Arc::new(SmtpTransport::new(
+                &host,
+                port,
+                starttls,
+                credentials,
+                sender,
+                timeout,
+            )?)
         }
-    }
-
-    async fn test_send_email_with_mock(mock_server: &MockServer) -> Result<(), SendEmailError> {
-        let sender = Email::parse(&SafeEmail().fake::<String>()).unwrap();
-        let base_url = Url::parse(&mock_server.uri()).unwrap();
-        // Initialize email client
-        let email_client = EmailClient::new(
-            base_url,
-            sender,
-            Secret::new(Faker.fake()),
-            std::time::Duration::from_millis(200),
-        );
-
-        // Generate random data
-        let subscriber_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
-        let subject: String = Sentence(1..2).fake();
-        let content: String = Paragraph(1..10).fake();
-
-        let outcome = email_client
-            .send_email(&subscriber_email, &subject, &content, &content)
-            .await;
-
-        outcome
-    }
-
-    #[tokio::test]
-    async fn send_email_fires_request_to_base_url() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        Mock::given(matchers::header_exists("X-Postmark-Server-Token"))
-            .and(matchers::header("Content-Type", "application/json"))
-            .and(matchers::path("/email"))
-            .and(matchers::method("POST"))
-            .and(SendEmailBodyMatcher)
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let _ = test_send_email_with_mock(&mock_server).await;
-
-        // Assert
-        // Mock expectations are checked on drop
-    }
-
-    #[tokio::test]
-    async fn send_email_succeeds_if_server_returns_200() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        // We do not copy in all the matchers we have in the other test.
-        // The purpose of this test is not to assert on the request we
-        // are sending out!
-        // We add the bare minimum needed to trigger the path we want
-        // to test in `send_email`.
-        Mock::given(matchers::any())
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let outcome = test_send_email_with_mock(&mock_server).await;
-
-        // Assert
-        assert!(outcome.is_ok());
-    }
-
-    #[tokio::test]
-    async fn send_email_fails_if_the_server_returns_500() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        Mock::given(matchers::any())
-            .respond_with(ResponseTemplate::new(500))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let outcome = test_send_email_with_mock(&mock_server).await;
-
-        // Assert
-        assert!(outcome.is_err());
-    }
+        EmailTransportSettings::File { directory } => {
+            Arc::new(FileTransport::new(directory.into(), sender))
+        }
+    };
 
-    #[tokio::test]
-    async fn send_email_times_out_if_server_takes_too_long() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        let responder = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(60));
-        Mock::given(matchers::any())
-            .respond_with(responder)
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let outcome = test_send_email_with_mock(&mock_server).await;
-
-        // Assert
-        assert!(outcome.is_err());
-    }
+    Ok(transport)
 }
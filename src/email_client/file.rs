@@ -0,0 +1,102 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use super::{EmailMessage, EmailReceipt, EmailTransport, SendEmailError};
+use crate::domain::Email;
+
+/// Writes emails as JSON files into a local directory instead of sending them.
+/// Useful for local development, where we do not want to reach a real email provider.
+pub struct FileTransport {
+    sender: Email,
+    directory: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SavedEmail<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    headers: Vec<(&'static str, String)>,
+}
+
+impl FileTransport {
+    pub fn new(directory: PathBuf, sender: Email) -> Self {
+        Self { sender, directory }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for FileTransport {
+    #[tracing::instrument(name = "Saving email to file", skip_all)]
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<EmailReceipt, SendEmailError> {
+        let email = SavedEmail {
+            from: self.sender.as_ref(),
+            to: message.recipient.as_ref(),
+            subject: message.subject,
+            html_body: message.html_content,
+            text_body: message.text_content,
+            headers: message.headers(),
+        };
+
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut buf = Vec::new();
+        let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+        email.serialize(&mut ser)?;
+
+        // Create directory if not exist
+        tokio::fs::create_dir_all(&self.directory).await?;
+
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Unexpected time error");
+        let file_name = format!("{}__{}.json", unix_time.as_nanos(), email.to);
+        tokio::fs::write(self.directory.join(&file_name), buf).await?;
+
+        Ok(EmailReceipt {
+            message_id: Some(file_name),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use super::FileTransport;
+    use crate::domain::Email;
+    use crate::email_client::EmailTransport;
+
+    #[tokio::test]
+    async fn send_email_writes_json_file_into_directory() {
+        // Arrange
+        let directory = std::env::temp_dir().join(format!("zero2prod-emails-{}", Uuid::new_v4()));
+        let transport = FileTransport::new(
+            directory.clone(),
+            Email::parse("sender@example.com").unwrap(),
+        );
+        let recipient = Email::parse("recipient@example.com").unwrap();
+
+        // Act
+        let receipt = transport
+            .send_email(&recipient, "Subject", "<p>HTML body</p>", "Text body")
+            .await
+            .unwrap();
+
+        // Assert
+        let file_name = receipt.message_id.unwrap();
+        let saved = std::fs::read_to_string(directory.join(&file_name)).unwrap();
+        let saved: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        assert_eq!(saved["To"], "recipient@example.com");
+        assert_eq!(saved["Subject"], "Subject");
+        assert_eq!(saved["TextBody"], "Text body");
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
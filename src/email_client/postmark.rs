@@ -0,0 +1,509 @@
+use std::sync::Arc;
+
+use reqwest::Client;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use super::{EmailMessage, EmailReceipt, EmailTransport, SendEmailError};
+use crate::domain::{Email, Url};
+
+/// Sends emails through Postmark's JSON API.
+pub struct PostmarkTransport {
+    sender: Email,
+    http_client: Client,
+    base_url: Url,
+    authorization_token: SecretString,
+    timeout: std::time::Duration,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<EmailHeader>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailHeader {
+    name: &'static str,
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: String,
+}
+
+/// Outcome of a single message within a batch, as reported by Postmark.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BatchMessageResponse {
+    error_code: i64,
+    message: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+impl PostmarkTransport {
+    pub fn new(
+        base_url: Url,
+        sender: Email,
+        authorization_token: SecretString,
+        timeout: std::time::Duration,
+    ) -> Self {
+        Self {
+            http_client: Client::new(),
+            base_url,
+            sender,
+            authorization_token,
+            timeout,
+        }
+    }
+
+    /// Maximum number of messages Postmark accepts in a single batch request.
+    pub const MAX_BATCH_SIZE: usize = 500;
+
+    fn request_body<'a>(&'a self, message: &'a EmailMessage<'_>) -> SendEmailRequest<'a> {
+        SendEmailRequest {
+            from: self.sender.as_ref(),
+            to: message.recipient.as_ref(),
+            subject: message.subject,
+            html_body: message.html_content,
+            text_body: message.text_content,
+            headers: message
+                .headers()
+                .into_iter()
+                .map(|(name, value)| EmailHeader { name, value })
+                .collect(),
+        }
+    }
+
+    async fn post_batch(
+        &self,
+        messages: &[EmailMessage<'_>],
+    ) -> Result<Vec<BatchMessageResponse>, SendEmailError> {
+        let url = self.base_url.join("email/batch").unwrap(); // safely unwrap since it's proper url
+        let request_body: Vec<_> = messages.iter().map(|m| self.request_body(m)).collect();
+
+        let responses = self
+            .http_client
+            .post(url.to_string())
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<BatchMessageResponse>>()
+            .await?;
+
+        if responses.len() != messages.len() {
+            return Err(SendEmailError::BatchResultMismatch {
+                expected: messages.len(),
+                actual: responses.len(),
+            });
+        }
+        Ok(responses)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for PostmarkTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<EmailReceipt, SendEmailError> {
+        let url = self.base_url.join("email").unwrap(); // safely unwrap since it's proper url
+        let request_body = self.request_body(message);
+
+        let response = self
+            .http_client
+            .post(url.to_string())
+            // Add Postmark token
+            .header(
+                "X-Postmark-Server-Token",
+                self.authorization_token.expose_secret(),
+            )
+            .json(&request_body)
+            .timeout(self.timeout)
+            .send()
+            .await?
+            // Return error status code
+            .error_for_status()?;
+
+        // The message ID is only informational, so a body we cannot parse is not an error
+        let message_id = response
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .map(|r| r.message_id);
+        Ok(EmailReceipt { message_id })
+    }
+
+    /// Sends the messages through Postmark's batch API, splitting them into requests
+    /// of at most `MAX_BATCH_SIZE` messages.
+    /// If a whole request fails, every message in it is reported as failed.
+    async fn send_batch(
+        &self,
+        messages: &[EmailMessage<'_>],
+    ) -> Vec<Result<EmailReceipt, SendEmailError>> {
+        let mut results = Vec::with_capacity(messages.len());
+        for chunk in messages.chunks(Self::MAX_BATCH_SIZE) {
+            match self.post_batch(chunk).await {
+                Ok(responses) => results.extend(responses.into_iter().map(|r| {
+                    // Postmark reports success with error code 0
+                    if r.error_code == 0 {
+                        Ok(EmailReceipt {
+                            message_id: r.message_id,
+                        })
+                    } else {
+                        Err(SendEmailError::Rejected {
+                            code: r.error_code,
+                            message: r.message,
+                        })
+                    }
+                })),
+                Err(e) => {
+                    let e = Arc::new(e);
+                    results.extend(
+                        chunk
+                            .iter()
+                            .map(|_| Err(SendEmailError::BatchFailed(Arc::clone(&e)))),
+                    );
+                }
+            }
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::matchers;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::PostmarkTransport;
+    use crate::domain::{Email, Url};
+    use crate::email_client::{EmailMessage, EmailReceipt, EmailTransport, SendEmailError};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            // Try to parse body as JSON value
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    async fn test_send_email_with_mock(
+        mock_server: &MockServer,
+    ) -> Result<EmailReceipt, SendEmailError> {
+        let sender = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let base_url = Url::parse(&mock_server.uri()).unwrap();
+        // Initialize Postmark transport
+        let email_client = PostmarkTransport::new(
+            base_url,
+            sender,
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+        );
+
+        // Generate random data
+        let subscriber_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let subject: String = Sentence(1..2).fake();
+        let content: String = Paragraph(1..10).fake();
+
+        let outcome = email_client
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+
+        outcome
+    }
+
+    #[tokio::test]
+    async fn send_email_fires_request_to_base_url() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::header_exists("X-Postmark-Server-Token"))
+            .and(matchers::header("Content-Type", "application/json"))
+            .and(matchers::path("/email"))
+            .and(matchers::method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = test_send_email_with_mock(&mock_server).await;
+
+        // Assert
+        // Mock expectations are checked on drop
+    }
+
+    #[tokio::test]
+    async fn send_email_succeeds_if_server_returns_200() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        // We do not copy in all the matchers we have in the other test.
+        // The purpose of this test is not to assert on the request we
+        // are sending out!
+        // We add the bare minimum needed to trigger the path we want
+        // to test in `send_email`.
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = test_send_email_with_mock(&mock_server).await;
+
+        // Assert
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_email_returns_message_id_from_server_response() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "To": "receiver@example.com",
+                "SubmittedAt": "2024-06-12T10:00:00.0000000Z",
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                "ErrorCode": 0,
+                "Message": "OK"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = test_send_email_with_mock(&mock_server).await;
+
+        // Assert
+        assert_eq!(
+            outcome.unwrap().message_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = test_send_email_with_mock(&mock_server).await;
+
+        // Assert
+        assert!(outcome.is_err());
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let responder = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(60));
+        Mock::given(matchers::any())
+            .respond_with(responder)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = test_send_email_with_mock(&mock_server).await;
+
+        // Assert
+        assert!(outcome.is_err());
+    }
+
+    fn postmark_transport(mock_server: &MockServer) -> PostmarkTransport {
+        PostmarkTransport::new(
+            Url::parse(&mock_server.uri()).unwrap(),
+            Email::parse(&SafeEmail().fake::<String>()).unwrap(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+        )
+    }
+
+    fn batch_message(recipient: &Email) -> EmailMessage<'_> {
+        EmailMessage {
+            recipient,
+            subject: "Subject",
+            html_content: "<p>Content</p>",
+            text_content: "Content",
+            unsubscribe_link: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_per_recipient_results() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::path("/email/batch"))
+            .and(matchers::method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {
+                    "ErrorCode": 0,
+                    "Message": "OK",
+                    "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                    "To": "first@example.com"
+                },
+                {
+                    "ErrorCode": 406,
+                    "Message": "You tried to send to a recipient that has been marked as inactive.",
+                    "To": "second@example.com"
+                }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let email_client = postmark_transport(&mock_server);
+        let recipients = [
+            Email::parse("first@example.com").unwrap(),
+            Email::parse("second@example.com").unwrap(),
+        ];
+        let messages: Vec<_> = recipients.iter().map(batch_message).collect();
+
+        // Act
+        let results = email_client.send_batch(&messages).await;
+
+        // Assert
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].as_ref().unwrap().message_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+        assert!(matches!(
+            results[1],
+            Err(SendEmailError::Rejected { code: 406, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn send_batch_sets_list_unsubscribe_headers() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::path("/email/batch"))
+            .and(matchers::body_partial_json(serde_json::json!([{
+                "Headers": [
+                    {
+                        "Name": "List-Unsubscribe",
+                        "Value": "<https://example.com/subscriptions/unsubscribe?token=abc>"
+                    },
+                    {
+                        "Name": "List-Unsubscribe-Post",
+                        "Value": "List-Unsubscribe=One-Click"
+                    }
+                ]
+            }])))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                    "ErrorCode": 0,
+                    "Message": "OK",
+                    "To": "first@example.com"
+                }])),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let email_client = postmark_transport(&mock_server);
+        let recipient = Email::parse("first@example.com").unwrap();
+        let unsubscribe_link =
+            Url::parse("https://example.com/subscriptions/unsubscribe?token=abc").unwrap();
+        let message = EmailMessage {
+            unsubscribe_link: Some(&unsubscribe_link),
+            ..batch_message(&recipient)
+        };
+
+        // Act
+        let results = email_client.send_batch(&[message]).await;
+
+        // Assert
+        assert!(results[0].is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_batch_fails_every_message_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let email_client = postmark_transport(&mock_server);
+        let recipients: Vec<_> = (0..3)
+            .map(|_| Email::parse(&SafeEmail().fake::<String>()).unwrap())
+            .collect();
+        let messages: Vec<_> = recipients.iter().map(batch_message).collect();
+
+        // Act
+        let results = email_client.send_batch(&messages).await;
+
+        // Assert
+        assert_eq!(results.len(), 3);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Err(SendEmailError::BatchFailed(_)))));
+    }
+
+    #[tokio::test]
+    async fn send_batch_splits_large_batches_into_several_requests() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(matchers::path("/email/batch"))
+            .respond_with(|request: &wiremock::Request| {
+                let messages: Vec<serde_json::Value> =
+                    serde_json::from_slice(&request.body).unwrap();
+                let results: Vec<_> = messages
+                    .iter()
+                    .map(|_| serde_json::json!({ "ErrorCode": 0, "Message": "OK" }))
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(results)
+            })
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let email_client = postmark_transport(&mock_server);
+        let recipient = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let messages: Vec<_> = (0..PostmarkTransport::MAX_BATCH_SIZE + 1)
+            .map(|_| batch_message(&recipient))
+            .collect();
+
+        // Act
+        let results = email_client.send_batch(&messages).await;
+
+        // Assert
+        assert_eq!(results.len(), PostmarkTransport::MAX_BATCH_SIZE + 1);
+        assert!(results.iter().all(Result::is_ok));
+    }
+}
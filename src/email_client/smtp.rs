@@ -0,0 +1,124 @@
+use lettre::{
+    message::{
+        header::{HeaderName, HeaderValue},
+        Mailbox, MultiPart,
+    },
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::{ExposeSecret, SecretString};
+
+use super::{EmailMessage, EmailReceipt, EmailTransport, SendEmailError};
+use crate::domain::Email;
+
+/// Sends emails through an SMTP relay.
+pub struct SmtpTransport {
+    sender: Email,
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    /// Creates a transport for the relay at `host`.
+    /// With `starttls` the connection must be upgraded to TLS before anything is sent,
+    /// otherwise the connection is unencrypted, which is only suitable for local relays.
+    /// Credentials are only sent if both username and password are given.
+    pub fn new(
+        host: &str,
+        port: u16,
+        starttls: bool,
+        credentials: Option<(String, SecretString)>,
+        sender: Email,
+        timeout: std::time::Duration,
+    ) -> Result<Self, lettre::transport::smtp::Error> {
+        let mut builder = if starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        }
+        .port(port)
+        .timeout(Some(timeout));
+
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_string(),
+            ));
+        }
+
+        Ok(Self {
+            sender,
+            mailer: builder.build(),
+        })
+    }
+}
+
+/// Builds a multipart MIME message with both text and HTML bodies.
+fn build_message(sender: &Email, message: &EmailMessage<'_>) -> Result<Message, SendEmailError> {
+    let mut email = Message::builder()
+        .from(sender.as_ref().parse::<Mailbox>()?)
+        .to(message.recipient.as_ref().parse::<Mailbox>()?)
+        .subject(message.subject)
+        .message_id(None)
+        .multipart(MultiPart::alternative_plain_html(
+            message.text_content.to_string(),
+            message.html_content.to_string(),
+        ))?;
+
+    for (name, value) in message.headers() {
+        email.headers_mut().insert_raw(HeaderValue::new(
+            HeaderName::new_from_ascii_str(name),
+            value,
+        ));
+    }
+
+    Ok(email)
+}
+
+#[async_trait::async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: &EmailMessage<'_>) -> Result<EmailReceipt, SendEmailError> {
+        let email = build_message(&self.sender, message)?;
+        let message_id = email.headers().get_raw("Message-ID").map(str::to_string);
+
+        self.mailer.send(email).await?;
+
+        Ok(EmailReceipt { message_id })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::domain::{Email, Url};
+    use crate::email_client::EmailMessage;
+
+    use super::build_message;
+
+    #[test]
+    fn message_contains_both_bodies_and_unsubscribe_headers() {
+        let sender = Email::parse("sender@example.com").unwrap();
+        let recipient = Email::parse("recipient@example.com").unwrap();
+        let link = Url::parse("https://example.com/subscriptions/unsubscribe").unwrap();
+
+        let email = build_message(
+            &sender,
+            &EmailMessage {
+                recipient: &recipient,
+                subject: "Subject",
+                html_content: "<p>HTML body</p>",
+                text_content: "Text body",
+                unsubscribe_link: Some(&link),
+            },
+        )
+        .unwrap();
+
+        let formatted = String::from_utf8(email.formatted()).unwrap();
+        assert!(formatted.contains("To: recipient@example.com"));
+        assert!(formatted.contains("<p>HTML body</p>"));
+        assert!(formatted.contains("Text body"));
+        assert!(
+            formatted.contains("List-Unsubscribe: <https://example.com/subscriptions/unsubscribe>")
+        );
+        assert!(formatted.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(email.headers().get_raw("Message-ID").is_some());
+    }
+}
@@ -52,6 +52,7 @@ pub async fn get_saved_response(
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 pub enum NextAction {
     StartProcessing(Transaction<'static, Postgres>),
     ReturnSavedResponse(Response),
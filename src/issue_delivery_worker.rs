@@ -1,38 +1,155 @@
-use std::time::Duration;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use sqlx::{Executor, PgPool, Postgres, Transaction};
-use tracing::{field::display, Span};
+use anyhow::Context;
+use chrono::Utc;
+use secrecy::SecretString;
+use sqlx::{postgres::PgListener, Executor, PgPool, Postgres, Transaction};
+use tokio::{sync::Notify, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+use tracing::Span;
 use uuid::Uuid;
 
-use crate::{configuration::Settings, domain::Email, email_client::EmailClient};
+use crate::{
+    configuration::{Settings, WorkerSettings},
+    database::{list_db::MailingList, newsletter_db},
+    domain::{DeliveryStatus, Email, Name, SubscriptionStatus, SubscriptionToken, Url},
+    email_client::{
+        build_email_transport, EmailMessage, EmailReceipt, EmailTransport, SendEmailError,
+    },
+    routes::{preferences_link, send_confirmation_email, unsubscribe_link},
+    telemetry,
+};
 
+/// Runs `worker.concurrency` workers against the delivery queue until `shutdown` is cancelled.
+/// Workers finish the batch they are working on before stopping.
 pub async fn run_worker_until_stopped(
     settings: Settings,
     overwrite_db_pool: Option<sqlx::PgPool>,
+    shutdown: CancellationToken,
 ) -> Result<(), anyhow::Error> {
     let db_pool = match overwrite_db_pool {
         Some(p) => p,
         None => PgPool::connect_lazy_with(settings.database.with_db()),
     };
 
-    let email_client = settings
-        .email_client
-        .try_into()
-        .expect("Failed to initialize email client");
+    let email_client =
+        build_email_transport(settings.email_client).expect("Failed to initialize email client");
+    let app_base_url = settings
+        .application
+        .base_url()
+        .expect("Failed to parse application base url");
 
-    worker_loop(db_pool, email_client).await
+    let new_tasks = Arc::new(Notify::new());
+    let mut workers = JoinSet::new();
+    workers.spawn(listen_for_new_tasks(
+        db_pool.clone(),
+        new_tasks.clone(),
+        shutdown.clone(),
+    ));
+    for _ in 0..settings.worker.concurrency.max(1) {
+        workers.spawn(worker_loop(
+            db_pool.clone(),
+            email_client.clone(),
+            settings.worker.clone(),
+            app_base_url.clone(),
+            settings.application.hmac_secret.clone(),
+            new_tasks.clone(),
+            shutdown.clone(),
+        ));
+    }
+    while let Some(outcome) = workers.join_next().await {
+        outcome?;
+    }
+
+    Ok(())
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
-        match try_execute_task(&pool, &email_client).await {
-            Ok(ExecutionOutcome::TaskCompleted) => {}
-            Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+async fn worker_loop(
+    pool: PgPool,
+    email_client: Arc<dyn EmailTransport>,
+    worker_settings: WorkerSettings,
+    app_base_url: Url,
+    hmac_secret: SecretString,
+    new_tasks: Arc<Notify>,
+    shutdown: CancellationToken,
+) {
+    while !shutdown.is_cancelled() {
+        // Start listening before checking the queue, so tasks enqueued meanwhile are not missed
+        let notified = new_tasks.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        let deliveries = try_execute_task(
+            &pool,
+            email_client.as_ref(),
+            &worker_settings,
+            &app_base_url,
+            &hmac_secret,
+        )
+        .await;
+        let confirmations = try_send_confirmation_emails(
+            &pool,
+            email_client.as_ref(),
+            &worker_settings,
+            &app_base_url,
+        )
+        .await;
+        let idle_time = match (deliveries, confirmations) {
+            (Ok(ExecutionOutcome::TaskCompleted), _) | (_, Ok(ExecutionOutcome::TaskCompleted)) => {
+                continue
             }
-            Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+            (Ok(ExecutionOutcome::EmptyQueue), Ok(ExecutionOutcome::EmptyQueue)) => {
+                Duration::from_secs(10)
+            }
+            _ => Duration::from_secs(1),
+        };
+
+        // Polling remains as a fallback in case a notification is lost
+        tokio::select! {
+            _ = tokio::time::sleep(idle_time) => {}
+            _ = notified => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+}
+
+/// Wakes idle workers whenever new tasks are enqueued, as announced through `NOTIFY`.
+/// If the listener connection fails, workers keep polling until it is re-established.
+async fn listen_for_new_tasks(pool: PgPool, new_tasks: Arc<Notify>, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        if let Err(e) = forward_notifications(&pool, &new_tasks, &shutdown).await {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Delivery queue listener failed, falling back to polling",
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(10)) => {}
+                _ = shutdown.cancelled() => {}
+            }
+        }
+    }
+}
+
+async fn forward_notifications(
+    pool: &PgPool,
+    new_tasks: &Notify,
+    shutdown: &CancellationToken,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener
+        .listen(newsletter_db::DELIVERY_QUEUE_CHANNEL)
+        .await?;
+
+    loop {
+        tokio::select! {
+            notification = listener.try_recv() => {
+                // `None` means the connection was lost and has been re-established,
+                // so wake workers up in case a notification was missed in between
+                let _ = notification?;
+                new_tasks.notify_waiters();
             }
+            _ = shutdown.cancelled() => return Ok(()),
         }
     }
 }
@@ -42,93 +159,222 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
-#[tracing::instrument(
-    skip_all,
-    fields(
-        newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty
-    ),
-    err
-)]
+/// Dequeues up to `batch_size` pending deliveries and sends them as a single batch.
+/// The provider's result for each recipient decides whether its delivery is completed,
+/// retried later or moved to `failed_deliveries`.
+#[tracing::instrument(skip_all, fields(n_tasks=tracing::field::Empty), err)]
 pub async fn try_execute_task(
     pool: &PgPool,
-    email_client: &EmailClient,
+    email_client: &dyn EmailTransport,
+    worker_settings: &WorkerSettings,
+    app_base_url: &Url,
+    hmac_secret: &SecretString,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    if task.is_none() {
+    let mut transaction = pool.begin().await?;
+    let tasks = dequeue_tasks(&mut transaction, worker_settings.batch_size).await?;
+    if tasks.is_empty() {
         return Ok(ExecutionOutcome::EmptyQueue);
     }
+    Span::current().record("n_tasks", tasks.len());
+
+    let recipients = get_confirmed_recipients(pool, &tasks).await?;
+    let issues = get_issues(pool, &tasks).await?;
 
-    let (transaction, issue_id, email) = task.unwrap();
-    Span::current()
-        .record("newsletter_issue_id", &display(issue_id))
-        .record("subscriber_email", &display(&email));
-
-    match Email::parse(&email) {
-        Ok(email) => {
-            let issue = get_issue(pool, issue_id).await?;
-            if let Err(e) = email_client
-                .send_email(
-                    &email,
-                    &issue.title,
-                    &issue.html_content,
-                    &issue.text_content,
-                )
-                .await
-            {
+    let mut deliveries = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        // Subscribers may have left the list between the issue being published and delivered
+        let Some(recipient) =
+            recipients.get(&(task.newsletter_issue_id, task.subscriber_email.clone()))
+        else {
+            tracing::info!(
+                subscriber_email = %task.subscriber_email,
+                "Subscriber is no longer confirmed on the list, skipping"
+            );
+            delete_task(&mut transaction, &task).await?;
+            continue;
+        };
+
+        match Email::parse(&task.subscriber_email) {
+            Ok(email) => {
+                let issue = issues
+                    .get(&task.newsletter_issue_id)
+                    .context("Queued delivery refers to an unknown newsletter issue")?;
+                let unsubscribe_link = unsubscribe_link(
+                    app_base_url,
+                    recipient.subscriber_id,
+                    recipient.list_id,
+                    hmac_secret,
+                );
+                let preferences_link =
+                    preferences_link(app_base_url, recipient.subscriber_id, hmac_secret);
+                let (html_content, text_content) =
+                    issue.with_footer_links(&unsubscribe_link, &preferences_link);
+                deliveries.push(Delivery {
+                    task,
+                    email,
+                    subject: &issue.title,
+                    html_content,
+                    text_content,
+                    unsubscribe_link,
+                });
+            }
+            Err(e) => {
                 tracing::error!(
                     error.cause_chain = ?e,
                     error.message = %e,
-                    "Failed to delivery issue to confirmed subscriber, skipping",
+                    subscriber_email = %task.subscriber_email,
+                    "Stored subscriber contact details are invalid, skipping",
                 );
+                record_delivery(&mut transaction, &task, DeliveryStatus::Failed, None).await?;
+                dead_letter_task(&mut transaction, &task, &telemetry::error_chain_string(&e))
+                    .await?;
             }
         }
+    }
+
+    let messages: Vec<_> = deliveries.iter().map(Delivery::message).collect();
+    let results = email_client.send_batch(&messages).await;
+    for (delivery, result) in deliveries.iter().zip(results) {
+        handle_delivery_result(&mut transaction, &delivery.task, result, worker_settings).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// A dequeued task together with the email to be sent for it.
+struct Delivery<'a> {
+    task: Task,
+    email: Email,
+    subject: &'a str,
+    html_content: String,
+    text_content: String,
+    unsubscribe_link: Url,
+}
+
+impl Delivery<'_> {
+    fn message(&self) -> EmailMessage<'_> {
+        EmailMessage {
+            recipient: &self.email,
+            subject: self.subject,
+            html_content: &self.html_content,
+            text_content: &self.text_content,
+            unsubscribe_link: Some(&self.unsubscribe_link),
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(
+        newsletter_issue_id=%task.newsletter_issue_id,
+        subscriber_email=%task.subscriber_email,
+        n_retries=task.n_retries
+    )
+)]
+async fn handle_delivery_result(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    result: Result<EmailReceipt, SendEmailError>,
+    worker_settings: &WorkerSettings,
+) -> Result<(), anyhow::Error> {
+    match result {
+        Ok(receipt) => {
+            record_delivery(
+                transaction,
+                task,
+                DeliveryStatus::Sent,
+                receipt.message_id.as_deref(),
+            )
+            .await?;
+            delete_task(transaction, task).await?;
+        }
+        Err(e) if task.n_retries < i32::from(worker_settings.max_retries) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver issue to confirmed subscriber, retrying later",
+            );
+            let delay = retry_delay(worker_settings.retry_base_delay(), task.n_retries);
+            retry_task(transaction, task, delay).await?;
+        }
         Err(e) => {
             tracing::error!(
                 error.cause_chain = ?e,
                 error.message = %e,
-                "Stored subscriber contact details are invalid, skipping",
+                "Failed to deliver issue to confirmed subscriber, retries exhausted",
             );
+            record_delivery(transaction, task, DeliveryStatus::Failed, None).await?;
+            dead_letter_task(transaction, task, &telemetry::error_chain_string(&e)).await?;
         }
     }
+    Ok(())
+}
 
-    delete_task(transaction, issue_id, &email).await?;
-    Ok(ExecutionOutcome::TaskCompleted)
+/// Computes how long to wait before the next delivery attempt.
+/// The delay doubles with every retry, starting from `base_delay`.
+fn retry_delay(base_delay: Duration, n_retries: i32) -> Duration {
+    let exponent = n_retries.clamp(0, 31) as u32;
+    base_delay.saturating_mul(2u32.saturating_pow(exponent))
 }
 
-#[tracing::instrument(skip_all)]
-async fn dequeue_task(
-    pool: &PgPool,
-) -> Result<Option<(Transaction<'static, Postgres>, Uuid, String)>, anyhow::Error> {
-    let mut transaction = pool.begin().await?;
-    let r = sqlx::query!(
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn dequeue_tasks(
+    transaction: &mut Transaction<'static, Postgres>,
+    batch_size: u16,
+) -> Result<Vec<Task>, anyhow::Error> {
+    let tasks = sqlx::query_as!(
+        Task,
         r#"
-        SELECT newsletter_issue_id, subscriber_email
+        SELECT newsletter_issue_id, subscriber_email, n_retries
         FROM issue_delivery_queue
+        WHERE execute_after <= now()
         FOR UPDATE
         SKIP LOCKED
-        LIMIT 1
+        LIMIT $1
         "#,
+        i64::from(batch_size)
     )
-    .fetch_optional(&mut *transaction)
+    .fetch_all(&mut **transaction)
     .await?;
 
-    if let Some(r) = r {
-        Ok(Some((
-            transaction,
-            r.newsletter_issue_id,
-            r.subscriber_email,
-        )))
-    } else {
-        Ok(None)
-    }
+    Ok(tasks)
+}
+
+#[tracing::instrument(skip_all)]
+async fn retry_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE issue_delivery_queue
+        SET
+            n_retries = n_retries + 1,
+            execute_after = now() + make_interval(secs => $3)
+        WHERE
+        newsletter_issue_id = $1 AND
+        subscriber_email = $2
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        delay.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    Ok(())
 }
 
 #[tracing::instrument(skip_all)]
 async fn delete_task(
-    mut transaction: Transaction<'static, Postgres>,
-    issue_id: Uuid,
-    email: &str,
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
 ) -> Result<(), anyhow::Error> {
     let query = sqlx::query!(
         r#"
@@ -137,34 +383,392 @@ async fn delete_task(
         newsletter_issue_id = $1 AND
         subscriber_email = $2
         "#,
-        issue_id,
-        email
+        task.newsletter_issue_id,
+        task.subscriber_email
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Logs the outcome of a delivery in `newsletter_delivery`, overwriting any earlier outcome.
+#[tracing::instrument(skip_all)]
+async fn record_delivery(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    status: DeliveryStatus,
+    provider_message_id: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let sent_at = (status == DeliveryStatus::Sent).then(Utc::now);
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO newsletter_delivery (
+            newsletter_issue_id,
+            subscriber_email,
+            status,
+            sent_at,
+            provider_message_id
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            status = EXCLUDED.status,
+            sent_at = EXCLUDED.sent_at,
+            provider_message_id = EXCLUDED.provider_message_id
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        status.to_string(),
+        sent_at,
+        provider_message_id
     );
     transaction.execute(query).await?;
+    Ok(())
+}
+
+/// Moves a task that cannot be delivered out of the queue and into `failed_deliveries`,
+/// so that it can be inspected and requeued or discarded by an admin.
+/// Requeuing removes the failed delivery, so attempts are counted from the requeue on.
+#[tracing::instrument(skip_all)]
+async fn dead_letter_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    last_error: &str,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        INSERT INTO failed_deliveries (
+            newsletter_issue_id,
+            subscriber_email,
+            last_error,
+            n_attempts,
+            failed_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+        SET
+            last_error = EXCLUDED.last_error,
+            n_attempts = EXCLUDED.n_attempts,
+            failed_at = EXCLUDED.failed_at
+        "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        last_error,
+        task.n_retries + 1
+    );
+    transaction.execute(query).await?;
+    delete_task(transaction, task).await
+}
+
+/// Sends up to `batch_size` queued confirmation emails, with the latest link of each subscriber.
+/// Failed emails are retried later, until the retries are exhausted and admins have to resend them.
+#[tracing::instrument(skip_all, fields(n_tasks=tracing::field::Empty), err)]
+pub async fn try_send_confirmation_emails(
+    pool: &PgPool,
+    email_client: &dyn EmailTransport,
+    worker_settings: &WorkerSettings,
+    app_base_url: &Url,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let tasks = dequeue_confirmation_tasks(&mut transaction, worker_settings.batch_size).await?;
+    if tasks.is_empty() {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    }
+    Span::current().record("n_tasks", tasks.len());
+
+    for task in tasks {
+        // The subscription was confirmed or the subscriber purged since the email was queued
+        let Some(token) = &task.subscription_token else {
+            delete_confirmation_task(&mut transaction, &task).await?;
+            continue;
+        };
+        let result = async {
+            let list = MailingList {
+                list_id: task.list_id,
+                slug: task.list_slug.clone(),
+                name: task.list_name.clone(),
+            };
+            send_confirmation_email(
+                email_client,
+                &Name::parse(&task.name)?,
+                &Email::parse(&task.email)?,
+                &list,
+                app_base_url,
+                &SubscriptionToken::parse(token)?,
+            )
+            .await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => delete_confirmation_task(&mut transaction, &task).await?,
+            Err(e) if task.n_retries < i32::from(worker_settings.max_retries) => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    subscriber_id = %task.subscriber_id,
+                    "Failed to send confirmation email, retrying later",
+                );
+                let delay = retry_delay(worker_settings.retry_base_delay(), task.n_retries);
+                retry_confirmation_task(&mut transaction, &task, delay).await?;
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    subscriber_id = %task.subscriber_id,
+                    "Failed to send confirmation email, retries exhausted",
+                );
+                delete_confirmation_task(&mut transaction, &task).await?;
+            }
+        }
+    }
     transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct ConfirmationTask {
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    n_retries: i32,
+    name: String,
+    email: String,
+    list_slug: String,
+    list_name: String,
+    subscription_token: Option<String>,
+}
+
+#[tracing::instrument(skip(transaction))]
+async fn dequeue_confirmation_tasks(
+    transaction: &mut Transaction<'static, Postgres>,
+    batch_size: u16,
+) -> Result<Vec<ConfirmationTask>, anyhow::Error> {
+    let tasks = sqlx::query_as!(
+        ConfirmationTask,
+        r#"
+        SELECT
+            q.subscriber_id,
+            q.list_id,
+            q.n_retries,
+            s.name,
+            s.email,
+            l.slug AS list_slug,
+            l.name AS list_name,
+            t.subscription_token AS "subscription_token?"
+        FROM confirmation_email_queue q
+        JOIN subscriptions s ON s.id = q.subscriber_id
+        JOIN lists l ON l.list_id = q.list_id
+        LEFT JOIN LATERAL (
+            SELECT subscription_token
+            FROM subscription_tokens
+            WHERE subscriber_id = q.subscriber_id AND list_id = q.list_id
+            ORDER BY created_at DESC
+            LIMIT 1
+        ) t ON true
+        WHERE q.execute_after <= now()
+        FOR UPDATE OF q
+        SKIP LOCKED
+        LIMIT $1
+        "#,
+        i64::from(batch_size)
+    )
+    .fetch_all(&mut **transaction)
+    .await?;
+
+    Ok(tasks)
+}
+
+#[tracing::instrument(skip_all)]
+async fn retry_confirmation_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &ConfirmationTask,
+    delay: Duration,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        UPDATE confirmation_email_queue
+        SET
+            n_retries = n_retries + 1,
+            execute_after = now() + make_interval(secs => $3)
+        WHERE
+        subscriber_id = $1 AND
+        list_id = $2
+        "#,
+        task.subscriber_id,
+        task.list_id,
+        delay.as_secs_f64()
+    );
+    transaction.execute(query).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_confirmation_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &ConfirmationTask,
+) -> Result<(), anyhow::Error> {
+    let query = sqlx::query!(
+        r#"
+        DELETE FROM confirmation_email_queue
+        WHERE
+        subscriber_id = $1 AND
+        list_id = $2
+        "#,
+        task.subscriber_id,
+        task.list_id
+    );
+    transaction.execute(query).await?;
     Ok(())
 }
 
-#[allow(dead_code)]
 struct NewsletterIssue {
     title: String,
     text_content: String,
     html_content: String,
 }
 
+impl NewsletterIssue {
+    /// Appends the unsubscribe and preferences links to both the HTML and text content of the issue.
+    fn with_footer_links(
+        &self,
+        unsubscribe_link: &Url,
+        preferences_link: &Url,
+    ) -> (String, String) {
+        let html_content = format!(
+            "{}<p><a href=\"{}\">Unsubscribe</a> from this newsletter or \
+            <a href=\"{}\">manage your preferences</a>.</p>",
+            self.html_content,
+            unsubscribe_link.as_str().replace('&', "&amp;"),
+            preferences_link.as_str().replace('&', "&amp;")
+        );
+        let text_content = format!(
+            "{}\n\nManage your preferences: {}\nUnsubscribe from this newsletter: {}",
+            self.text_content, preferences_link, unsubscribe_link
+        );
+        (html_content, text_content)
+    }
+}
+
+/// Subscriber a task is delivered to, along with the list of the task's issue.
+struct Recipient {
+    subscriber_id: Uuid,
+    list_id: Uuid,
+}
+
+/// Maps the issue and email of every task whose subscriber is still confirmed on the list of the
+/// task's issue to the recipient.
 #[tracing::instrument(skip_all)]
-async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, anyhow::Error> {
-    let issue = sqlx::query_as!(
-        NewsletterIssue,
+async fn get_confirmed_recipients(
+    pool: &PgPool,
+    tasks: &[Task],
+) -> Result<HashMap<(Uuid, String), Recipient>, anyhow::Error> {
+    let issue_ids: Vec<_> = tasks.iter().map(|t| t.newsletter_issue_id).collect();
+    let emails: Vec<_> = tasks.iter().map(|t| t.subscriber_email.clone()).collect();
+    let rows = sqlx::query!(
         r#"
-        SELECT title, text_content, html_content
+        SELECT
+            t.newsletter_issue_id AS "newsletter_issue_id!",
+            s.email,
+            s.id AS subscriber_id,
+            ls.list_id
+        FROM UNNEST($1::uuid[], $2::text[]) AS t(newsletter_issue_id, subscriber_email)
+        JOIN newsletter_issues n ON n.newsletter_issue_id = t.newsletter_issue_id
+        JOIN subscriptions s ON s.email = t.subscriber_email
+        JOIN list_subscriptions ls ON ls.subscriber_id = s.id AND ls.list_id = n.list_id
+        WHERE ls.status = $3
+        "#,
+        &issue_ids,
+        &emails,
+        SubscriptionStatus::Confirmed.to_string()
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let recipient = Recipient {
+                subscriber_id: r.subscriber_id,
+                list_id: r.list_id,
+            };
+            ((r.newsletter_issue_id, r.email), recipient)
+        })
+        .collect())
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issues(
+    pool: &PgPool,
+    tasks: &[Task],
+) -> Result<HashMap<Uuid, NewsletterIssue>, anyhow::Error> {
+    let issue_ids: Vec<_> = tasks.iter().map(|t| t.newsletter_issue_id).collect();
+    let rows = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content
         FROM newsletter_issues
         WHERE
-        newsletter_issue_id = $1
+        newsletter_issue_id = ANY($1)
         "#,
-        issue_id
+        &issue_ids
     )
-    .fetch_one(pool)
+    .fetch_all(pool)
     .await?;
-    Ok(issue)
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let issue = NewsletterIssue {
+                title: r.title,
+                text_content: r.text_content,
+                html_content: r.html_content,
+            };
+            (r.newsletter_issue_id, issue)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{retry_delay, NewsletterIssue};
+    use crate::domain::Url;
+
+    #[test]
+    fn footer_links_are_appended_to_both_contents() {
+        let issue = NewsletterIssue {
+            title: "Title".into(),
+            text_content: "Text".into(),
+            html_content: "<p>HTML</p>".into(),
+        };
+        let link = Url::parse("https://example.com/subscriptions/unsubscribe").unwrap();
+        let preferences_link = Url::parse("https://example.com/subscriptions/preferences").unwrap();
+
+        let (html_content, text_content) = issue.with_footer_links(&link, &preferences_link);
+
+        assert!(html_content.starts_with("<p>HTML</p>"));
+        assert!(html_content.contains(r#"href="https://example.com/subscriptions/unsubscribe""#));
+        assert!(text_content.starts_with("Text"));
+        assert!(text_content.contains("https://example.com/subscriptions/unsubscribe"));
+        assert!(html_content.contains(r#"href="https://example.com/subscriptions/preferences""#));
+        assert!(text_content.contains("https://example.com/subscriptions/preferences"));
+    }
+
+    #[test]
+    fn first_retry_waits_for_base_delay() {
+        let base = Duration::from_secs(10);
+        assert_eq!(retry_delay(base, 0), base);
+    }
+
+    #[test]
+    fn retry_delay_doubles_on_every_attempt() {
+        let base = Duration::from_secs(10);
+        assert_eq!(retry_delay(base, 1), Duration::from_secs(20));
+        assert_eq!(retry_delay(base, 3), Duration::from_secs(80));
+    }
+
+    #[test]
+    fn retry_delay_saturates_instead_of_overflowing() {
+        let base = Duration::from_secs(10);
+        assert_eq!(retry_delay(base, i32::MAX), retry_delay(base, 31));
+    }
 }
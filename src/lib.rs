@@ -5,9 +5,12 @@ pub mod domain;
 pub mod email_client;
 pub mod idempotency;
 pub mod issue_delivery_worker;
+pub mod newsletter_scheduler;
+pub mod rate_limit;
 pub mod routes;
 pub mod session_state;
 pub mod startup;
+pub mod subscription_purger;
 pub mod telemetry;
 pub mod template;
 
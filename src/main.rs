@@ -1,7 +1,10 @@
-use tokio::task::JoinError;
+use tokio::task::{JoinError, JoinHandle};
+use tokio_util::sync::CancellationToken;
 use zero2prod::configuration::get_configuration;
 use zero2prod::issue_delivery_worker::run_worker_until_stopped;
+use zero2prod::newsletter_scheduler::run_scheduler_until_stopped;
 use zero2prod::startup::Application;
+use zero2prod::subscription_purger::run_purger_until_stopped;
 use zero2prod::telemetry;
 
 #[tokio::main]
@@ -14,18 +17,72 @@ async fn main() -> Result<(), std::io::Error> {
     telemetry::init_subscriber(subscriber);
 
     let settings = get_configuration().expect("Failed to read configuration.");
+    let shutdown = CancellationToken::new();
+    tokio::spawn(cancel_on_shutdown_signal(shutdown.clone()));
+
     let app = Application::build(&settings).await;
-    let app_task = tokio::spawn(app.serve());
-    let worker_task = tokio::spawn(run_worker_until_stopped(settings, None));
+    let app_task = tokio::spawn(app.serve(shutdown.clone()));
+    let scheduler_task = tokio::spawn(run_scheduler_until_stopped(
+        settings.clone(),
+        None,
+        shutdown.clone(),
+    ));
+    let purger_task = tokio::spawn(run_purger_until_stopped(
+        settings.clone(),
+        None,
+        shutdown.clone(),
+    ));
+    let worker_task = tokio::spawn(run_worker_until_stopped(settings, None, shutdown.clone()));
 
-    tokio::select! {
-        o = app_task => report_exit("API", o),
-        o = worker_task => report_exit("Background worker", o),
-    };
+    // Wait for every task to drain, so in-flight requests and deliveries are not cut off
+    tokio::join!(
+        wait_for_exit("API", app_task, &shutdown),
+        wait_for_exit("Background worker", worker_task, &shutdown),
+        wait_for_exit("Newsletter scheduler", scheduler_task, &shutdown),
+        wait_for_exit("Subscription purger", purger_task, &shutdown),
+    );
 
     Ok(())
 }
 
+/// Cancels `shutdown` on ctrl-c or SIGTERM.
+async fn cancel_on_shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install ctrl-c handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutdown signal received, finishing in-flight work");
+    shutdown.cancel();
+}
+
+/// Waits for a task to exit. A task exiting for any reason shuts down the others as well.
+async fn wait_for_exit<E>(
+    task_name: &str,
+    task: JoinHandle<Result<(), E>>,
+    shutdown: &CancellationToken,
+) where
+    E: std::fmt::Debug + std::fmt::Display,
+{
+    report_exit(task_name, task.await);
+    shutdown.cancel();
+}
+
 fn report_exit(
     task_name: &str,
     outcome: Result<Result<(), impl std::fmt::Debug + std::fmt::Display>, JoinError>,
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use sqlx::{Executor, PgPool};
+use tokio_util::sync::CancellationToken;
+use tracing::{field::display, Span};
+
+use crate::{
+    configuration::Settings, database::newsletter_db, domain::NewsletterIssueStatus,
+    issue_delivery_worker::ExecutionOutcome,
+};
+
+/// Publishes scheduled newsletter issues as they become due until `shutdown` is cancelled.
+pub async fn run_scheduler_until_stopped(
+    settings: Settings,
+    overwrite_db_pool: Option<sqlx::PgPool>,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let db_pool = match overwrite_db_pool {
+        Some(p) => p,
+        None => PgPool::connect_lazy_with(settings.database.with_db()),
+    };
+
+    scheduler_loop(db_pool, shutdown).await;
+    Ok(())
+}
+
+async fn scheduler_loop(pool: PgPool, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        let idle_time = match try_publish_scheduled_issue(&pool).await {
+            Ok(ExecutionOutcome::TaskCompleted) => continue,
+            Ok(ExecutionOutcome::EmptyQueue) => Duration::from_secs(10),
+            Err(_) => Duration::from_secs(1),
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(idle_time) => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+}
+
+/// Publishes a single scheduled newsletter issue whose time has come,
+/// fanning it out to the delivery queue.
+#[tracing::instrument(skip_all, fields(newsletter_issue_id=tracing::field::Empty), err)]
+pub async fn try_publish_scheduled_issue(pool: &PgPool) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool.begin().await?;
+    let issue = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM newsletter_issues
+        WHERE
+            status = $1 AND
+            scheduled_for <= now()
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+        NewsletterIssueStatus::Scheduled.to_string()
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+
+    let Some(issue) = issue else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("newsletter_issue_id", display(issue.newsletter_issue_id));
+
+    let query = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            status = $2,
+            published_at = now()
+        WHERE newsletter_issue_id = $1
+        "#,
+        issue.newsletter_issue_id,
+        NewsletterIssueStatus::Published.to_string()
+    );
+    transaction.execute(query).await?;
+    newsletter_db::enqueue_delivery_tasks(&mut transaction, issue.newsletter_issue_id).await?;
+    transaction.commit().await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
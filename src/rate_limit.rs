@@ -0,0 +1,356 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use tower_sessions_redis_store::fred::{
+    clients::RedisPool, error::RedisError, interfaces::KeysInterface, types::Expiration,
+};
+use uuid::Uuid;
+
+use crate::{
+    configuration::{LoginRateLimitSettings, SubscribeRateLimitSettings},
+    startup::AppState,
+};
+
+/// IP address of the client that sent the request, if it can be determined.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        // Behind a reverse proxy the peer address is the proxy's, so trust its header instead
+        let ip = match &state.client_ip_header {
+            Some(header) => parts
+                .headers
+                .get(header)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .and_then(|v| v.trim().parse().ok()),
+            None => parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        };
+
+        Ok(Self(ip))
+    }
+}
+
+/// Throttles password guessing by counting failed logins per username and per client IP in Redis.
+/// Once either count reaches its limit, further attempts are locked out for a period that doubles
+/// with every additional failure.
+#[derive(Clone)]
+pub struct LoginRateLimiter {
+    redis_pool: RedisPool,
+    settings: LoginRateLimitSettings,
+}
+
+impl LoginRateLimiter {
+    pub fn new(redis_pool: RedisPool, settings: LoginRateLimitSettings) -> Self {
+        Self {
+            redis_pool,
+            settings,
+        }
+    }
+
+    /// Returns how long the username or IP is still locked out for, if at all.
+    #[tracing::instrument(name = "Check login lockout", skip(self))]
+    pub async fn lockout(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<Option<Duration>, RedisError> {
+        self.lockout_for(Subject::Username(username), ip).await
+    }
+
+    #[tracing::instrument(name = "Record failed login", skip(self))]
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RedisError> {
+        self.record_failure_for(Subject::Username(username), ip)
+            .await
+    }
+
+    #[tracing::instrument(name = "Reset failed logins", skip(self))]
+    pub async fn reset(&self, username: &str, ip: Option<IpAddr>) -> Result<(), RedisError> {
+        self.reset_for(Subject::Username(username), ip).await
+    }
+
+    /// Returns how long the user or IP is still locked out of the second factor step for, if at all.
+    /// Failed codes are counted separately from failed passwords, so that knowing the password
+    /// does not reset them.
+    #[tracing::instrument(name = "Check two-factor lockout", skip(self))]
+    pub async fn two_factor_lockout(
+        &self,
+        user_id: Uuid,
+        ip: Option<IpAddr>,
+    ) -> Result<Option<Duration>, RedisError> {
+        self.lockout_for(Subject::TwoFactorUser(user_id), ip).await
+    }
+
+    #[tracing::instrument(name = "Record failed second factor", skip(self))]
+    pub async fn record_two_factor_failure(
+        &self,
+        user_id: Uuid,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RedisError> {
+        self.record_failure_for(Subject::TwoFactorUser(user_id), ip)
+            .await
+    }
+
+    #[tracing::instrument(name = "Reset failed second factors", skip(self))]
+    pub async fn reset_two_factor(
+        &self,
+        user_id: Uuid,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RedisError> {
+        self.reset_for(Subject::TwoFactorUser(user_id), ip).await
+    }
+
+    async fn lockout_for(
+        &self,
+        account: Subject<'_>,
+        ip: Option<IpAddr>,
+    ) -> Result<Option<Duration>, RedisError> {
+        let mut remaining_seconds = 0;
+        for subject in Self::subjects(account, ip) {
+            // TTL is negative for keys that do not exist
+            let ttl: i64 = self.redis_pool.ttl(subject.lockout_key()).await?;
+            remaining_seconds = remaining_seconds.max(ttl);
+        }
+
+        Ok((remaining_seconds > 0).then(|| Duration::from_secs(remaining_seconds as u64)))
+    }
+
+    async fn record_failure_for(
+        &self,
+        account: Subject<'_>,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RedisError> {
+        for subject in Self::subjects(account, ip) {
+            let failures: u32 = self.redis_pool.incr(subject.failures_key()).await?;
+            // Failures are forgotten after a quiet period as long as the longest lockout
+            self.redis_pool
+                .expire::<(), _>(
+                    subject.failures_key(),
+                    self.settings.max_lockout_seconds as i64,
+                )
+                .await?;
+
+            let max_failures = match subject {
+                Subject::Username(_) | Subject::TwoFactorUser(_) => {
+                    self.settings.max_failures_per_username
+                }
+                Subject::Ip(_) => self.settings.max_failures_per_ip,
+            };
+            if let Some(lockout) = lockout_duration(&self.settings, failures, max_failures) {
+                self.redis_pool
+                    .set::<(), _, _>(
+                        subject.lockout_key(),
+                        1,
+                        Some(Expiration::EX(lockout.as_secs() as i64)),
+                        None,
+                        false,
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn reset_for(&self, account: Subject<'_>, ip: Option<IpAddr>) -> Result<(), RedisError> {
+        let keys: Vec<_> = Self::subjects(account, ip)
+            .flat_map(|s| [s.failures_key(), s.lockout_key()])
+            .collect();
+        self.redis_pool.del(keys).await
+    }
+
+    fn subjects(account: Subject<'_>, ip: Option<IpAddr>) -> impl Iterator<Item = Subject<'_>> {
+        std::iter::once(account).chain(ip.map(Subject::Ip))
+    }
+}
+
+/// Limits how often confirmation emails can be triggered through the public subscription form,
+/// counting attempts per email and per client IP in fixed windows in Redis.
+#[derive(Clone)]
+pub struct SubscribeRateLimiter {
+    redis_pool: RedisPool,
+    settings: SubscribeRateLimitSettings,
+}
+
+impl SubscribeRateLimiter {
+    pub fn new(redis_pool: RedisPool, settings: SubscribeRateLimitSettings) -> Self {
+        Self {
+            redis_pool,
+            settings,
+        }
+    }
+
+    /// Counts a subscription attempt. Returns `false` if the email or IP has made too many
+    /// attempts within the current window.
+    #[tracing::instrument(name = "Count subscription attempt", skip(self))]
+    pub async fn try_acquire(&self, email: &str, ip: Option<IpAddr>) -> Result<bool, RedisError> {
+        let email_key = format!("subscribe_attempts:email:{}", email.trim().to_lowercase());
+        if self.count_in_window(email_key).await? > self.settings.max_attempts_per_email {
+            return Ok(false);
+        }
+
+        if let Some(ip) = ip {
+            let ip_key = format!("subscribe_attempts:ip:{}", ip);
+            if self.count_in_window(ip_key).await? > self.settings.max_attempts_per_ip {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Returns `true` if a confirmation email was sent to the subscriber too recently to send
+    /// another one.
+    #[tracing::instrument(name = "Check confirmation email cooldown", skip(self))]
+    pub async fn in_confirmation_cooldown(&self, subscriber_id: Uuid) -> Result<bool, RedisError> {
+        self.redis_pool
+            .exists(Self::confirmation_cooldown_key(subscriber_id))
+            .await
+    }
+
+    #[tracing::instrument(name = "Start confirmation email cooldown", skip(self))]
+    pub async fn start_confirmation_cooldown(&self, subscriber_id: Uuid) -> Result<(), RedisError> {
+        self.redis_pool
+            .set(
+                Self::confirmation_cooldown_key(subscriber_id),
+                1,
+                Some(Expiration::EX(
+                    self.settings.confirmation_cooldown_seconds as i64,
+                )),
+                None,
+                false,
+            )
+            .await
+    }
+
+    /// Increments the counter at `key`, which is reset once the window is over.
+    async fn count_in_window(&self, key: String) -> Result<u32, RedisError> {
+        let count: u32 = self.redis_pool.incr(&key).await?;
+        if count == 1 {
+            self.redis_pool
+                .expire::<(), _>(&key, self.settings.window_seconds as i64)
+                .await?;
+        }
+
+        Ok(count)
+    }
+
+    fn confirmation_cooldown_key(subscriber_id: Uuid) -> String {
+        format!("confirmation_cooldown:{}", subscriber_id)
+    }
+}
+
+/// Lockout after `failures` failed attempts, starting at the base duration once the limit is
+/// reached and doubling on every subsequent failure, up to the maximum.
+fn lockout_duration(
+    settings: &LoginRateLimitSettings,
+    failures: u32,
+    max_failures: u32,
+) -> Option<Duration> {
+    let excess = failures.checked_sub(max_failures)?;
+    let seconds = 2u64
+        .checked_pow(excess)
+        .and_then(|factor| settings.base_lockout_seconds.checked_mul(factor))
+        .map_or(settings.max_lockout_seconds, |s| {
+            s.min(settings.max_lockout_seconds)
+        });
+
+    Some(Duration::from_secs(seconds))
+}
+
+#[derive(Debug)]
+enum Subject<'a> {
+    Username(&'a str),
+    TwoFactorUser(Uuid),
+    Ip(IpAddr),
+}
+
+impl Subject<'_> {
+    fn failures_key(&self) -> String {
+        match self {
+            Self::Username(username) => format!("login_failures:username:{}", username),
+            Self::TwoFactorUser(user_id) => format!("two_factor_failures:user:{}", user_id),
+            Self::Ip(ip) => format!("login_failures:ip:{}", ip),
+        }
+    }
+
+    fn lockout_key(&self) -> String {
+        match self {
+            Self::Username(username) => format!("login_lockout:username:{}", username),
+            Self::TwoFactorUser(user_id) => format!("two_factor_lockout:user:{}", user_id),
+            Self::Ip(ip) => format!("login_lockout:ip:{}", ip),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::lockout_duration;
+    use crate::configuration::LoginRateLimitSettings;
+
+    fn settings() -> LoginRateLimitSettings {
+        LoginRateLimitSettings {
+            max_failures_per_username: 5,
+            max_failures_per_ip: 50,
+            base_lockout_seconds: 30,
+            max_lockout_seconds: 3600,
+        }
+    }
+
+    #[test]
+    fn no_lockout_below_the_limit() {
+        assert_eq!(lockout_duration(&settings(), 4, 5), None);
+    }
+
+    #[test]
+    fn lockout_doubles_with_every_failure_over_the_limit() {
+        assert_eq!(
+            lockout_duration(&settings(), 5, 5),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            lockout_duration(&settings(), 6, 5),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            lockout_duration(&settings(), 8, 5),
+            Some(Duration::from_secs(240))
+        );
+    }
+
+    #[test]
+    fn lockout_is_capped_at_the_maximum() {
+        assert_eq!(
+            lockout_duration(&settings(), 12, 5),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            lockout_duration(&settings(), u32::MAX, 5),
+            Some(Duration::from_secs(3600))
+        );
+    }
+}
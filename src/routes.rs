@@ -1,15 +1,25 @@
 mod admin;
-mod email;
+mod api;
 mod health_check;
 mod index;
 mod login;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_preferences;
+mod subscriptions_resend;
+mod subscriptions_unsubscribe;
 
 pub use admin::*;
-pub use email::*;
+pub use api::*;
 pub use health_check::*;
 pub use index::*;
 pub use login::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data::*;
+pub use subscriptions_preferences::*;
+pub use subscriptions_resend::*;
+pub use subscriptions_unsubscribe::*;
@@ -1,9 +1,27 @@
+mod api_keys;
 mod dashboard;
+mod delivery_failures;
+mod lists;
 mod logout;
+mod newsletter_drafts;
+mod newsletter_issue;
 mod newsletters;
 mod password;
+mod subscriber_list;
+mod subscribers;
+mod two_factor;
+mod users;
 
+pub use api_keys::*;
 pub use dashboard::*;
+pub use delivery_failures::*;
+pub use lists::*;
 pub use logout::*;
+pub use newsletter_drafts::*;
+pub use newsletter_issue::*;
 pub use newsletters::*;
 pub use password::*;
+pub use subscriber_list::*;
+pub use subscribers::*;
+pub use two_factor::*;
+pub use users::*;
@@ -0,0 +1,181 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::Form;
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    database::api_key_db,
+    domain::{ApiKey, ApiKeyScope, CsrfToken},
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeySummary {
+    pub api_key_id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+pub async fn api_keys_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let api_keys = get_api_keys(&db_pool, *user_id)
+        .await
+        .context("Failed to retrieve API keys")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_api_keys_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &api_keys,
+            None,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyFormData {
+    name: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+/// Mints a new API key and renders it right away instead of redirecting,
+/// since it is the only time the key can be shown.
+pub async fn create_api_key_with_flash(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    flash: Flash,
+    Form(data): Form<ApiKeyFormData>,
+) -> Result<Response, InternalServerError> {
+    let redirect = Redirect::to("/admin/api_keys");
+    let name = data.name.trim();
+    if name.is_empty() {
+        return Ok((flash.error("API key name must not be empty"), redirect).into_response());
+    }
+    let scopes = match data
+        .scopes
+        .into_iter()
+        .map(ApiKeyScope::try_from)
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(scopes) if !scopes.is_empty() => scopes,
+        Ok(_) => {
+            return Ok((
+                flash.error("API key must be granted at least one scope"),
+                redirect,
+            )
+                .into_response())
+        }
+        Err(e) => return Ok((flash.error(e.to_string()), redirect).into_response()),
+    };
+
+    let api_key = ApiKey::generate();
+    api_key_db::insert_api_key(&db_pool, *user_id, name, &api_key, &scopes)
+        .await
+        .context("Failed to store API key")
+        .map_err(e500)?;
+
+    let api_keys = get_api_keys(&db_pool, *user_id)
+        .await
+        .context("Failed to retrieve API keys")
+        .map_err(e500)?;
+    Ok(Html(template::admin_api_keys_html(
+        &csrf_token,
+        Some(format!(
+            "API key {} created, copy it now as it will not be shown again",
+            name
+        )),
+        None,
+        &api_keys,
+        Some(&api_key),
+    ))
+    .into_response())
+}
+
+pub async fn revoke_api_key_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    Path(api_key_id): Path<Uuid>,
+    flash: Flash,
+) -> Response {
+    let redirect = Redirect::to("/admin/api_keys");
+    match revoke_api_key(&db_pool, *user_id, api_key_id).await {
+        Ok(true) => (flash.success("API key successfully revoked"), redirect).into_response(),
+        Ok(false) => (
+            flash.error("API key not found or already revoked"),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get API keys", skip(pool))]
+async fn get_api_keys(pool: &PgPool, user_id: Uuid) -> Result<Vec<ApiKeySummary>, sqlx::Error> {
+    sqlx::query_as!(
+        ApiKeySummary,
+        r#"
+        SELECT api_key_id, name, key_prefix, scopes, created_at, last_used_at, revoked_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY revoked_at IS NOT NULL, created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Revokes an API key owned by the user. Returns `false` if there is no such active key.
+#[tracing::instrument(name = "Revoke API key", skip(pool))]
+async fn revoke_api_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    api_key_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE api_keys
+        SET revoked_at = now()
+        WHERE
+            api_key_id = $1 AND
+            user_id = $2 AND
+            revoked_at IS NULL
+        "#,
+        api_key_id,
+        user_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
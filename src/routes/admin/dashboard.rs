@@ -6,19 +6,26 @@ use axum::{
 
 use crate::{
     authentication::UserId,
-    database::user_db,
-    domain::Name,
+    database::user_db::{self, ActiveUser},
+    domain::{CsrfToken, Name},
     startup::AppState,
     template,
     utils::{e500, InternalServerError},
 };
 
 pub async fn admin_dashboard(
+    csrf_token: CsrfToken,
     State(AppState { db_pool, .. }): State<AppState>,
     Extension(user_id): Extension<UserId>,
+    Extension(active_user): Extension<ActiveUser>,
 ) -> Result<Response, InternalServerError> {
     let username = user_db::get_username(&db_pool, *user_id).await?;
 
     let name = Name::parse(&username).map_err(e500)?;
-    Ok(Html(template::admin_dashboard_html(&name)).into_response())
+    Ok(Html(template::admin_dashboard_html(
+        &csrf_token,
+        &name,
+        active_user,
+    ))
+    .into_response())
 }
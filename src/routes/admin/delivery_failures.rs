@@ -0,0 +1,250 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    database::newsletter_db,
+    domain::{CsrfToken, DeliveryStatus},
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+#[derive(Debug, Serialize)]
+pub struct FailedDelivery {
+    pub newsletter_issue_id: Uuid,
+    pub newsletter_title: String,
+    pub subscriber_email: String,
+    pub last_error: String,
+    pub n_attempts: i32,
+    pub failed_at: DateTime<Utc>,
+}
+
+pub async fn delivery_failures_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let failures = get_failed_deliveries(&db_pool)
+        .await
+        .context("Failed to retrieve failed deliveries")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_delivery_failures_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &failures,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FailedDeliveryFormData {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+}
+
+pub async fn requeue_failed_delivery_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Form(data): Form<FailedDeliveryFormData>,
+) -> Response {
+    match requeue_failed_delivery(&db_pool, &data).await {
+        Ok(true) => (
+            flash.success(format!(
+                "Delivery to {} has been requeued",
+                data.subscriber_email
+            )),
+            Redirect::to("/admin/newsletters/failures"),
+        )
+            .into_response(),
+        // Another admin, or a double click, got there first
+        Ok(false) => (
+            flash.success(format!(
+                "Delivery to {} was already requeued or discarded",
+                data.subscriber_email
+            )),
+            Redirect::to("/admin/newsletters/failures"),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (
+                flash.error(e.to_string()),
+                Redirect::to("/admin/newsletters/failures"),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn discard_failed_delivery_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Form(data): Form<FailedDeliveryFormData>,
+) -> Response {
+    match discard_failed_delivery(&db_pool, &data).await {
+        Ok(true) => (
+            flash.success(format!(
+                "Delivery to {} has been discarded",
+                data.subscriber_email
+            )),
+            Redirect::to("/admin/newsletters/failures"),
+        )
+            .into_response(),
+        Ok(false) => (
+            flash.success(format!(
+                "Delivery to {} was already requeued or discarded",
+                data.subscriber_email
+            )),
+            Redirect::to("/admin/newsletters/failures"),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (
+                flash.error(e.to_string()),
+                Redirect::to("/admin/newsletters/failures"),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get failed deliveries", skip(pool))]
+async fn get_failed_deliveries(pool: &PgPool) -> Result<Vec<FailedDelivery>, sqlx::Error> {
+    sqlx::query_as!(
+        FailedDelivery,
+        r#"
+        SELECT
+            f.newsletter_issue_id,
+            n.title AS newsletter_title,
+            f.subscriber_email,
+            f.last_error,
+            f.n_attempts,
+            f.failed_at
+        FROM failed_deliveries f
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        ORDER BY f.failed_at DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns `false` if the failed delivery was already requeued or discarded.
+#[tracing::instrument(name = "Requeue failed delivery", skip(pool))]
+async fn requeue_failed_delivery(
+    pool: &PgPool,
+    data: &FailedDeliveryFormData,
+) -> Result<bool, InternalServerError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM failed_deliveries
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        data.newsletter_issue_id,
+        data.subscriber_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove failed delivery")
+    .map_err(e500)?
+    .rows_affected();
+    if deleted == 0 {
+        return Ok(false);
+    }
+
+    // The delivery is pending again, so its logged outcome no longer applies
+    sqlx::query!(
+        r#"
+        DELETE FROM newsletter_delivery
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2 AND
+            status = $3
+        "#,
+        data.newsletter_issue_id,
+        data.subscriber_email,
+        DeliveryStatus::Failed.to_string()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove logged delivery outcome")
+    .map_err(e500)?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email
+        )
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        data.newsletter_issue_id,
+        data.subscriber_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to enqueue delivery task")
+    .map_err(e500)?;
+    newsletter_db::notify_delivery_workers(&mut transaction)
+        .await
+        .context("Failed to notify delivery workers")
+        .map_err(e500)?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to requeue delivery")
+        .map_err(e500)?;
+
+    Ok(true)
+}
+
+/// Returns `false` if the failed delivery was already requeued or discarded.
+#[tracing::instrument(name = "Discard failed delivery", skip(pool))]
+async fn discard_failed_delivery(
+    pool: &PgPool,
+    data: &FailedDeliveryFormData,
+) -> Result<bool, InternalServerError> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM failed_deliveries
+        WHERE
+            newsletter_issue_id = $1 AND
+            subscriber_email = $2
+        "#,
+        data.newsletter_issue_id,
+        data.subscriber_email
+    )
+    .execute(pool)
+    .await
+    .context("Failed to remove failed delivery")
+    .map_err(e500)?
+    .rows_affected();
+
+    Ok(deleted > 0)
+}
@@ -0,0 +1,78 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use serde::Deserialize;
+
+use crate::{
+    database::list_db,
+    domain::{CsrfToken, ListSlug},
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+pub async fn lists_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let lists = list_db::get_list_summaries(&db_pool)
+        .await
+        .context("Failed to retrieve lists")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_lists_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &lists,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFormData {
+    slug: String,
+    name: String,
+}
+
+pub async fn create_list_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Form(data): Form<ListFormData>,
+) -> Response {
+    let redirect = Redirect::to("/admin/lists");
+    let slug = match ListSlug::parse(data.slug.trim()) {
+        Ok(slug) => slug,
+        Err(e) => return (flash.error(e.to_string()), redirect).into_response(),
+    };
+    let name = data.name.trim();
+    if name.is_empty() {
+        return (flash.error("List name must not be empty"), redirect).into_response();
+    }
+
+    match list_db::insert_list(&db_pool, &slug, name).await {
+        Ok(Some(_)) => (
+            flash.success(format!("List {} has been created", name)),
+            redirect,
+        )
+            .into_response(),
+        Ok(None) => (
+            flash.error(format!("A list with slug {} already exists", slug.as_ref())),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    database::{list_db, newsletter_db, user_db},
+    domain::{CsrfToken, Email, ListSlug, NewsletterIssueStatus, ScheduledTime, Segment},
+    routes::SegmentParameters,
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+#[derive(Debug, Serialize)]
+pub struct NewsletterDraft {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DraftFormData {
+    title: String,
+    html_content: String,
+    text_content: String,
+}
+
+fn draft_url(newsletter_issue_id: Uuid) -> String {
+    format!("/admin/newsletters/drafts/{}", newsletter_issue_id)
+}
+
+pub async fn newsletter_drafts_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let drafts = get_drafts(&db_pool)
+        .await
+        .context("Failed to retrieve newsletter drafts")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_newsletter_drafts_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &drafts,
+        )),
+    )
+        .into_response())
+}
+
+pub async fn create_newsletter_draft_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Form(data): Form<DraftFormData>,
+) -> Response {
+    match insert_draft(&db_pool, &data).await {
+        Ok(newsletter_issue_id) => (
+            flash.success("Draft successfully saved"),
+            Redirect::to(&draft_url(newsletter_issue_id)),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (
+                flash.error("Something went wrong"),
+                Redirect::to("/admin/newsletters/drafts"),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn newsletter_draft_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let Some(draft) = get_draft(&db_pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve newsletter draft")
+        .map_err(e500)?
+    else {
+        return Ok((StatusCode::NOT_FOUND, "Newsletter draft not found").into_response());
+    };
+    let lists = list_db::get_lists(&db_pool)
+        .await
+        .context("Failed to retrieve lists")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_newsletter_draft_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &draft,
+            &lists,
+        )),
+    )
+        .into_response())
+}
+
+pub async fn update_newsletter_draft_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flash: Flash,
+    Form(data): Form<DraftFormData>,
+) -> Response {
+    let redirect = Redirect::to(&draft_url(newsletter_issue_id));
+    match update_draft(&db_pool, newsletter_issue_id, &data).await {
+        Ok(true) => (flash.success("Draft successfully saved"), redirect).into_response(),
+        Ok(false) => (
+            flash.error("Only drafts can be edited"),
+            Redirect::to("/admin/newsletters/drafts"),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+pub async fn preview_newsletter_draft(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+) -> Result<Response, InternalServerError> {
+    let Some(draft) = get_draft(&db_pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve newsletter draft")
+        .map_err(e500)?
+    else {
+        return Ok((StatusCode::NOT_FOUND, "Newsletter draft not found").into_response());
+    };
+
+    Ok(Html(template::admin_newsletter_draft_preview_html(
+        &csrf_token,
+        &draft,
+    ))
+    .into_response())
+}
+
+/// Sends the draft to the logged-in user without touching the delivery queue.
+pub async fn send_test_newsletter_draft_with_flash(
+    State(AppState {
+        db_pool,
+        email_client,
+        ..
+    }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flash: Flash,
+) -> Response {
+    let redirect = Redirect::to(&draft_url(newsletter_issue_id));
+    let recipient = match user_db::get_user_email(&db_pool, *user_id).await {
+        Ok(Some(email)) => match Email::parse(&email) {
+            Ok(email) => email,
+            Err(e) => return (flash.error(e.to_string()), redirect).into_response(),
+        },
+        Ok(None) => {
+            return (
+                flash.error("Your account has no email address to send the test email to"),
+                redirect,
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            return (flash.error("Something went wrong"), redirect).into_response();
+        }
+    };
+
+    let draft = match get_draft(&db_pool, newsletter_issue_id).await {
+        Ok(Some(draft)) => draft,
+        Ok(None) => {
+            return (
+                flash.error("Newsletter draft not found"),
+                Redirect::to("/admin/newsletters/drafts"),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            return (flash.error("Something went wrong"), redirect).into_response();
+        }
+    };
+
+    match email_client
+        .send_email(
+            &recipient,
+            &draft.title,
+            &draft.html_content,
+            &draft.text_content,
+        )
+        .await
+    {
+        Ok(_) => (
+            flash.success(format!("Test email sent to {}", recipient.as_ref())),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Failed to send test email"), redirect).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublishDraftFormData {
+    // Publish immediately if empty
+    scheduled_for: Option<String>,
+    /// Slug of the list to send the issue to.
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(flatten)]
+    segment: SegmentParameters,
+}
+
+fn default_list() -> String {
+    ListSlug::DEFAULT.to_string()
+}
+
+pub async fn publish_newsletter_draft_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flash: Flash,
+    Form(data): Form<PublishDraftFormData>,
+) -> Response {
+    let scheduled_for = match data
+        .scheduled_for
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .map(ScheduledTime::parse)
+        .transpose()
+    {
+        Ok(scheduled_for) => scheduled_for,
+        Err(e) => {
+            return (
+                flash.error(e.to_string()),
+                Redirect::to(&draft_url(newsletter_issue_id)),
+            )
+                .into_response()
+        }
+    };
+    let segment = match data.segment.segment() {
+        Ok(segment) => segment,
+        Err(e) => {
+            return (
+                flash.error(e.to_string()),
+                Redirect::to(&draft_url(newsletter_issue_id)),
+            )
+                .into_response()
+        }
+    };
+    let list = match list_db::get_list_by_slug(&db_pool, &data.list).await {
+        Ok(Some(list)) => list,
+        Ok(None) => {
+            return (
+                flash.error("Unknown mailing list"),
+                Redirect::to(&draft_url(newsletter_issue_id)),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            return (
+                flash.error("Something went wrong"),
+                Redirect::to(&draft_url(newsletter_issue_id)),
+            )
+                .into_response();
+        }
+    };
+
+    match publish_draft(
+        &db_pool,
+        newsletter_issue_id,
+        list.list_id,
+        &segment,
+        scheduled_for,
+    )
+    .await
+    {
+        Ok(true) => {
+            let success_msg = match scheduled_for {
+                Some(time) => format!("Newsletter successfully scheduled for {}", time),
+                None => "Newsletter successfully published".to_string(),
+            };
+            (
+                flash.success(success_msg),
+                Redirect::to(&format!("/admin/newsletters/{}", newsletter_issue_id)),
+            )
+                .into_response()
+        }
+        Ok(false) => (
+            flash.error("Only drafts can be published"),
+            Redirect::to("/admin/newsletters/drafts"),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (
+                flash.error(e.to_string()),
+                Redirect::to(&draft_url(newsletter_issue_id)),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get newsletter drafts", skip(pool))]
+async fn get_drafts(pool: &PgPool) -> Result<Vec<NewsletterDraft>, sqlx::Error> {
+    sqlx::query_as!(
+        NewsletterDraft,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, updated_at
+        FROM newsletter_issues
+        WHERE status = $1
+        ORDER BY updated_at DESC
+        "#,
+        NewsletterIssueStatus::Draft.to_string()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get newsletter draft", skip(pool))]
+async fn get_draft(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterDraft>, sqlx::Error> {
+    sqlx::query_as!(
+        NewsletterDraft,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, updated_at
+        FROM newsletter_issues
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = $2
+        "#,
+        newsletter_issue_id,
+        NewsletterIssueStatus::Draft.to_string()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Insert newsletter draft", skip_all)]
+async fn insert_draft(pool: &PgPool, data: &DraftFormData) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            status
+        )
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        newsletter_issue_id,
+        data.title,
+        data.text_content,
+        data.html_content,
+        NewsletterIssueStatus::Draft.to_string()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// Returns `false` if the issue is not a draft anymore.
+#[tracing::instrument(name = "Update newsletter draft", skip(pool, data))]
+async fn update_draft(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    data: &DraftFormData,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            title = $3,
+            text_content = $4,
+            html_content = $5,
+            updated_at = now()
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = $2
+        "#,
+        newsletter_issue_id,
+        NewsletterIssueStatus::Draft.to_string(),
+        data.title,
+        data.text_content,
+        data.html_content
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Publish newsletter draft", skip(pool))]
+async fn publish_draft(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    list_id: Uuid,
+    segment: &Segment,
+    scheduled_for: Option<ScheduledTime>,
+) -> Result<bool, InternalServerError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let published = newsletter_db::publish_draft(
+        &mut transaction,
+        newsletter_issue_id,
+        list_id,
+        segment,
+        scheduled_for,
+    )
+    .await
+    .context("Failed to update newsletter issue status")
+    .map_err(e500)?;
+    if !published {
+        return Ok(false);
+    }
+
+    // Scheduled issues are enqueued by the scheduler once they are due
+    if scheduled_for.is_none() {
+        newsletter_db::enqueue_delivery_tasks(&mut transaction, newsletter_issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")
+            .map_err(e500)?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to publish draft")
+        .map_err(e500)?;
+
+    Ok(true)
+}
@@ -0,0 +1,283 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    domain::{CsrfToken, DeliveryStatus, NewsletterIssueStatus, ScheduledTime},
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+#[derive(Debug, Serialize)]
+pub struct NewsletterIssueSummary {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub status: String,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryProgress {
+    pub total: i64,
+    pub sent: i64,
+    pub failed: i64,
+    pub pending: i64,
+    pub percent_complete: i64,
+}
+
+impl DeliveryProgress {
+    pub fn new(sent: i64, failed: i64, pending: i64) -> Self {
+        let total = sent + failed + pending;
+        // An issue without any recipients has nothing left to do
+        let percent_complete = if total == 0 {
+            100
+        } else {
+            (sent + failed) * 100 / total
+        };
+
+        Self {
+            total,
+            sent,
+            failed,
+            pending,
+            percent_complete,
+        }
+    }
+}
+
+pub async fn newsletter_issue_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let issue = get_issue_summary(&*db_pool, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve newsletter issue")
+        .map_err(e500)?;
+    let Some(issue) = issue else {
+        return Ok((StatusCode::NOT_FOUND, "Newsletter issue not found").into_response());
+    };
+
+    let progress = get_delivery_progress(&db_pool, newsletter_issue_id)
+        .await
+        .context("Failed to compute delivery progress")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_newsletter_issue_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &issue,
+            &progress,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RescheduleFormData {
+    scheduled_for: String,
+}
+
+pub async fn reschedule_newsletter_issue_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flash: Flash,
+    Form(data): Form<RescheduleFormData>,
+) -> Response {
+    let redirect = Redirect::to(&format!("/admin/newsletters/{}", newsletter_issue_id));
+    let scheduled_for = match ScheduledTime::parse(&data.scheduled_for) {
+        Ok(scheduled_for) => scheduled_for,
+        Err(e) => return (flash.error(e.to_string()), redirect).into_response(),
+    };
+
+    match reschedule_issue(&db_pool, newsletter_issue_id, scheduled_for).await {
+        Ok(true) => (
+            flash.success(format!(
+                "Newsletter successfully rescheduled for {}",
+                scheduled_for
+            )),
+            redirect,
+        )
+            .into_response(),
+        Ok(false) => (
+            flash.error("Only scheduled newsletters can be rescheduled"),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+pub async fn cancel_newsletter_issue_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Path(newsletter_issue_id): Path<Uuid>,
+    flash: Flash,
+) -> Response {
+    let redirect = Redirect::to(&format!("/admin/newsletters/{}", newsletter_issue_id));
+    match cancel_issue(&db_pool, newsletter_issue_id).await {
+        Ok(true) => (flash.success("Newsletter successfully cancelled"), redirect).into_response(),
+        Ok(false) => (
+            flash.error("Only scheduled newsletters can be cancelled"),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get newsletter issue summary", skip(executor))]
+pub async fn get_issue_summary(
+    executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterIssueSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        NewsletterIssueSummary,
+        r#"
+        SELECT newsletter_issue_id, title, status, scheduled_for, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+#[tracing::instrument(name = "Get recent newsletter issues", skip(pool))]
+pub async fn get_recent_issues(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<NewsletterIssueSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        NewsletterIssueSummary,
+        r#"
+        SELECT newsletter_issue_id, title, status, scheduled_for, published_at
+        FROM newsletter_issues
+        WHERE status <> $2
+        ORDER BY COALESCE(published_at, scheduled_for) DESC
+        LIMIT $1
+        "#,
+        limit,
+        NewsletterIssueStatus::Draft.to_string()
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Moves a scheduled issue to a new time. Returns `false` if the issue is no longer scheduled.
+#[tracing::instrument(name = "Reschedule newsletter issue", skip(pool))]
+async fn reschedule_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    scheduled_for: ScheduledTime,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET scheduled_for = $3
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = $2
+        "#,
+        newsletter_issue_id,
+        NewsletterIssueStatus::Scheduled.to_string(),
+        scheduled_for.as_datetime()
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+/// Cancels a scheduled issue. Returns `false` if the issue is no longer scheduled.
+#[tracing::instrument(name = "Cancel newsletter issue", skip(pool))]
+async fn cancel_issue(pool: &PgPool, newsletter_issue_id: Uuid) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = $3
+        WHERE
+            newsletter_issue_id = $1 AND
+            status = $2
+        "#,
+        newsletter_issue_id,
+        NewsletterIssueStatus::Scheduled.to_string(),
+        NewsletterIssueStatus::Cancelled.to_string()
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Get delivery progress", skip(pool))]
+async fn get_delivery_progress(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<DeliveryProgress, sqlx::Error> {
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            (
+                SELECT COUNT(*) FROM newsletter_delivery
+                WHERE newsletter_issue_id = $1 AND status = $2
+            ) AS "sent!",
+            (
+                SELECT COUNT(*) FROM newsletter_delivery
+                WHERE newsletter_issue_id = $1 AND status = $3
+            ) AS "failed!",
+            (
+                SELECT COUNT(*) FROM issue_delivery_queue
+                WHERE newsletter_issue_id = $1
+            ) AS "pending!"
+        "#,
+        newsletter_issue_id,
+        DeliveryStatus::Sent.to_string(),
+        DeliveryStatus::Failed.to_string(),
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(DeliveryProgress::new(r.sent, r.failed, r.pending))
+}
+
+#[cfg(test)]
+mod test {
+    use super::DeliveryProgress;
+
+    #[test]
+    fn progress_counts_failed_deliveries_as_done() {
+        let progress = DeliveryProgress::new(2, 1, 1);
+        assert_eq!(progress.total, 4);
+        assert_eq!(progress.percent_complete, 75);
+    }
+
+    #[test]
+    fn issue_without_recipients_is_complete() {
+        assert_eq!(DeliveryProgress::new(0, 0, 0).percent_complete, 100);
+    }
+}
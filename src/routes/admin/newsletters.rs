@@ -1,33 +1,55 @@
 use anyhow::Context;
 use axum::{
-    extract::State,
+    extract::{Query, State},
+    http::StatusCode,
     response::{Html, IntoResponse, Redirect, Response},
-    Extension, Form,
+    Extension, Form, Json,
 };
 use axum_flash::{Flash, IncomingFlashes};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
+use super::get_recent_issues;
 use crate::{
     authentication::UserId,
-    domain::SubscriptionStatus,
+    database::{list_db, newsletter_db},
+    domain::{
+        CsrfToken, ListSlug, ParseScheduledTimeError, ParseSegmentError, ScheduledTime, Segment,
+    },
     idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
     startup::AppState,
     template,
     utils::{e500, get_success_and_error_flash_message, InternalServerError},
 };
 
-pub async fn publish_newsletter_form(flashes: IncomingFlashes) -> impl IntoResponse {
+pub async fn publish_newsletter_form(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let recent_issues = get_recent_issues(&db_pool, 10)
+        .await
+        .context("Failed to retrieve recent newsletter issues")
+        .map_err(e500)?;
+    let lists = list_db::get_lists(&db_pool)
+        .await
+        .context("Failed to retrieve lists")
+        .map_err(e500)?;
+
     let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
-    (
+    Ok((
         flashes,
         Html(template::admin_newsletter_html(
+            &csrf_token,
             success_msg,
             error_msg,
             Uuid::new_v4().to_string(),
+            &lists,
+            &recent_issues,
         )),
     )
+        .into_response())
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,6 +58,90 @@ pub struct NewsletterFormData {
     html_content: String,
     text_content: String,
     idempotency_key: String,
+    // Publish immediately if empty
+    scheduled_for: Option<String>,
+    /// Slug of the list to send the issue to.
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(flatten)]
+    segment: SegmentParameters,
+}
+
+fn default_list() -> String {
+    ListSlug::DEFAULT.to_string()
+}
+
+/// Segment of the list to send an issue to, as submitted by the publish forms.
+/// Tags are comma-separated and every field may be left empty.
+#[derive(Debug, Default, Deserialize)]
+pub struct SegmentParameters {
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    excluded_tags: String,
+    #[serde(default)]
+    subscribed_after: String,
+    #[serde(default)]
+    subscribed_before: String,
+}
+
+impl SegmentParameters {
+    pub fn segment(&self) -> Result<Segment, ParseSegmentError> {
+        Segment::parse(
+            &self.tags,
+            &self.excluded_tags,
+            &self.subscribed_after,
+            &self.subscribed_before,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RecipientCountParameters {
+    #[serde(default = "default_list")]
+    list: String,
+    #[serde(flatten)]
+    segment: SegmentParameters,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecipientCount {
+    pub recipients: i64,
+}
+
+/// Counts how many subscribers an issue would be sent to, for the publish forms to show while
+/// the list and segment are being chosen.
+pub async fn count_newsletter_recipients(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Query(params): Query<RecipientCountParameters>,
+) -> Result<Response, InternalServerError> {
+    let segment = match params.segment.segment() {
+        Ok(segment) => segment,
+        Err(e) => return Ok((StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response()),
+    };
+    let Some(list) = list_db::get_list_by_slug(&db_pool, &params.list)
+        .await
+        .context("Failed to retrieve mailing list")
+        .map_err(e500)?
+    else {
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, "Unknown mailing list").into_response());
+    };
+
+    let recipients = newsletter_db::count_recipients(&db_pool, list.list_id, &segment)
+        .await
+        .context("Failed to count recipients")
+        .map_err(e500)?;
+    Ok(Json(RecipientCount { recipients }).into_response())
+}
+
+impl NewsletterFormData {
+    fn scheduled_for(&self) -> Result<Option<ScheduledTime>, ParseScheduledTimeError> {
+        self.scheduled_for
+            .as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .map(ScheduledTime::parse)
+            .transpose()
+    }
 }
 
 pub async fn publish_newsletter_with_flash(
@@ -44,8 +150,60 @@ pub async fn publish_newsletter_with_flash(
     Extension(user_id): Extension<UserId>,
     Form(data): Form<NewsletterFormData>,
 ) -> Response {
-    match publish_newsletter_with_idempotent_handling(state, user_id, data).await {
-        Ok(r) => (flash.success("Newsletter successfully published"), r).into_response(),
+    let scheduled_for = match data.scheduled_for() {
+        Ok(scheduled_for) => scheduled_for,
+        Err(e) => {
+            return (
+                flash.error(e.to_string()),
+                Redirect::to("/admin/newsletters"),
+            )
+                .into_response()
+        }
+    };
+    let segment = match data.segment.segment() {
+        Ok(segment) => segment,
+        Err(e) => {
+            return (
+                flash.error(e.to_string()),
+                Redirect::to("/admin/newsletters"),
+            )
+                .into_response()
+        }
+    };
+    let list = match list_db::get_list_by_slug(&state.db_pool, &data.list).await {
+        Ok(Some(list)) => list,
+        Ok(None) => {
+            return (
+                flash.error("Unknown mailing list"),
+                Redirect::to("/admin/newsletters"),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            return (
+                flash.error("Something went wrong"),
+                Redirect::to("/admin/newsletters"),
+            )
+                .into_response();
+        }
+    };
+    let success_msg = match scheduled_for {
+        Some(time) => format!("Newsletter successfully scheduled for {}", time),
+        None => "Newsletter successfully published".to_string(),
+    };
+
+    match publish_newsletter_with_idempotent_handling(
+        state,
+        user_id,
+        data,
+        list.list_id,
+        segment,
+        scheduled_for,
+    )
+    .await
+    {
+        Ok(r) => (flash.success(success_msg), r).into_response(),
         Err(e) => {
             tracing::error!("{:?}", e);
             (
@@ -61,6 +219,9 @@ async fn publish_newsletter_with_idempotent_handling(
     state: AppState,
     user_id: UserId,
     data: NewsletterFormData,
+    list_id: Uuid,
+    segment: Segment,
+    scheduled_for: Option<ScheduledTime>,
 ) -> Result<Response, InternalServerError> {
     let idempotency_key: IdempotencyKey =
         data.idempotency_key.to_string().try_into().map_err(e500)?;
@@ -77,7 +238,15 @@ async fn publish_newsletter_with_idempotent_handling(
     };
 
     // Publish newsletter
-    publish_newsletter(&mut transaction, user_id, data).await?;
+    publish_newsletter(
+        &mut transaction,
+        user_id,
+        data,
+        list_id,
+        &segment,
+        scheduled_for,
+    )
+    .await?;
 
     // Save response
     let response = Redirect::to("/admin/newsletters").into_response();
@@ -92,75 +261,30 @@ async fn publish_newsletter(
     transaction: &mut Transaction<'_, Postgres>,
     user_id: UserId,
     data: NewsletterFormData,
+    list_id: Uuid,
+    segment: &Segment,
+    scheduled_for: Option<ScheduledTime>,
 ) -> Result<(), InternalServerError> {
-    let issue_id = insert_newsletter_issue(
+    let issue_id = newsletter_db::insert_newsletter_issue(
         transaction,
+        list_id,
+        segment,
         &data.title,
         &data.text_content,
         &data.html_content,
+        scheduled_for,
     )
     .await
     .context("Failed to store newsletter issue details")
     .map_err(e500)?;
 
-    enqueue_delivery_tasks(transaction, issue_id)
-        .await
-        .context("Failed to enqueue delivery tasks")
-        .map_err(e500)?;
-
-    Ok(())
-}
-
-#[tracing::instrument(name = "Insert newsletter issue", skip_all)]
-async fn insert_newsletter_issue(
-    transaction: &mut Transaction<'_, Postgres>,
-    title: &str,
-    text_content: &str,
-    html_content: &str,
-) -> Result<Uuid, sqlx::Error> {
-    let newsletter_issue_id = Uuid::new_v4();
-    sqlx::query!(
-        r#"
-        INSERT INTO newsletter_issues (
-            newsletter_issue_id,
-            title,
-            text_content,
-            html_content,
-            published_at
-        )
-        VALUES ($1, $2, $3, $4, now())
-        "#,
-        newsletter_issue_id,
-        title,
-        text_content,
-        html_content
-    )
-    .execute(&mut **transaction)
-    .await?;
-
-    Ok(newsletter_issue_id)
-}
-
-#[tracing::instrument(name = "Enqueue delivery tasks", skip_all)]
-async fn enqueue_delivery_tasks(
-    transaction: &mut Transaction<'_, Postgres>,
-    newsletter_issue_id: Uuid,
-) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-        INSERT INTO issue_delivery_queue (
-            newsletter_issue_id,
-            subscriber_email
-        )
-        SELECT $1, email
-        FROM subscriptions
-        WHERE status = $2
-        "#,
-        newsletter_issue_id,
-        SubscriptionStatus::Confirmed.to_string()
-    )
-    .execute(&mut **transaction)
-    .await?;
+    // Scheduled issues are enqueued by the scheduler once they are due
+    if scheduled_for.is_none() {
+        newsletter_db::enqueue_delivery_tasks(transaction, issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")
+            .map_err(e500)?;
+    }
 
     Ok(())
 }
@@ -10,16 +10,24 @@ use secrecy::{ExposeSecret, SecretString};
 use crate::{
     authentication::{self, UserId},
     database::user_db,
+    domain::CsrfToken,
     startup::AppState,
     telemetry, template,
     utils::{get_success_and_error_flash_message, InternalServerError},
 };
 
-pub async fn change_password_form(flashes: IncomingFlashes) -> impl IntoResponse {
+pub async fn change_password_form(
+    csrf_token: CsrfToken,
+    flashes: IncomingFlashes,
+) -> impl IntoResponse {
     let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
     (
         flashes,
-        Html(template::admin_change_password_html(success_msg, error_msg)),
+        Html(template::admin_change_password_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+        )),
     )
 }
 
@@ -0,0 +1,373 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    database::{list_db, subscriber_db},
+    domain::{CsrfToken, Email, Name, SubscriberTag, SubscriptionStatus},
+    routes::{mark_subscriber_as_unsubscribed, rotate_subscription_token, send_confirmation_email},
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+/// Number of subscribers shown per page.
+const PAGE_SIZE: i64 = 50;
+
+#[derive(Debug, Serialize)]
+pub struct SubscriberSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+}
+
+/// Search and filters of the subscriber list. Pages are keyed on the subscriber id, which is a
+/// time-ordered v7 UUID, so `after` is the id of the last subscriber on the previous page.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubscriberFilter {
+    #[serde(default)]
+    pub search: String,
+    #[serde(default)]
+    pub status: String,
+    pub after: Option<Uuid>,
+}
+
+pub async fn subscribers_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Query(filter): Query<SubscriberFilter>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let (success_msg, mut error_msg) = get_success_and_error_flash_message(&flashes);
+    let status = match filter.status.as_str() {
+        "" => None,
+        s => match SubscriptionStatus::try_from(s.to_string()) {
+            Ok(status) => Some(status),
+            Err(e) => {
+                error_msg = Some(e.to_string());
+                None
+            }
+        },
+    };
+
+    let mut subscribers =
+        get_subscribers_page(&db_pool, filter.search.trim(), status, filter.after)
+            .await
+            .context("Failed to retrieve subscribers")
+            .map_err(e500)?;
+    // One more than a page was fetched to know whether there is a next page
+    let next_after = if subscribers.len() as i64 > PAGE_SIZE {
+        subscribers.truncate(PAGE_SIZE as usize);
+        subscribers.last().map(|s| s.id)
+    } else {
+        None
+    };
+
+    Ok((
+        flashes,
+        Html(template::admin_subscribers_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &subscribers,
+            &filter,
+            next_after,
+        )),
+    )
+        .into_response())
+}
+
+pub async fn confirm_subscriber_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+) -> Response {
+    let redirect = Redirect::to("/admin/subscribers");
+    match confirm_pending_subscriber(&db_pool, subscriber_id).await {
+        Ok(Some(email)) => (
+            flash.success(format!("{} has been confirmed", email)),
+            redirect,
+        )
+            .into_response(),
+        Ok(None) => (
+            flash.error("Subscriber is not waiting for confirmation"),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+pub async fn resend_subscriber_confirmation_with_flash(
+    State(app_state): State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+) -> Response {
+    let redirect = Redirect::to("/admin/subscribers");
+    match resend_confirmation(&app_state, subscriber_id).await {
+        Ok(Some(email)) => (
+            flash.success(format!(
+                "A new confirmation email has been sent to {}",
+                email
+            )),
+            redirect,
+        )
+            .into_response(),
+        Ok(None) => (
+            flash.error("Subscriber is not waiting for confirmation"),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+pub async fn unsubscribe_subscriber_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+) -> Response {
+    let redirect = Redirect::to("/admin/subscribers");
+    match mark_subscriber_as_unsubscribed(&db_pool, subscriber_id).await {
+        Ok(true) => (flash.success("Subscriber has been unsubscribed"), redirect).into_response(),
+        Ok(false) => (flash.error("Subscriber not found"), redirect).into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+pub async fn delete_subscriber_with_flash(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+) -> Result<Response, InternalServerError> {
+    let redirect = Redirect::to("/admin/subscribers");
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")
+        .map_err(e500)?;
+    let deleted = subscriber_db::delete_subscriber(&mut transaction, subscriber_id, &hmac_secret)
+        .await
+        .context("Failed to delete subscriber")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete subscriber")
+        .map_err(e500)?;
+
+    match deleted {
+        Some(email) => Ok((
+            flash.success(format!("{} has been deleted", email)),
+            redirect,
+        )
+            .into_response()),
+        None => Ok((flash.error("Subscriber not found"), redirect).into_response()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriberTagsFormData {
+    tags: String,
+}
+
+/// Replaces the tags of the subscriber with the comma-separated tags of the form.
+pub async fn update_subscriber_tags_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Path(subscriber_id): Path<Uuid>,
+    Form(data): Form<SubscriberTagsFormData>,
+) -> Result<Response, InternalServerError> {
+    let redirect = Redirect::to("/admin/subscribers");
+    let tags = match SubscriberTag::parse_list(&data.tags) {
+        Ok(tags) => tags,
+        Err(e) => return Ok((flash.error(e.to_string()), redirect).into_response()),
+    };
+
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")
+        .map_err(e500)?;
+    let updated = subscriber_db::set_subscriber_tags(&mut transaction, subscriber_id, &tags)
+        .await
+        .context("Failed to update subscriber tags")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update subscriber tags")
+        .map_err(e500)?;
+
+    if updated {
+        Ok((flash.success("Subscriber tags have been updated"), redirect).into_response())
+    } else {
+        Ok((flash.error("Subscriber not found"), redirect).into_response())
+    }
+}
+
+/// Lists subscribers from newest to oldest, fetching one more than a page.
+#[tracing::instrument(name = "Get subscribers page", skip(pool))]
+async fn get_subscribers_page(
+    pool: &PgPool,
+    search: &str,
+    status: Option<SubscriptionStatus>,
+    after: Option<Uuid>,
+) -> Result<Vec<SubscriberSummary>, sqlx::Error> {
+    let pattern = format!("%{}%", escape_like(search));
+    sqlx::query_as!(
+        SubscriberSummary,
+        r#"
+        SELECT
+            id, name, email, status, subscribed_at,
+            ARRAY(
+                SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id ORDER BY tag
+            ) AS "tags!"
+        FROM subscriptions s
+        WHERE
+            (email ILIKE $1 OR name ILIKE $1) AND
+            ($2::text IS NULL OR status = $2) AND
+            ($3::uuid IS NULL OR id < $3)
+        ORDER BY id DESC
+        LIMIT $4
+        "#,
+        pattern,
+        status.map(|s| s.to_string()),
+        after,
+        PAGE_SIZE + 1
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Escapes the wildcards of `LIKE` patterns, so that searches match literally.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Confirms every list the subscriber has yet to confirm. Returns the email of the subscriber,
+/// or `None` if they had nothing to confirm.
+#[tracing::instrument(name = "Confirm pending subscriber", skip(pool))]
+async fn confirm_pending_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let confirmed_lists = sqlx::query!(
+        r#"
+        UPDATE list_subscriptions SET status = $1
+        WHERE subscriber_id = $2 AND status = $3
+        "#,
+        SubscriptionStatus::Confirmed.to_string(),
+        subscriber_id,
+        SubscriptionStatus::PendingConfirmation.to_string()
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    if confirmed_lists == 0 {
+        return Ok(None);
+    }
+    let confirmed = sqlx::query!(
+        "UPDATE subscriptions SET status = $1 WHERE id = $2 RETURNING email",
+        SubscriptionStatus::Confirmed.to_string(),
+        subscriber_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(Some(confirmed.email))
+}
+
+/// Sends a subscriber a confirmation email with a newly issued token for every list they have
+/// yet to confirm, regardless of when the last one was sent. Returns the email of the
+/// subscriber, or `None` if they had nothing to confirm.
+#[tracing::instrument(name = "Resend confirmation email from admin", skip(app_state))]
+async fn resend_confirmation(
+    app_state: &AppState,
+    subscriber_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let Some(subscriber) = sqlx::query!(
+        "SELECT name, email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_optional(app_state.db_pool.as_ref())
+    .await?
+    else {
+        return Ok(None);
+    };
+    let pending_lists = list_db::get_pending_lists(&app_state.db_pool, subscriber_id)
+        .await
+        .context("Failed to get the lists waiting for confirmation")?;
+    if pending_lists.is_empty() {
+        return Ok(None);
+    }
+    let name = Name::parse(&subscriber.name)?;
+    let email = Email::parse(&subscriber.email)?;
+
+    for list in pending_lists {
+        let mut transaction = app_state
+            .db_pool
+            .begin()
+            .await
+            .context("Failed to acquire Postgres connection from the pool")?;
+        let subscription_token =
+            rotate_subscription_token(&mut transaction, subscriber_id, list.list_id)
+                .await
+                .context("Failed to rotate the subscription token")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to rotate subscription token")?;
+
+        send_confirmation_email(
+            app_state.email_client.as_ref(),
+            &name,
+            &email,
+            &list,
+            &app_state.app_base_url,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send a new confirmation email")?;
+    }
+
+    Ok(Some(subscriber.email))
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape_like;
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like(r"50%_off\"), r"50\%\_off\\");
+    }
+}
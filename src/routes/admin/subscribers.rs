@@ -0,0 +1,521 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{Multipart, State},
+    http::header,
+    response::{Html, IntoResponse},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    database::{list_db, newsletter_db, subscriber_db},
+    domain::{
+        CsrfToken, Email, ListSlug, Name, NewSubscriber, SubscriberTag, SubscriptionStatus,
+        SubscriptionToken,
+    },
+    startup::AppState,
+    template,
+    utils::{e500, InternalServerError},
+};
+
+/// Number of subscribers read from the database at a time while exporting.
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+/// A subscriber read from the CSV file along with their tags.
+pub type TaggedSubscriber = (NewSubscriber, Vec<SubscriberTag>);
+
+/// A CSV row that could not be imported, numbered as in a spreadsheet, the header being line 1.
+#[derive(Debug, Serialize)]
+pub struct ImportRowError {
+    pub line: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Rows of subscribers who already existed but were not on the list yet, and were added to it.
+    pub added_existing: usize,
+    /// Rows of subscribers who were already on the list.
+    pub skipped_existing: usize,
+    /// Rows of subscribers who asked for their data to be erased.
+    pub skipped_erased: usize,
+    pub invalid_rows: Vec<ImportRowError>,
+    pub confirmation_emails_sent: bool,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ImportError {
+    #[error("The CSV file needs a header row with `name` and `email` columns")]
+    MissingColumns,
+
+    #[error("Imported subscribers must start as confirmed or pending confirmation")]
+    InvalidStatus,
+
+    #[error("No CSV file was uploaded")]
+    MissingFile,
+
+    #[error("Unknown mailing list")]
+    UnknownList,
+
+    #[error("Failed to read the upload: {0}")]
+    InvalidUpload(String),
+}
+
+pub async fn import_subscribers_form(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+) -> Result<Html<String>, InternalServerError> {
+    let lists = list_db::get_lists(&db_pool)
+        .await
+        .context("Failed to retrieve lists")
+        .map_err(e500)?;
+    Ok(Html(template::admin_subscribers_import_html(
+        &csrf_token,
+        &lists,
+        None,
+        None,
+    )))
+}
+
+/// Imports subscribers from an uploaded CSV file with `name` and `email` columns, and optionally
+/// `tags`, into a list, then shows what was imported and which rows were rejected.
+#[tracing::instrument(name = "Import subscribers", skip_all)]
+pub async fn import_subscribers(
+    csrf_token: CsrfToken,
+    State(app_state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Html<String>, InternalServerError> {
+    let lists = list_db::get_lists(&app_state.db_pool)
+        .await
+        .context("Failed to retrieve lists")
+        .map_err(e500)?;
+    let parsed = read_import_form(multipart)
+        .await
+        .and_then(|(status, slug, csv)| {
+            let list = lists
+                .iter()
+                .find(|l| l.slug == slug)
+                .ok_or(ImportError::UnknownList)?;
+            Ok((status, list, parse_subscribers_csv(&csv)?))
+        });
+    let (status, list, (subscribers, invalid_rows)) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return Ok(Html(template::admin_subscribers_import_html(
+                &csrf_token,
+                &lists,
+                None,
+                Some(e.to_string()),
+            )))
+        }
+    };
+
+    let mut transaction = app_state
+        .db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")
+        .map_err(e500)?;
+    let (subscribers, mut tags): (Vec<_>, HashMap<_, _>) = subscribers
+        .into_iter()
+        .map(|(s, tags)| {
+            let email = s.email.as_ref().to_string();
+            (s, (email, tags))
+        })
+        .unzip();
+    // Subscribers who asked to be erased must not come back through an old list
+    let erased =
+        subscriber_db::get_erased_emails(&mut transaction, &subscribers, &app_state.hmac_secret)
+            .await
+            .context("Failed to look up erased subscribers")
+            .map_err(e500)?;
+    let n_parsed = subscribers.len();
+    let subscribers: Vec<_> = subscribers
+        .into_iter()
+        .filter(|s| !erased.contains(s.email.as_ref()))
+        .collect();
+    let skipped_erased = n_parsed - subscribers.len();
+    let n_valid = subscribers.len();
+    let inserted = subscriber_db::insert_subscribers(&mut transaction, &subscribers, &status)
+        .await
+        .context("Failed to insert imported subscribers")
+        .map_err(e500)?;
+    // Subscribers who already exist still join the list and get the tags of their row, unless
+    // they never confirmed their address or unsubscribed from every list
+    let inserted_emails: HashSet<_> = inserted.iter().map(|(_, email)| email.as_str()).collect();
+    let existing_emails: Vec<_> = subscribers
+        .iter()
+        .map(|s| s.email.as_ref())
+        .filter(|email| !inserted_emails.contains(email))
+        .map(ToString::to_string)
+        .collect();
+    let existing =
+        subscriber_db::get_confirmed_subscriber_ids_by_email(&mut transaction, &existing_emails)
+            .await
+            .context("Failed to look up existing subscribers")
+            .map_err(e500)?;
+    let imported: Vec<_> = inserted.iter().chain(&existing).collect();
+    let imported_ids: Vec<_> = imported.iter().map(|(id, _)| *id).collect();
+    let added: HashSet<_> =
+        list_db::insert_list_subscriptions(&mut transaction, list.list_id, &imported_ids, &status)
+            .await
+            .context("Failed to subscribe imported subscribers to the list")
+            .map_err(e500)?
+            .into_iter()
+            .collect();
+    let imported_tags: Vec<_> = imported
+        .iter()
+        .flat_map(|(id, email)| {
+            tags.remove(email)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|t| (*id, t))
+        })
+        .collect();
+    subscriber_db::insert_subscriber_tags(&mut transaction, &imported_tags)
+        .await
+        .context("Failed to tag imported subscribers")
+        .map_err(e500)?;
+
+    let mut n_confirmations = 0;
+    if status == SubscriptionStatus::PendingConfirmation {
+        // Only subscribers who just joined the list have a subscription to confirm
+        let joined: Vec<_> = imported_ids
+            .iter()
+            .copied()
+            .filter(|id| added.contains(id))
+            .collect();
+        let tokens: Vec<_> = joined
+            .iter()
+            .map(|id| (*id, SubscriptionToken::generate()))
+            .collect();
+        subscriber_db::store_tokens(&mut transaction, list.list_id, &tokens)
+            .await
+            .context("Failed to store subscription tokens of imported subscribers")
+            .map_err(e500)?;
+        // Sending thousands of emails takes a while, so leave it to the delivery worker
+        subscriber_db::enqueue_confirmation_emails(&mut transaction, list.list_id, &joined)
+            .await
+            .context("Failed to enqueue confirmation emails of imported subscribers")
+            .map_err(e500)?;
+        newsletter_db::notify_delivery_workers(&mut transaction)
+            .await
+            .context("Failed to notify delivery workers")
+            .map_err(e500)?;
+        n_confirmations = joined.len();
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to import subscribers")
+        .map_err(e500)?;
+
+    let report = ImportReport {
+        imported: inserted.len(),
+        added_existing: added.len() - inserted.len(),
+        skipped_existing: n_valid - added.len(),
+        skipped_erased,
+        invalid_rows,
+        confirmation_emails_sent: n_confirmations > 0,
+    };
+
+    Ok(Html(template::admin_subscribers_import_html(
+        &csrf_token,
+        &lists,
+        Some(&report),
+        None,
+    )))
+}
+
+/// Reads the initial status, the slug of the list and the CSV file from the import form.
+async fn read_import_form(
+    mut multipart: Multipart,
+) -> Result<(SubscriptionStatus, String, Vec<u8>), ImportError> {
+    let mut status = None;
+    let mut list = ListSlug::DEFAULT.to_string();
+    let mut csv = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ImportError::InvalidUpload(e.body_text()))?
+    {
+        match field.name() {
+            Some("initial_status") => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| ImportError::InvalidUpload(e.body_text()))?;
+                status = match SubscriptionStatus::try_from(text) {
+                    Ok(s @ SubscriptionStatus::Confirmed)
+                    | Ok(s @ SubscriptionStatus::PendingConfirmation) => Some(s),
+                    _ => return Err(ImportError::InvalidStatus),
+                };
+            }
+            Some("list") => {
+                list = field
+                    .text()
+                    .await
+                    .map_err(|e| ImportError::InvalidUpload(e.body_text()))?;
+            }
+            Some("file") => {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ImportError::InvalidUpload(e.body_text()))?;
+                csv = Some(bytes.to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    let status = status.ok_or(ImportError::InvalidStatus)?;
+    let csv = csv
+        .filter(|c| !c.is_empty())
+        .ok_or(ImportError::MissingFile)?;
+    Ok((status, list, csv))
+}
+
+/// Validates every row of the CSV file, which needs `name` and `email` columns in any order.
+/// An optional `tags` column holds comma-separated tags for each subscriber.
+/// Rows repeating an email from earlier in the file are rejected as well.
+pub fn parse_subscribers_csv(
+    csv: &[u8],
+) -> Result<(Vec<TaggedSubscriber>, Vec<ImportRowError>), ImportError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv);
+    let headers = reader
+        .headers()
+        .map_err(|_| ImportError::MissingColumns)?
+        .clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(name_column), Some(email_column)) = (column("name"), column("email")) else {
+        return Err(ImportError::MissingColumns);
+    };
+    let tags_column = column("tags");
+
+    let mut subscribers = vec![];
+    let mut invalid_rows = vec![];
+    let mut first_seen_on = HashMap::new();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                invalid_rows.push(ImportRowError {
+                    line: e.position().map_or(0, |p| p.line()),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let name = Name::parse(record.get(name_column).unwrap_or_default());
+        let email = Email::parse(record.get(email_column).unwrap_or_default());
+        let tags =
+            SubscriberTag::parse_list(tags_column.and_then(|c| record.get(c)).unwrap_or_default());
+        let (subscriber, tags) = match (name, email, tags) {
+            (Ok(name), Ok(email), Ok(tags)) => (NewSubscriber { name, email }, tags),
+            (Err(e), _, _) => {
+                invalid_rows.push(ImportRowError {
+                    line,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+            (_, Err(e), _) => {
+                invalid_rows.push(ImportRowError {
+                    line,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+            (_, _, Err(e)) => {
+                invalid_rows.push(ImportRowError {
+                    line,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        match first_seen_on.entry(subscriber.email.as_ref().to_string()) {
+            Entry::Occupied(first_line) => invalid_rows.push(ImportRowError {
+                line,
+                error: format!("Email already appears on line {}", first_line.get()),
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(line);
+                subscribers.push((subscriber, tags));
+            }
+        }
+    }
+
+    Ok((subscribers, invalid_rows))
+}
+
+/// Streams every subscriber as CSV, reading them from the database in batches.
+pub async fn export_subscribers(
+    State(AppState { db_pool, .. }): State<AppState>,
+) -> impl IntoResponse {
+    let (writer, reader) = tokio::io::duplex(64 * 1024);
+    tokio::spawn(
+        async move {
+            if let Err(e) = write_subscribers_csv(&db_pool, writer).await {
+                tracing::error!("{:?}", e);
+            }
+        }
+        .instrument(tracing::Span::current()),
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"subscribers.csv\"",
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response()
+}
+
+struct ExportedSubscriber {
+    id: Uuid,
+    name: String,
+    email: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    tags: Vec<String>,
+}
+
+#[tracing::instrument(name = "Write subscribers CSV", skip_all)]
+async fn write_subscribers_csv(
+    pool: &PgPool,
+    mut writer: impl AsyncWrite + Unpin,
+) -> Result<(), anyhow::Error> {
+    let mut csv = csv::Writer::from_writer(vec![]);
+    csv.write_record(["email", "name", "status", "subscribed_at", "tags"])?;
+    writer.write_all(&csv.into_inner()?).await?;
+
+    let mut after = None;
+    loop {
+        let batch = get_subscribers_after(pool, after).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = Some(last.id);
+
+        let mut csv = csv::Writer::from_writer(vec![]);
+        for s in &batch {
+            csv.write_record([
+                s.email.as_str(),
+                s.name.as_str(),
+                s.status.as_str(),
+                &s.subscribed_at.to_rfc3339(),
+                &s.tags.join(","),
+            ])?;
+        }
+        // Fails once the client goes away, which ends the export
+        writer
+            .write_all(&csv.into_inner()?)
+            .await
+            .context("Failed to stream subscribers CSV")?;
+    }
+
+    writer.shutdown().await?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get subscribers batch", skip(pool))]
+async fn get_subscribers_after(
+    pool: &PgPool,
+    after: Option<Uuid>,
+) -> Result<Vec<ExportedSubscriber>, sqlx::Error> {
+    sqlx::query_as!(
+        ExportedSubscriber,
+        r#"
+        SELECT
+            id, name, email, status, subscribed_at,
+            ARRAY(
+                SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id ORDER BY tag
+            ) AS "tags!"
+        FROM subscriptions s
+        WHERE $1::uuid IS NULL OR id > $1
+        ORDER BY id
+        LIMIT $2
+        "#,
+        after,
+        EXPORT_BATCH_SIZE
+    )
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_subscribers_csv, ImportError};
+
+    #[test]
+    fn valid_rows_are_parsed_whatever_the_column_order() {
+        let csv = "Email,Name\nursula@example.com,Ursula\n le.guin@example.com , Le Guin \n";
+
+        let (subscribers, invalid_rows) = parse_subscribers_csv(csv.as_bytes()).unwrap();
+
+        assert!(invalid_rows.is_empty());
+        assert_eq!(subscribers.len(), 2);
+        assert_eq!(subscribers[1].0.name.as_ref(), "Le Guin");
+        assert_eq!(subscribers[1].0.email.as_ref(), "le.guin@example.com");
+        assert!(subscribers[1].1.is_empty());
+    }
+
+    #[test]
+    fn tags_column_is_optional_and_validated() {
+        let csv = "name,email,tags\n\
+            Ursula,ursula@example.com,\"VIP, beta-tester\"\n\
+            Le Guin,le.guin@example.com,\n\
+            Ged,ged@example.com,\"bad\ttag\"\n";
+
+        let (subscribers, invalid_rows) = parse_subscribers_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(subscribers.len(), 2);
+        let tags: Vec<_> = subscribers[0].1.iter().map(AsRef::as_ref).collect();
+        assert_eq!(tags, vec!["beta-tester", "vip"]);
+        assert!(subscribers[1].1.is_empty());
+        let lines: Vec<_> = invalid_rows.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![4]);
+    }
+
+    #[test]
+    fn invalid_and_repeated_rows_are_reported_with_their_line() {
+        let csv = "name,email\n\
+            Ursula,ursula@example.com\n\
+            ,missing-name@example.com\n\
+            Ursula,not-an-email\n\
+            Ursula Again,ursula@example.com\n";
+
+        let (subscribers, invalid_rows) = parse_subscribers_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(subscribers.len(), 1);
+        let lines: Vec<_> = invalid_rows.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+        assert_eq!(invalid_rows[2].error, "Email already appears on line 2");
+    }
+
+    #[test]
+    fn csv_without_required_columns_is_rejected() {
+        let result = parse_subscribers_csv("name,address\nUrsula,Earthsea\n".as_bytes());
+
+        assert!(matches!(result, Err(ImportError::MissingColumns)));
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{self, UserId},
+    database::{two_factor_db, user_db},
+    domain::{CsrfToken, RecoveryCode, TotpCode, TotpSecret},
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+/// Name shown for the account in authenticator apps.
+const TOTP_ISSUER: &str = "Zero2Prod";
+
+/// What the user needs to add the account to their authenticator app.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorEnrollment {
+    pub otpauth_uri: String,
+    pub secret: String,
+}
+
+pub async fn two_factor_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let credential = two_factor_db::get_two_factor_credential(&db_pool, *user_id)
+        .await
+        .context("Failed to retrieve two-factor credential")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    let html = match credential {
+        Some(credential) if credential.confirmed_at.is_some() => {
+            let unused_recovery_codes =
+                two_factor_db::count_unused_recovery_codes(&db_pool, *user_id)
+                    .await
+                    .context("Failed to count recovery codes")
+                    .map_err(e500)?;
+            template::admin_two_factor_html(
+                &csrf_token,
+                success_msg,
+                error_msg,
+                None,
+                unused_recovery_codes,
+                &[],
+            )
+        }
+        credential => {
+            // Keep showing the same secret until it is confirmed,
+            // in case the user already scanned it
+            let secret = match credential.map(|c| TotpSecret::parse_base32(&c.secret)) {
+                Some(secret) => secret
+                    .context("Stored two-factor secret is invalid")
+                    .map_err(e500)?,
+                None => {
+                    let secret = TotpSecret::generate();
+                    two_factor_db::store_pending_secret(&db_pool, *user_id, &secret)
+                        .await
+                        .context("Failed to store two-factor secret")
+                        .map_err(e500)?;
+                    secret
+                }
+            };
+            let enrollment = enrollment(&db_pool, *user_id, &secret).await?;
+            template::admin_two_factor_html(
+                &csrf_token,
+                success_msg,
+                error_msg,
+                Some(&enrollment),
+                0,
+                &[],
+            )
+        }
+    };
+
+    Ok((flashes, Html(html)).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorCodeFormData {
+    code: String,
+}
+
+/// Enables two-factor authentication once the first code from the authenticator app checks out,
+/// and renders the recovery codes right away, since it is the only time they can be shown.
+pub async fn enable_two_factor_with_flash(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    flash: Flash,
+    Form(data): Form<TwoFactorCodeFormData>,
+) -> Result<Response, InternalServerError> {
+    let redirect = Redirect::to("/admin/two_factor");
+    let code = match TotpCode::parse(&data.code) {
+        Ok(code) => code,
+        Err(e) => return Ok((flash.error(e.to_string()), redirect).into_response()),
+    };
+    let Some(credential) = two_factor_db::get_two_factor_credential(&db_pool, *user_id)
+        .await
+        .context("Failed to retrieve two-factor credential")
+        .map_err(e500)?
+        .filter(|c| c.confirmed_at.is_none())
+    else {
+        return Ok((
+            flash.error("Two-factor authentication is already enabled"),
+            redirect,
+        )
+            .into_response());
+    };
+    let secret = TotpSecret::parse_base32(&credential.secret)
+        .context("Stored two-factor secret is invalid")
+        .map_err(e500)?;
+    let Some(step) = secret.verify(&code, Utc::now()) else {
+        return Ok((flash.error("Invalid authentication code"), redirect).into_response());
+    };
+
+    let recovery_codes: Vec<_> = std::iter::repeat_with(RecoveryCode::generate)
+        .take(RecoveryCode::BATCH_SIZE)
+        .collect();
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")
+        .map_err(e500)?;
+    two_factor_db::confirm_two_factor(&mut transaction, *user_id, step)
+        .await
+        .context("Failed to enable two-factor authentication")
+        .map_err(e500)?;
+    two_factor_db::replace_recovery_codes(&mut transaction, *user_id, &recovery_codes)
+        .await
+        .context("Failed to store recovery codes")
+        .map_err(e500)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to enable two-factor authentication")
+        .map_err(e500)?;
+
+    let recovery_codes: Vec<_> = recovery_codes
+        .iter()
+        .map(RecoveryCode::expose_formatted)
+        .collect();
+    Ok(Html(template::admin_two_factor_html(
+        &csrf_token,
+        Some(
+            "Two-factor authentication enabled, write down your recovery codes now \
+            as they will not be shown again"
+                .into(),
+        ),
+        None,
+        None,
+        recovery_codes.len() as i64,
+        &recovery_codes,
+    ))
+    .into_response())
+}
+
+/// Disables two-factor authentication, which requires a current code or a recovery code.
+pub async fn disable_two_factor_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    flash: Flash,
+    Form(data): Form<TwoFactorCodeFormData>,
+) -> Result<Response, InternalServerError> {
+    let redirect = Redirect::to("/admin/two_factor");
+    let verified = authentication::verify_second_factor(&db_pool, *user_id, &data.code).await?;
+    if !verified {
+        return Ok((flash.error("Invalid authentication code"), redirect).into_response());
+    }
+
+    two_factor_db::delete_two_factor(&db_pool, *user_id)
+        .await
+        .context("Failed to disable two-factor authentication")
+        .map_err(e500)?;
+    Ok((
+        flash.success("Two-factor authentication disabled"),
+        redirect,
+    )
+        .into_response())
+}
+
+async fn enrollment(
+    pool: &PgPool,
+    user_id: Uuid,
+    secret: &TotpSecret,
+) -> Result<TwoFactorEnrollment, InternalServerError> {
+    let username = user_db::get_username(pool, user_id).await?;
+    Ok(TwoFactorEnrollment {
+        otpauth_uri: secret.otpauth_uri(TOTP_ISSUER, &username),
+        secret: secret.expose_base32(),
+    })
+}
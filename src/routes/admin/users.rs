@@ -0,0 +1,214 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{compute_password_hash, UserId},
+    database::user_db,
+    domain::{CsrfToken, Email, UserRole},
+    startup::AppState,
+    telemetry::spawn_blocking_with_tracing,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+#[derive(Debug, Serialize)]
+pub struct UserSummary {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+    pub deactivated_at: Option<DateTime<Utc>>,
+}
+
+pub async fn users_page(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    flashes: IncomingFlashes,
+) -> Result<Response, InternalServerError> {
+    let users = get_users(&db_pool)
+        .await
+        .context("Failed to retrieve users")
+        .map_err(e500)?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::admin_users_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &users,
+            *user_id,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct InviteUserFormData {
+    username: String,
+    email: String,
+    password: SecretString,
+    role: String,
+}
+
+/// Creates a user with an initial password, which the owner hands over to the invitee.
+pub async fn invite_user_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Form(data): Form<InviteUserFormData>,
+) -> Result<Response, InternalServerError> {
+    let redirect = Redirect::to("/admin/users");
+    let username = data.username.trim();
+    if username.is_empty() {
+        return Ok((flash.error("Username must not be empty"), redirect).into_response());
+    }
+    let email = match Email::parse(&data.email) {
+        Ok(email) => email,
+        Err(e) => return Ok((flash.error(e.to_string()), redirect).into_response()),
+    };
+    if data.password.expose_secret().is_empty() {
+        return Ok((flash.error("Password must not be empty"), redirect).into_response());
+    }
+    let role = match UserRole::try_from(data.role) {
+        Ok(role) => role,
+        Err(e) => return Ok((flash.error(e.to_string()), redirect).into_response()),
+    };
+
+    let password = data.password;
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking task")
+        .map_err(e500)?
+        .context("Failed to hash password")
+        .map_err(e500)?;
+
+    match user_db::insert_user(&db_pool, username, &email, &password_hash, role).await {
+        Ok(_) => Ok((
+            flash.success(format!("User {} invited as {}", username, role)),
+            redirect,
+        )
+            .into_response()),
+        Err(sqlx::Error::Database(e)) if e.constraint() == Some("users_email_key") => Ok((
+            flash.error(format!("Email {} is already taken", email.as_ref())),
+            redirect,
+        )
+            .into_response()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok((
+            flash.error(format!("Username {} is already taken", username)),
+            redirect,
+        )
+            .into_response()),
+        Err(e) => Err(e500(e)),
+    }
+}
+
+pub async fn deactivate_user_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(current_user_id): Extension<UserId>,
+    Path(user_id): Path<Uuid>,
+    flash: Flash,
+) -> Response {
+    let redirect = Redirect::to("/admin/users");
+    // Prevent owners from locking themselves out
+    if *current_user_id == user_id {
+        return (
+            flash.error("You cannot deactivate your own account"),
+            redirect,
+        )
+            .into_response();
+    }
+
+    match deactivate_user(&db_pool, user_id).await {
+        Ok(true) => (flash.success("User successfully deactivated"), redirect).into_response(),
+        Ok(false) => (
+            flash.error("User not found or already deactivated"),
+            redirect,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+pub async fn delete_user_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(current_user_id): Extension<UserId>,
+    Path(user_id): Path<Uuid>,
+    flash: Flash,
+) -> Response {
+    let redirect = Redirect::to("/admin/users");
+    // Prevent owners from locking themselves out
+    if *current_user_id == user_id {
+        return (flash.error("You cannot delete your own account"), redirect).into_response();
+    }
+
+    match delete_user(&db_pool, user_id).await {
+        Ok(true) => (flash.success("User successfully deleted"), redirect).into_response(),
+        Ok(false) => (flash.error("User not found"), redirect).into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            (flash.error("Something went wrong"), redirect).into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Get users", skip(pool))]
+async fn get_users(pool: &PgPool) -> Result<Vec<UserSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        UserSummary,
+        r#"
+        SELECT user_id, username, email, role, created_at, deactivated_at
+        FROM users
+        ORDER BY deactivated_at IS NOT NULL, username
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deactivates a user, who can no longer log in or use their API keys.
+/// Returns `false` if there is no such active user.
+#[tracing::instrument(name = "Deactivate user", skip(pool))]
+async fn deactivate_user(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users
+        SET deactivated_at = now()
+        WHERE
+            user_id = $1 AND
+            deactivated_at IS NULL
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+/// Deletes a user along with their API keys. Returns `false` if there is no such user.
+#[tracing::instrument(name = "Delete user", skip(pool))]
+async fn delete_user(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query!("DELETE FROM users WHERE user_id = $1", user_id)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(deleted > 0)
+}
@@ -0,0 +1,7 @@
+mod error;
+mod newsletters;
+mod subscribers;
+
+pub use error::*;
+pub use newsletters::*;
+pub use subscribers::*;
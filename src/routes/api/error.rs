@@ -0,0 +1,149 @@
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{routes::SubscribeError, utils::InternalServerError};
+
+/// Error returned by the JSON API.
+/// It is serialized as `{"error": {"code": "...", "message": "..."}}`, where `code` is a stable,
+/// machine-readable identifier and `message` is meant for humans.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "Missing or invalid API key",
+        )
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "validation_error",
+            message,
+        )
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let body = serde_json::json!({
+            "error": {
+                "code": self.code,
+                "message": self.message,
+            }
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<InternalServerError> for ApiError {
+    fn from(e: InternalServerError) -> Self {
+        // Log unexpected error
+        tracing::error!("{:?}", e.0);
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Something went wrong",
+        )
+    }
+}
+
+impl From<SubscribeError> for ApiError {
+    fn from(e: SubscribeError) -> Self {
+        match e {
+            // Report which field is invalid rather than the generic form error
+            SubscribeError::FormValidationError(e) => Self::validation(e.to_string()),
+            SubscribeError::AlreadyConfirmed => {
+                Self::new(StatusCode::CONFLICT, "already_confirmed", e.to_string())
+            }
+            SubscribeError::UnknownList => Self::validation(e.to_string()),
+            SubscribeError::TooManyAttempts => {
+                Self::new(StatusCode::TOO_MANY_REQUESTS, "rate_limited", e.to_string())
+            }
+            SubscribeError::UnexpectedError(e) => InternalServerError(e).into(),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(rejection.status(), "invalid_request", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid_request", rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "invalid_request", rejection.body_text())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::StatusCode;
+
+    use super::ApiError;
+    use crate::routes::SubscribeError;
+
+    #[test]
+    fn already_confirmed_subscription_is_a_conflict() {
+        let e = ApiError::from(SubscribeError::AlreadyConfirmed);
+        assert_eq!(e.status(), StatusCode::CONFLICT);
+        assert_eq!(e.code(), "already_confirmed");
+    }
+
+    #[test]
+    fn unexpected_errors_do_not_leak_details() {
+        let e = ApiError::from(SubscribeError::UnexpectedError(anyhow::anyhow!(
+            "connection refused"
+        )));
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(e.to_string(), "Something went wrong");
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::WithRejection;
+use serde::{Deserialize, Serialize};
+
+use super::ApiError;
+use crate::{
+    authentication::{ApiKeyScopes, UserId},
+    database::{list_db, newsletter_db},
+    domain::{ApiKeyScope, ListSlug, ScheduledTime},
+    idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    routes::{get_issue_summary, get_recent_issues, NewsletterIssueSummary, SegmentParameters},
+    startup::AppState,
+    utils::e500,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PublishNewsletterRequest {
+    title: String,
+    text_content: String,
+    html_content: String,
+    // Publish immediately if missing
+    scheduled_for: Option<String>,
+    // Send to the default list if missing
+    list: Option<String>,
+    // Send to the whole list if missing
+    #[serde(default)]
+    segment: SegmentParameters,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListNewslettersParams {
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListNewslettersResponse {
+    pub newsletters: Vec<NewsletterIssueSummary>,
+}
+
+/// Header with which clients can safely retry a publish request.
+pub const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Maximum number of newsletter issues returned by a single list request.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Publishes a newsletter issue to the confirmed subscribers in a segment of a list, or schedules
+/// it for later. Retries carrying the same `Idempotency-Key` header get the saved response back
+/// instead of publishing the issue again.
+pub async fn api_publish_newsletter(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    Extension(scopes): Extension<ApiKeyScopes>,
+    headers: HeaderMap,
+    WithRejection(Json(body), _): WithRejection<Json<PublishNewsletterRequest>, ApiError>,
+) -> Result<Response, ApiError> {
+    scopes.require(ApiKeyScope::NewslettersWrite)?;
+    if body.title.trim().is_empty() {
+        return Err(ApiError::validation("The title cannot be empty"));
+    }
+    if body.text_content.trim().is_empty() || body.html_content.trim().is_empty() {
+        return Err(ApiError::validation("The content cannot be empty"));
+    }
+    let idempotency_key: Option<IdempotencyKey> = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|value| {
+            let value = value
+                .to_str()
+                .map_err(|_| ApiError::validation("The idempotency key must be ASCII"))?;
+            value
+                .to_string()
+                .try_into()
+                .map_err(|e: anyhow::Error| ApiError::validation(e.to_string()))
+        })
+        .transpose()?;
+    let scheduled_for = body
+        .scheduled_for
+        .as_deref()
+        .map(ScheduledTime::parse)
+        .transpose()
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+    let segment = body
+        .segment
+        .segment()
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+    let list =
+        list_db::get_list_by_slug(&db_pool, body.list.as_deref().unwrap_or(ListSlug::DEFAULT))
+            .await
+            .context("Failed to retrieve mailing list")
+            .map_err(e500)?
+            .ok_or_else(|| ApiError::validation("Unknown mailing list"))?;
+
+    // Return early if we have a saved response in the database
+    let mut transaction = match &idempotency_key {
+        Some(key) => match try_processing(&db_pool, key, *user_id)
+            .await
+            .map_err(e500)?
+        {
+            NextAction::StartProcessing(t) => t,
+            NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
+        },
+        None => db_pool
+            .begin()
+            .await
+            .context("Failed to acquire Postgres connection from the pool")
+            .map_err(e500)?,
+    };
+    let newsletter_issue_id = newsletter_db::insert_newsletter_issue(
+        &mut transaction,
+        list.list_id,
+        &segment,
+        &body.title,
+        &body.text_content,
+        &body.html_content,
+        scheduled_for,
+    )
+    .await
+    .context("Failed to store newsletter issue details")
+    .map_err(e500)?;
+
+    // Scheduled issues are enqueued by the scheduler once they are due
+    if scheduled_for.is_none() {
+        newsletter_db::enqueue_delivery_tasks(&mut transaction, newsletter_issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")
+            .map_err(e500)?;
+    }
+
+    let issue = get_issue_summary(&mut *transaction, newsletter_issue_id)
+        .await
+        .context("Failed to retrieve newsletter issue")
+        .map_err(e500)?
+        .ok_or_else(|| e500("Published newsletter issue was not stored"))?;
+    let response = (StatusCode::CREATED, Json(issue)).into_response();
+    match &idempotency_key {
+        Some(key) => Ok(save_response(transaction, key, *user_id, response)
+            .await
+            .map_err(e500)?),
+        None => {
+            transaction
+                .commit()
+                .await
+                .context("Failed to commit SQL transaction to publish newsletter")
+                .map_err(e500)?;
+            Ok(response)
+        }
+    }
+}
+
+pub async fn api_list_newsletters(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(scopes): Extension<ApiKeyScopes>,
+    WithRejection(Query(params), _): WithRejection<Query<ListNewslettersParams>, ApiError>,
+) -> Result<Json<ListNewslettersResponse>, ApiError> {
+    scopes.require(ApiKeyScope::NewslettersRead)?;
+    let limit = params
+        .limit
+        .unwrap_or(MAX_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let newsletters = get_recent_issues(&db_pool, limit)
+        .await
+        .context("Failed to retrieve recent newsletter issues")
+        .map_err(e500)?;
+    Ok(Json(ListNewslettersResponse { newsletters }))
+}
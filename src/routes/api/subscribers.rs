@@ -0,0 +1,158 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::ApiError;
+use crate::{
+    authentication::ApiKeyScopes,
+    domain::{ApiKeyScope, ListSlug, SubscriptionStatus},
+    routes::{subscribe, SubscribeFormData},
+    startup::AppState,
+    utils::e500,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSubscriberRequest {
+    name: String,
+    email: String,
+    // Subscribe to the default list if missing
+    list: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriberResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListSubscribersParams {
+    status: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListSubscribersResponse {
+    pub subscribers: Vec<SubscriberResponse>,
+}
+
+/// Maximum number of subscribers returned by a single list request.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Subscribes a new pending subscriber and sends them a confirmation email,
+/// exactly like the public subscription form.
+pub async fn api_create_subscriber(
+    State(state): State<AppState>,
+    Extension(scopes): Extension<ApiKeyScopes>,
+    WithRejection(Json(body), _): WithRejection<Json<CreateSubscriberRequest>, ApiError>,
+) -> Result<(StatusCode, Json<SubscriberResponse>), ApiError> {
+    scopes.require(ApiKeyScope::SubscribersWrite)?;
+    let db_pool = state.db_pool.clone();
+    let data = SubscribeFormData {
+        name: body.name,
+        email: body.email,
+        website: String::new(),
+        list: body.list.unwrap_or_else(|| ListSlug::DEFAULT.to_string()),
+    };
+    let subscriber_id = subscribe(State(state), data).await?;
+
+    let subscriber = get_subscriber(&db_pool, subscriber_id)
+        .await
+        .context("Failed to retrieve new subscriber")
+        .map_err(e500)?
+        .ok_or_else(|| e500("New subscriber was not stored"))?;
+    Ok((StatusCode::CREATED, Json(subscriber)))
+}
+
+pub async fn api_get_subscriber(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(scopes): Extension<ApiKeyScopes>,
+    WithRejection(Path(subscriber_id), _): WithRejection<Path<Uuid>, ApiError>,
+) -> Result<Json<SubscriberResponse>, ApiError> {
+    scopes.require(ApiKeyScope::SubscribersRead)?;
+    get_subscriber(&db_pool, subscriber_id)
+        .await
+        .context("Failed to retrieve subscriber")
+        .map_err(e500)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("Subscriber not found"))
+}
+
+pub async fn api_list_subscribers(
+    State(AppState { db_pool, .. }): State<AppState>,
+    Extension(scopes): Extension<ApiKeyScopes>,
+    WithRejection(Query(params), _): WithRejection<Query<ListSubscribersParams>, ApiError>,
+) -> Result<Json<ListSubscribersResponse>, ApiError> {
+    scopes.require(ApiKeyScope::SubscribersRead)?;
+    let status = params
+        .status
+        .map(SubscriptionStatus::try_from)
+        .transpose()
+        .map_err(|e| ApiError::validation(e.to_string()))?;
+    let limit = params
+        .limit
+        .unwrap_or(MAX_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let subscribers = list_subscribers(&db_pool, status, limit, offset)
+        .await
+        .context("Failed to list subscribers")
+        .map_err(e500)?;
+    Ok(Json(ListSubscribersResponse { subscribers }))
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberResponse>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberResponse,
+        r#"
+        SELECT id, name, email, status, subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "List subscribers", skip(pool))]
+async fn list_subscribers(
+    pool: &PgPool,
+    status: Option<SubscriptionStatus>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SubscriberResponse>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberResponse,
+        r#"
+        SELECT id, name, email, status, subscribed_at
+        FROM subscriptions
+        WHERE $1::text IS NULL OR status = $1
+        ORDER BY subscribed_at DESC, id
+        LIMIT $2
+        OFFSET $3
+        "#,
+        status.map(|s| s.to_string()),
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -1,13 +1,39 @@
-use axum::response::{Html, IntoResponse};
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
 use axum_flash::IncomingFlashes;
 
-use crate::{session_state::TypedSession, template, utils::get_success_and_error_flash_message};
+use crate::{
+    database::list_db,
+    domain::CsrfToken,
+    session_state::TypedSession,
+    startup::AppState,
+    template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
 
-pub async fn index(flashes: IncomingFlashes, session: TypedSession) -> impl IntoResponse {
+pub async fn index(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    flashes: IncomingFlashes,
+    session: TypedSession,
+) -> Result<impl IntoResponse, InternalServerError> {
     let user_id = session.get_user_id().await.unwrap_or(None);
     let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
-    (
+    let lists = list_db::get_lists(&db_pool)
+        .await
+        .context("Failed to retrieve lists")
+        .map_err(e500)?;
+    Ok((
         flashes,
-        Html(template::index_html(user_id, success_msg, error_msg)),
-    )
+        Html(template::index_html(
+            &csrf_token,
+            user_id,
+            &lists,
+            success_msg,
+            error_msg,
+        )),
+    ))
 }
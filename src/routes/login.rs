@@ -1,3 +1,6 @@
+use std::{net::IpAddr, time::Duration};
+
+use anyhow::Context;
 use axum::{
     extract::State,
     response::{Html, IntoResponse, Redirect, Response},
@@ -9,6 +12,9 @@ use serde::Deserialize;
 
 use crate::{
     authentication,
+    database::two_factor_db,
+    domain::CsrfToken,
+    rate_limit::ClientIp,
     session_state::TypedSession,
     startup::AppState,
     telemetry, template,
@@ -16,6 +22,7 @@ use crate::{
 };
 
 pub async fn login_form(
+    csrf_token: CsrfToken,
     flashes: IncomingFlashes,
     session: TypedSession,
 ) -> Result<Response, InternalServerError> {
@@ -23,7 +30,11 @@ pub async fn login_form(
         Ok((flashes, Redirect::to("/admin/dashboard")).into_response())
     } else {
         let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
-        Ok((flashes, Html(template::login_html(success_msg, error_msg))).into_response())
+        Ok((
+            flashes,
+            Html(template::login_html(&csrf_token, success_msg, error_msg)),
+        )
+            .into_response())
     }
 }
 
@@ -47,6 +58,9 @@ pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
 
+    #[error("Too many failed login attempts, please try again in {} seconds", .0.as_secs())]
+    TooManyAttempts(Duration),
+
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -61,10 +75,12 @@ pub async fn login_with_flash(
     state: State<AppState>,
     flash: Flash,
     session: TypedSession,
+    ClientIp(ip): ClientIp,
     Form(data): Form<LoginFormData>,
 ) -> impl IntoResponse {
-    match login(state, session, data).await {
-        Ok(()) => (flash, Redirect::to("/admin/dashboard")),
+    match login(state, session, ip, data).await {
+        Ok(LoginOutcome::LoggedIn) => (flash, Redirect::to("/admin/dashboard")),
+        Ok(LoginOutcome::TwoFactorRequired) => (flash, Redirect::to("/login/two_factor")),
         // Redirect back to login page with flash message
         Err(e) => {
             tracing::error!("{:?}", e);
@@ -73,26 +89,182 @@ pub async fn login_with_flash(
     }
 }
 
-#[tracing::instrument(skip(db_pool, session, data), fields(username=tracing::field::Empty, user_id=tracing::field::Empty))]
+enum LoginOutcome {
+    LoggedIn,
+    /// The password was correct, but the user still has to enter their second factor.
+    TwoFactorRequired,
+}
+
+#[tracing::instrument(
+    skip(db_pool, login_rate_limiter, session, data),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
 async fn login(
-    State(AppState { db_pool, .. }): State<AppState>,
+    State(AppState {
+        db_pool,
+        login_rate_limiter,
+        ..
+    }): State<AppState>,
     session: TypedSession,
+    ip: Option<IpAddr>,
     data: LoginFormData,
-) -> Result<(), LoginError> {
+) -> Result<LoginOutcome, LoginError> {
     let credentials: authentication::Credentials = data.into();
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    let username = credentials.username.clone();
+    tracing::Span::current().record("username", tracing::field::display(&username));
+
+    // Check the lockout first, so that locked out attempts do not cost a password hash
+    if let Some(remaining) = login_rate_limiter
+        .lockout(&username, ip)
+        .await
+        .context("Failed to check login lockout")?
+    {
+        return Err(LoginError::TooManyAttempts(remaining));
+    }
+
+    let user_id = match authentication::validate_credentials(&db_pool, credentials).await {
+        Ok(user_id) => user_id,
+        Err(e @ authentication::AuthError::InvalidCredentials(_)) => {
+            login_rate_limiter
+                .record_failure(&username, ip)
+                .await
+                .context("Failed to record failed login")?;
+            return Err(LoginError::AuthError(e.into()));
+        }
+        Err(e @ authentication::AuthError::UnexpectedError(_)) => {
+            return Err(LoginError::UnexpectedError(e.into()))
+        }
+    };
+
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+    login_rate_limiter
+        .reset(&username, ip)
+        .await
+        .context("Failed to reset failed logins")?;
 
-    let user_id = authentication::validate_credentials(&db_pool, credentials)
+    let two_factor_enabled = two_factor_db::is_two_factor_enabled(&db_pool, user_id)
         .await
-        .map_err(|e| match e {
-            authentication::AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
-            authentication::AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
-        })?;
+        .context("Failed to check if two-factor authentication is enabled")?;
+    if two_factor_enabled {
+        session
+            .insert_pending_two_factor_user_id(user_id)
+            .await
+            .map_err(|e| LoginError::UnexpectedError(e.into()))?;
+        return Ok(LoginOutcome::TwoFactorRequired);
+    }
 
-    tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
     session
         .insert_user_id(user_id)
         .await
         .map_err(|e| LoginError::UnexpectedError(e.into()))?;
-    Ok(())
+    Ok(LoginOutcome::LoggedIn)
+}
+
+pub async fn two_factor_form(
+    csrf_token: CsrfToken,
+    flashes: IncomingFlashes,
+    session: TypedSession,
+) -> Result<Response, InternalServerError> {
+    if session
+        .get_pending_two_factor_user_id()
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Ok((flashes, Redirect::to("/login")).into_response());
+    }
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::login_two_factor_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct TwoFactorFormData {
+    code: String,
+}
+
+pub async fn two_factor_with_flash(
+    State(AppState {
+        db_pool,
+        login_rate_limiter,
+        ..
+    }): State<AppState>,
+    flash: Flash,
+    session: TypedSession,
+    ClientIp(ip): ClientIp,
+    Form(data): Form<TwoFactorFormData>,
+) -> Result<Response, InternalServerError> {
+    let Some(user_id) = session
+        .get_pending_two_factor_user_id()
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(Redirect::to("/login").into_response());
+    };
+
+    // Once too many codes were wrong, the password has to be entered again as well
+    if let Some(remaining) = login_rate_limiter
+        .two_factor_lockout(user_id, ip)
+        .await
+        .map_err(e500)?
+    {
+        return abandon_two_factor(flash, session, remaining).await;
+    }
+
+    let verified = authentication::verify_second_factor(&db_pool, user_id, &data.code).await?;
+    if !verified {
+        login_rate_limiter
+            .record_two_factor_failure(user_id, ip)
+            .await
+            .map_err(e500)?;
+        if let Some(remaining) = login_rate_limiter
+            .two_factor_lockout(user_id, ip)
+            .await
+            .map_err(e500)?
+        {
+            return abandon_two_factor(flash, session, remaining).await;
+        }
+        return Ok((
+            flash.error("Invalid authentication code"),
+            Redirect::to("/login/two_factor"),
+        )
+            .into_response());
+    }
+
+    login_rate_limiter
+        .reset_two_factor(user_id, ip)
+        .await
+        .map_err(e500)?;
+    session
+        .remove_pending_two_factor_user_id()
+        .await
+        .map_err(e500)?;
+    session.insert_user_id(user_id).await.map_err(e500)?;
+    Ok((flash, Redirect::to("/admin/dashboard")).into_response())
+}
+
+/// Drops the pending login of a user who entered too many wrong codes, sending them back to the
+/// password form.
+async fn abandon_two_factor(
+    flash: Flash,
+    session: TypedSession,
+    remaining: Duration,
+) -> Result<Response, InternalServerError> {
+    session
+        .remove_pending_two_factor_user_id()
+        .await
+        .map_err(e500)?;
+    Ok((
+        flash.error(LoginError::TooManyAttempts(remaining).to_string()),
+        Redirect::to("/login"),
+    )
+        .into_response())
 }
@@ -0,0 +1,332 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    authentication,
+    domain::{CsrfToken, Email, PasswordResetToken},
+    startup::AppState,
+    telemetry, template,
+    utils::{e500, get_success_and_error_flash_message, InternalServerError},
+};
+
+/// How long an emailed password reset link stays valid.
+const RESET_TOKEN_TTL_MINUTES: i32 = 60;
+
+pub async fn password_reset_form(
+    csrf_token: CsrfToken,
+    flashes: IncomingFlashes,
+) -> impl IntoResponse {
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    (
+        flashes,
+        Html(template::password_reset_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+        )),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetFormData {
+    username: String,
+}
+
+pub async fn request_password_reset_with_flash(
+    State(app_state): State<AppState>,
+    flash: Flash,
+    Form(data): Form<PasswordResetFormData>,
+) -> impl IntoResponse {
+    // Issue the reset in the background, so that neither the response nor how long it takes
+    // reveals whether the username exists
+    tokio::spawn(
+        async move {
+            if let Err(e) = issue_password_reset(&app_state, &data.username).await {
+                tracing::error!("{:?}", e);
+            }
+        }
+        .instrument(tracing::Span::current()),
+    );
+
+    (
+        flash.success(
+            "If the account exists, a password reset link has been sent to its email address",
+        ),
+        Redirect::to("/login"),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetQuery {
+    token: String,
+}
+
+pub async fn password_reset_confirm_form(
+    csrf_token: CsrfToken,
+    State(AppState { db_pool, .. }): State<AppState>,
+    flashes: IncomingFlashes,
+    flash: Flash,
+    Query(query): Query<PasswordResetQuery>,
+) -> Result<Response, InternalServerError> {
+    let is_valid = match PasswordResetToken::parse(&query.token) {
+        Ok(token) => is_valid_token(&db_pool, &token)
+            .await
+            .context("Failed to look up password reset token")
+            .map_err(e500)?,
+        Err(_) => false,
+    };
+    if !is_valid {
+        return Ok((
+            flash.error(ResetPasswordError::InvalidToken.to_string()),
+            Redirect::to("/password_reset"),
+        )
+            .into_response());
+    }
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::password_reset_confirm_html(
+            &csrf_token,
+            success_msg,
+            error_msg,
+            &query.token,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordFormData {
+    token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[derive(thiserror::Error)]
+pub enum ResetPasswordError {
+    #[error("The password reset link is invalid or has expired")]
+    InvalidToken,
+
+    #[error("You entered two different new passwords")]
+    DifferentNewPasswords,
+
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ResetPasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        telemetry::error_chain_fmt(self, f)
+    }
+}
+
+pub async fn reset_password_with_flash(
+    State(AppState { db_pool, .. }): State<AppState>,
+    flash: Flash,
+    Form(data): Form<ResetPasswordFormData>,
+) -> Response {
+    let Ok(token) = PasswordResetToken::parse(&data.token) else {
+        return (
+            flash.error(ResetPasswordError::InvalidToken.to_string()),
+            Redirect::to("/password_reset"),
+        )
+            .into_response();
+    };
+
+    match reset_password(&db_pool, &token, data).await {
+        Ok(()) => (
+            flash.success("Your password has been reset, you can now log in"),
+            Redirect::to("/login"),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            let redirect = match e {
+                // Let the user try again with the same link
+                ResetPasswordError::DifferentNewPasswords => Redirect::to(&format!(
+                    "/password_reset/confirm?token={}",
+                    token.expose_secret()
+                )),
+                _ => Redirect::to("/password_reset"),
+            };
+            (flash.error(e.to_string()), redirect).into_response()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Issue password reset", skip(app_state))]
+async fn issue_password_reset(app_state: &AppState, username: &str) -> Result<(), anyhow::Error> {
+    let Some((user_id, email)) = get_user_email(&app_state.db_pool, username)
+        .await
+        .context("Failed to look up user email")?
+    else {
+        tracing::info!("No active user with an email address, skipping password reset");
+        return Ok(());
+    };
+    let email = Email::parse(&email).context("Stored user email is invalid")?;
+
+    let token = PasswordResetToken::generate();
+    store_token(&app_state.db_pool, user_id, &token)
+        .await
+        .context("Failed to store password reset token")?;
+
+    // The reset link should be `<BASE_URL>/password_reset/confirm?token=<TOKEN>`
+    let mut reset_link = app_state
+        .app_base_url
+        .join("password_reset/confirm")
+        .unwrap(); // safely unwrap since it's proper url
+    reset_link.set_query(Some(&format!("token={}", token.expose_secret())));
+
+    let html_body = format!(
+        "<p>Someone asked to reset the password of your Zero2Prod account.</p>\
+        <p><a href=\"{}\">Click here</a> to choose a new password. \
+        The link expires in {} minutes.</p>\
+        <p>If this was not you, you can ignore this email.</p>",
+        reset_link, RESET_TOKEN_TTL_MINUTES
+    );
+    let plain_body = format!(
+        "Someone asked to reset the password of your Zero2Prod account.\n\
+        Visit {} to choose a new password. The link expires in {} minutes.\n\
+        If this was not you, you can ignore this email.",
+        reset_link, RESET_TOKEN_TTL_MINUTES
+    );
+    app_state
+        .email_client
+        .send_email(&email, "Reset your password", &html_body, &plain_body)
+        .await
+        .context("Failed to send password reset email")?;
+
+    Ok(())
+}
+
+async fn reset_password(
+    pool: &PgPool,
+    token: &PasswordResetToken,
+    data: ResetPasswordFormData,
+) -> Result<(), ResetPasswordError> {
+    if data.new_password.expose_secret() != data.new_password_check.expose_secret() {
+        return Err(ResetPasswordError::DifferentNewPasswords);
+    }
+
+    let user_id = consume_token(pool, token)
+        .await
+        .context("Failed to consume password reset token")?
+        .ok_or(ResetPasswordError::InvalidToken)?;
+    authentication::change_password(pool, user_id, data.new_password).await?;
+    // Any other links the user asked for must not work anymore either
+    delete_tokens(pool, user_id)
+        .await
+        .context("Failed to delete remaining password reset tokens")?;
+
+    Ok(())
+}
+
+/// Returns the id and email of the user, or `None` if the user does not exist,
+/// is deactivated, or has no email address to send the reset link to.
+#[tracing::instrument(name = "Get user email", skip(pool))]
+async fn get_user_email(
+    pool: &PgPool,
+    username: &str,
+) -> Result<Option<(Uuid, String)>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, email AS "email!"
+        FROM users
+        WHERE
+            username = $1 AND
+            email IS NOT NULL AND
+            deactivated_at IS NULL
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| (r.user_id, r.email)))
+}
+
+#[tracing::instrument(name = "Store password reset token", skip(pool, token))]
+async fn store_token(
+    pool: &PgPool,
+    user_id: Uuid,
+    token: &PasswordResetToken,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+        VALUES ($1, $2, now() + make_interval(mins => $3))
+        "#,
+        token.hash(),
+        user_id,
+        RESET_TOKEN_TTL_MINUTES
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Check password reset token", skip(pool, token))]
+async fn is_valid_token(pool: &PgPool, token: &PasswordResetToken) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM password_reset_tokens
+        WHERE
+            token_hash = $1 AND
+            expires_at > now()
+        "#,
+        token.hash()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+/// Deletes the token so that it cannot be used again, returning the user it was issued to.
+/// Returns `None` if the token is unknown, expired, or its user has been deactivated.
+#[tracing::instrument(name = "Consume password reset token", skip(pool, token))]
+async fn consume_token(
+    pool: &PgPool,
+    token: &PasswordResetToken,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        DELETE FROM password_reset_tokens
+        WHERE
+            token_hash = $1 AND
+            expires_at > now() AND
+            user_id IN (SELECT user_id FROM users WHERE deactivated_at IS NULL)
+        RETURNING user_id
+        "#,
+        token.hash()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| r.user_id))
+}
+
+#[tracing::instrument(name = "Delete password reset tokens", skip(pool))]
+async fn delete_tokens(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM password_reset_tokens WHERE user_id = $1",
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
@@ -2,15 +2,18 @@ use anyhow::Context;
 use axum::response::Redirect;
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Form};
 use axum_flash::Flash;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use sqlx::{Postgres, Transaction};
 use uuid::{NoContext, Timestamp, Uuid};
 
+use crate::database::list_db::{self, MailingList};
 use crate::domain::{
-    Email, Name, ParseEmailError, ParseNameError, SubscriptionStatus, SubscriptionToken, Url,
+    Email, ListSlug, Name, NewSubscriber, ParseEmailError, ParseNameError, SubscriptionStatus,
+    SubscriptionToken, Url,
 };
-use crate::email_client::{EmailClient, SendEmailError};
+use crate::email_client::{EmailTransport, SendEmailError};
+use crate::rate_limit::ClientIp;
 use crate::startup::AppState;
 use crate::utils::InternalServerError;
 use crate::{telemetry, template};
@@ -28,11 +31,16 @@ pub enum FormDataError {
 pub struct SubscribeFormData {
     pub name: String,
     pub email: String,
+    /// Honeypot hidden from people by the form, so anything filled in came from a bot.
+    #[serde(default)]
+    pub website: String,
+    /// Slug of the list to subscribe to.
+    #[serde(default = "default_list")]
+    pub list: String,
 }
 
-struct NewSubscriber {
-    name: Name,
-    email: Email,
+fn default_list() -> String {
+    ListSlug::DEFAULT.to_string()
 }
 
 impl TryFrom<SubscribeFormData> for NewSubscriber {
@@ -53,6 +61,12 @@ pub enum SubscribeError {
     #[error("Subscription already confirmed")]
     AlreadyConfirmed,
 
+    #[error("Unknown mailing list")]
+    UnknownList,
+
+    #[error("Too many subscription attempts, please try again later")]
+    TooManyAttempts,
+
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -82,6 +96,19 @@ impl IntoResponse for SubscribeError {
                 )
                     .into_response()
             }
+            Self::UnknownList => {
+                // User error, ignore logging
+                (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "Unknown mailing list".to_string(),
+                )
+                    .into_response()
+            }
+            Self::TooManyAttempts => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many subscription attempts".to_string(),
+            )
+                .into_response(),
             Self::UnexpectedError(e) => InternalServerError(e).into_response(),
         }
     }
@@ -90,10 +117,28 @@ impl IntoResponse for SubscribeError {
 pub async fn subscribe_with_flash(
     state: State<AppState>,
     flash: Flash,
+    ClientIp(ip): ClientIp,
     Form(data): Form<SubscribeFormData>,
 ) -> impl IntoResponse {
-    match subscribe(state, data).await {
-        Ok(()) => (flash.success("Thanks for subscribing!"), Redirect::to("/")),
+    if !data.website.is_empty() {
+        // Pretend it worked, so that the bot has no reason to adapt
+        tracing::info!("Ignoring subscription with a filled in honeypot field");
+        return (flash.success("Thanks for subscribing!"), Redirect::to("/"));
+    }
+
+    let result = match state
+        .subscribe_rate_limiter
+        .try_acquire(&data.email, ip)
+        .await
+    {
+        Ok(true) => subscribe(state, data).await,
+        Ok(false) => Err(SubscribeError::TooManyAttempts),
+        Err(e) => Err(anyhow::Error::new(e)
+            .context("Failed to count subscription attempt")
+            .into()),
+    };
+    match result {
+        Ok(_) => (flash.success("Thanks for subscribing!"), Redirect::to("/")),
         Err(e) => {
             tracing::error!("{:?}", e);
             (flash.error(e.to_string()), Redirect::to("/"))
@@ -103,22 +148,32 @@ pub async fn subscribe_with_flash(
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(db_pool, email_client, app_base_url, data),
+    skip(db_pool, email_client, app_base_url, subscribe_rate_limiter, data),
     fields(
         subscriber_email = %data.email,
-        subscriber_name = %data.name
+        subscriber_name = %data.name,
+        list = %data.list
     )
 )]
-async fn subscribe(
+/// Stores a new pending subscriber, or reuses the one with the same email, subscribes them to
+/// the list and sends them a confirmation email for it. Returns the id of the subscriber.
+pub async fn subscribe(
     State(AppState {
         db_pool,
         email_client,
         app_base_url,
+        subscribe_rate_limiter,
+        confirmation_token_ttl,
         ..
     }): State<AppState>,
     data: SubscribeFormData,
-) -> Result<(), SubscribeError> {
+) -> Result<Uuid, SubscribeError> {
+    let list = list_db::get_list_by_slug(&db_pool, &data.list)
+        .await
+        .context("Failed to get mailing list from the database")?
+        .ok_or(SubscribeError::UnknownList)?;
     let mut new_subscriber: NewSubscriber = data.try_into()?;
+    let subscriber_id: Uuid;
     let subscription_token: SubscriptionToken;
 
     // Begin transaction
@@ -135,36 +190,96 @@ async fn subscribe(
     match existing_subscriber {
         // Subscriber already exists
         Some(subscriber) => {
-            // Subscription status already confirmed, return error
-            if subscriber.status == SubscriptionStatus::Confirmed {
-                return Err(SubscribeError::AlreadyConfirmed);
+            subscriber_id = subscriber.id;
+            let list_status = list_db::get_list_subscription_status(
+                &mut transaction,
+                list.list_id,
+                subscriber_id,
+            )
+            .await
+            .context("Failed to get list subscription status from the database")?
+            .map(SubscriptionStatus::try_from)
+            .transpose()
+            .context("Invalid list subscription status in the database")?;
+            match list_status {
+                // Subscription to the list already confirmed, return error
+                Some(SubscriptionStatus::Confirmed) => {
+                    return Err(SubscribeError::AlreadyConfirmed);
+                }
+                Some(SubscriptionStatus::PendingConfirmation) => {
+                    // Do not let repeated submissions flood the subscriber's inbox
+                    if subscribe_rate_limiter
+                        .in_confirmation_cooldown(subscriber_id)
+                        .await
+                        .context("Failed to check confirmation email cooldown")?
+                    {
+                        tracing::info!("Confirmation email sent recently, not sending another one");
+                        return Ok(subscriber_id);
+                    }
+                }
+                // New to this list, or coming back to it
+                _ => {
+                    list_db::upsert_list_subscription(
+                        &mut transaction,
+                        list.list_id,
+                        subscriber_id,
+                        &SubscriptionStatus::PendingConfirmation,
+                    )
+                    .await
+                    .context("Failed to subscribe the subscriber to the list")?;
+                }
             }
-            // Get existing subscription token
-            subscription_token = get_existing_subscription_token(&mut transaction, subscriber.id)
-                .await
-                .context("Failed to get existing subscription token from the database")?;
-            // Replace name with saved name
-            // TODO: Update database with new name if it's different
+            // Reuse the token sent previously while it is fresh, so that the earlier email still
+            // works, otherwise rotate it so that the new email carries a link that lasts
+            let fresh_after = Utc::now() - confirmation_token_ttl / 2;
+            subscription_token = match get_fresh_subscription_token(
+                &mut transaction,
+                subscriber_id,
+                list.list_id,
+                fresh_after,
+            )
+            .await
+            .context("Failed to get existing subscription token from the database")?
+            {
+                Some(token) => token,
+                None => rotate_subscription_token(&mut transaction, subscriber_id, list.list_id)
+                    .await
+                    .context("Failed to rotate the subscription token")?,
+            };
+            // Keep the saved name, subscribers change it through their preferences
             new_subscriber.name = subscriber.name;
 
-            // Rollback transaction
+            // Commit transaction
             transaction
-                .rollback()
+                .commit()
                 .await
-                .context("Failed to rollback SQL transaction after getting existing token")?;
+                .context("Failed to commit SQL transaction to reissue subscription token")?;
         }
         // Subscriber does not exist
         None => {
             // Insert new subscriber into DB
-            let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+            subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
                 .await
                 .context("Failed to insert new subscriber into the database")?;
+            list_db::upsert_list_subscription(
+                &mut transaction,
+                list.list_id,
+                subscriber_id,
+                &SubscriptionStatus::PendingConfirmation,
+            )
+            .await
+            .context("Failed to subscribe the new subscriber to the list")?;
 
             // Generate and insert subscription token into DB
             subscription_token = SubscriptionToken::generate();
-            store_token(&mut transaction, subscriber_id, &subscription_token)
-                .await
-                .context("Failed to store the confirmation token for a new subscriber")?;
+            store_token(
+                &mut transaction,
+                subscriber_id,
+                list.list_id,
+                &subscription_token,
+            )
+            .await
+            .context("Failed to store the confirmation token for a new subscriber")?;
 
             // Commit transaction
             transaction
@@ -176,22 +291,27 @@ async fn subscribe(
 
     // Send confirmation email with subscription token
     send_confirmation_email(
-        &email_client,
-        &new_subscriber,
+        email_client.as_ref(),
+        &new_subscriber.name,
+        &new_subscriber.email,
+        &list,
         &app_base_url,
         &subscription_token,
     )
     .await
     .context("Failed to send a new confirmation email")?;
+    subscribe_rate_limiter
+        .start_confirmation_cooldown(subscriber_id)
+        .await
+        .context("Failed to start confirmation email cooldown")?;
 
-    Ok(())
+    Ok(subscriber_id)
 }
 
 struct ExistingSubscriber {
     id: uuid::Uuid,
     name: Name,
     _email: Email,
-    status: SubscriptionStatus,
 }
 
 #[tracing::instrument(name = "Get existing subscriber using email", skip(transaction, email))]
@@ -200,7 +320,7 @@ async fn get_existing_subscriber(
     email: &Email,
 ) -> Result<Option<ExistingSubscriber>, anyhow::Error> {
     let result = sqlx::query!(
-        "SELECT id, name, email, status FROM subscriptions \
+        "SELECT id, name, email FROM subscriptions \
         WHERE email = $1",
         email.as_ref()
     )
@@ -212,29 +332,63 @@ async fn get_existing_subscriber(
             id: r.id,
             name: Name::parse(&r.name)?,
             _email: Email::parse(&r.email)?,
-            status: r.status.try_into()?,
         })),
         None => Ok(None),
     }
 }
 
+/// Returns the latest subscription token of the subscriber for the list if it was issued after
+/// `fresh_after`.
 #[tracing::instrument(
-    name = "Get existing token using subscriber id",
+    name = "Get fresh token using subscriber id",
     skip(transaction, subscriber_id)
 )]
-async fn get_existing_subscription_token(
+async fn get_fresh_subscription_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
-) -> Result<SubscriptionToken, anyhow::Error> {
+    list_id: Uuid,
+    fresh_after: DateTime<Utc>,
+) -> Result<Option<SubscriptionToken>, anyhow::Error> {
     let result = sqlx::query!(
-        "SELECT subscription_token FROM subscription_tokens \
-        WHERE subscriber_id = $1",
-        subscriber_id
+        r#"
+        SELECT subscription_token FROM subscription_tokens
+        WHERE subscriber_id = $1 AND list_id = $2 AND created_at > $3
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        subscriber_id,
+        list_id,
+        fresh_after
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+
+    match result {
+        Some(r) => Ok(Some(SubscriptionToken::parse(&r.subscription_token)?)),
+        None => Ok(None),
+    }
+}
+
+/// Replaces every token of the subscriber for the list with a newly generated one, invalidating
+/// links sent in earlier emails.
+#[tracing::instrument(name = "Rotate subscription token", skip(transaction))]
+pub(crate) async fn rotate_subscription_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<SubscriptionToken, sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = $2",
+        subscriber_id,
+        list_id
     )
-    .fetch_one(&mut **transaction)
+    .execute(&mut **transaction)
     .await?;
 
-    Ok(SubscriptionToken::parse(&result.subscription_token)?)
+    let subscription_token = SubscriptionToken::generate();
+    store_token(transaction, subscriber_id, list_id, &subscription_token).await?;
+
+    Ok(subscription_token)
 }
 
 #[tracing::instrument(
@@ -266,11 +420,13 @@ async fn insert_subscriber(
 
 #[tracing::instrument(
     name = "Sending confirmation email to new subscriber",
-    skip(email_client, subscriber, app_base_url, subscription_token)
+    skip(email_client, name, email, list, app_base_url, subscription_token)
 )]
-async fn send_confirmation_email(
-    email_client: &EmailClient,
-    subscriber: &NewSubscriber,
+pub(crate) async fn send_confirmation_email(
+    email_client: &dyn EmailTransport,
+    name: &Name,
+    email: &Email,
+    list: &MailingList,
     app_base_url: &Url,
     subscription_token: &SubscriptionToken,
 ) -> Result<(), SendEmailError> {
@@ -281,15 +437,16 @@ async fn send_confirmation_email(
         subscription_token.as_str()
     )));
 
-    let html_body = template::confirmation_email_html(&subscriber.name, &confirmation_link);
+    let html_body = template::confirmation_email_html(name, &list.name, &confirmation_link);
     let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link,
+        "Welcome to our {} list!\nVisit {} to confirm your subscription.",
+        list.name, confirmation_link,
     );
 
     email_client
-        .send_email(&subscriber.email, "Welcome!", &html_body, &plain_body)
+        .send_email(email, "Welcome!", &html_body, &plain_body)
         .await
+        .map(|_| ())
 }
 
 #[tracing::instrument(
@@ -299,15 +456,17 @@ async fn send_confirmation_email(
 async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Uuid,
     subscription_token: &SubscriptionToken,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"
-        INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-        VALUES ($1, $2)
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        VALUES ($1, $2, $3)
         "#,
         subscription_token.as_str(),
         subscriber_id,
+        list_id,
     )
     .execute(&mut **transaction)
     .await?;
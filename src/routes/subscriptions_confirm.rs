@@ -4,11 +4,13 @@ use axum::{
     http::StatusCode,
     response::IntoResponse,
 };
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
+    database::list_db,
     domain::{ParseSubscriptionTokenError, SubscriptionStatus, SubscriptionToken},
     startup::AppState,
     telemetry,
@@ -28,6 +30,9 @@ pub enum ConfirmSubscriptionError {
     #[error("Token not found")]
     TokenNotFound,
 
+    #[error("Token expired")]
+    TokenExpired,
+
     #[error("Subscription already confirmed")]
     AlreadyConfirmed,
 
@@ -52,6 +57,15 @@ impl IntoResponse for ConfirmSubscriptionError {
                 )
                     .into_response()
             }
+            Self::TokenExpired => {
+                // User error, ignore logging
+                (
+                    StatusCode::GONE,
+                    "Subscription token has expired, request a new confirmation email at /subscribe/resend"
+                        .to_string(),
+                )
+                    .into_response()
+            }
             Self::AlreadyConfirmed => {
                 // Probably user error, ignore logging
                 (
@@ -65,80 +79,113 @@ impl IntoResponse for ConfirmSubscriptionError {
     }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(db_pool, params))]
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(db_pool, confirmation_token_ttl, params)
+)]
 pub async fn confirm(
-    State(AppState { db_pool, .. }): State<AppState>,
+    State(AppState {
+        db_pool,
+        confirmation_token_ttl,
+        ..
+    }): State<AppState>,
     Query(params): Query<Parameters>,
 ) -> Result<(), ConfirmSubscriptionError> {
     let subscription_token = SubscriptionToken::parse(&params.subscription_token)?;
 
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")?;
+
     // Get subscriber ID from token
-    let subscriber_id = get_subscriber_id_from_token(&db_pool, &subscription_token)
+    let token = get_token_record(&mut transaction, &subscription_token)
         .await
         .context("Failed to get subscriber_id associated with the provided token")?;
 
     // Token not found, return error
-    if subscriber_id.is_none() {
+    let Some(token) = token else {
         return Err(ConfirmSubscriptionError::TokenNotFound);
+    };
+
+    // Check if subscription to the list already confirmed. Links left in the inbox must not
+    // undo an unsubscribe either.
+    let status =
+        list_db::get_list_subscription_status(&mut transaction, token.list_id, token.subscriber_id)
+            .await
+            .context("Failed to get list subscription status")?
+            .map(SubscriptionStatus::try_from)
+            .transpose()
+            .context("Stored list subscription status is invalid")?;
+    match status {
+        Some(SubscriptionStatus::PendingConfirmation) => {}
+        Some(SubscriptionStatus::Confirmed) => {
+            return Err(ConfirmSubscriptionError::AlreadyConfirmed)
+        }
+        Some(SubscriptionStatus::Unsubscribed) | None => {
+            return Err(ConfirmSubscriptionError::TokenNotFound)
+        }
     }
 
-    // Check if subscription already confirmed
-    let status = get_subscriber_status(&db_pool, subscriber_id.unwrap())
-        .await
-        .context("Failed to get subscriber status")?;
-    if status == SubscriptionStatus::Confirmed {
-        return Err(ConfirmSubscriptionError::AlreadyConfirmed);
+    // Token too old, the subscriber has to request a new one
+    if token.created_at + confirmation_token_ttl < Utc::now() {
+        return Err(ConfirmSubscriptionError::TokenExpired);
     }
 
-    // Confirm subscriber using retrieved ID
-    confirm_subscriber(&db_pool, subscriber_id.unwrap())
+    // Confirm the subscription to the list the token was issued for, then invalidate every link
+    // sent for it
+    list_db::confirm_list_subscription(&mut transaction, token.list_id, token.subscriber_id)
         .await
         .context("Failed to confirm subscriber in the database")?;
+    delete_tokens(&mut transaction, token.subscriber_id, token.list_id)
+        .await
+        .context("Failed to delete subscription tokens")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to confirm subscriber")?;
 
     Ok(())
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(pool, subscriber_id))]
-async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"UPDATE subscriptions SET status = $1 WHERE id = $2"#,
-        SubscriptionStatus::Confirmed.to_string(),
-        subscriber_id,
-    )
-    .execute(pool)
-    .await?;
-
-    Ok(())
+struct TokenRecord {
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    created_at: DateTime<Utc>,
 }
 
-#[tracing::instrument(name = "Get subscriber id using token", skip(pool, subscription_token))]
-async fn get_subscriber_id_from_token(
-    pool: &PgPool,
+#[tracing::instrument(
+    name = "Get subscriber id using token",
+    skip(transaction, subscription_token)
+)]
+async fn get_token_record(
+    transaction: &mut Transaction<'_, Postgres>,
     subscription_token: &SubscriptionToken,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        "SELECT subscriber_id FROM subscription_tokens \
-        WHERE subscription_token = $1",
+) -> Result<Option<TokenRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        TokenRecord,
+        "SELECT subscriber_id, list_id, created_at FROM subscription_tokens \
+        WHERE subscription_token = $1 \
+        FOR UPDATE",
         subscription_token.as_str(),
     )
-    .fetch_optional(pool)
-    .await?;
-
-    Ok(result.map(|r| r.subscriber_id))
+    .fetch_optional(&mut **transaction)
+    .await
 }
 
-#[tracing::instrument(name = "Get subscription status", skip(pool, subscriber_id))]
-async fn get_subscriber_status(
-    pool: &PgPool,
+#[tracing::instrument(name = "Delete subscription tokens", skip(transaction))]
+async fn delete_tokens(
+    transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
-) -> Result<SubscriptionStatus, anyhow::Error> {
-    let result = sqlx::query!(
-        "SELECT status FROM subscriptions \
-        WHERE id = $1",
+    list_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND list_id = $2",
         subscriber_id,
+        list_id
     )
-    .fetch_one(pool)
+    .execute(&mut **transaction)
     .await?;
 
-    Ok(result.status.try_into()?)
+    Ok(())
 }
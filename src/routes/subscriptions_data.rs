@@ -0,0 +1,195 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_flash::Flash;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    database::{
+        list_db::{self, ListMembership},
+        subscriber_db,
+    },
+    routes::{PreferencesError, PreferencesParameters},
+    startup::AppState,
+};
+
+/// Everything we store about a subscriber, as handed to them on request.
+#[derive(Debug, Serialize)]
+pub struct SubscriberData {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+    /// New email address waiting to be verified, if any.
+    pub pending_email: Option<String>,
+    pub lists: Vec<ListMembership>,
+    pub tags: Vec<String>,
+    pub deliveries: Vec<DeliveryRecord>,
+    pub failed_deliveries: Vec<FailedDeliveryRecord>,
+    /// Titles of the newsletters still waiting to be sent to the subscriber.
+    pub queued_newsletters: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryRecord {
+    pub newsletter_title: String,
+    pub status: String,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedDeliveryRecord {
+    pub newsletter_title: String,
+    pub last_error: String,
+    pub n_attempts: i32,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// Lets the subscriber download all the data we hold about them as JSON.
+#[tracing::instrument(name = "Export subscriber data", skip(db_pool, hmac_secret, params))]
+pub async fn download_subscriber_data(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+) -> Result<Response, PreferencesError> {
+    params.verify(&hmac_secret)?;
+
+    let data = get_subscriber_data(&db_pool, params.subscriber_id)
+        .await
+        .context("Failed to retrieve subscriber data")?
+        .ok_or(PreferencesError::SubscriberNotFound)?;
+
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"zero2prod-subscriber-data.json\"",
+        )],
+        Json(data),
+    )
+        .into_response())
+}
+
+/// Permanently deletes the subscriber and everything stored about them.
+#[tracing::instrument(name = "Erase subscriber", skip(db_pool, hmac_secret, params, flash))]
+pub async fn erase_subscriber_with_flash(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+    flash: Flash,
+) -> Result<Response, PreferencesError> {
+    params.verify(&hmac_secret)?;
+
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")?;
+    subscriber_db::delete_subscriber(&mut transaction, params.subscriber_id, &hmac_secret)
+        .await
+        .context("Failed to erase subscriber")?
+        .ok_or(PreferencesError::SubscriberNotFound)?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to erase subscriber")?;
+
+    Ok((
+        flash.success("Your subscription and all your data have been deleted"),
+        Redirect::to("/"),
+    )
+        .into_response())
+}
+
+#[tracing::instrument(name = "Get subscriber data", skip(pool))]
+async fn get_subscriber_data(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberData>, sqlx::Error> {
+    let Some(subscriber) = sqlx::query!(
+        r#"
+        SELECT
+            s.id, s.name, s.email, s.status, s.subscribed_at, c.new_email AS "pending_email?",
+            ARRAY(
+                SELECT tag FROM subscriber_tags t WHERE t.subscriber_id = s.id ORDER BY tag
+            ) AS "tags!"
+        FROM subscriptions s
+        LEFT JOIN subscription_email_changes c ON c.subscriber_id = s.id
+        WHERE s.id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let lists = list_db::get_list_memberships(pool, subscriber_id).await?;
+    let deliveries = sqlx::query_as!(
+        DeliveryRecord,
+        r#"
+        SELECT n.title AS newsletter_title, d.status, d.sent_at
+        FROM newsletter_delivery d
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        WHERE d.subscriber_email = $1
+        ORDER BY d.sent_at
+        "#,
+        subscriber.email
+    )
+    .fetch_all(pool)
+    .await?;
+    let failed_deliveries = sqlx::query_as!(
+        FailedDeliveryRecord,
+        r#"
+        SELECT n.title AS newsletter_title, f.last_error, f.n_attempts, f.failed_at
+        FROM failed_deliveries f
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        WHERE f.subscriber_email = $1
+        ORDER BY f.failed_at
+        "#,
+        subscriber.email
+    )
+    .fetch_all(pool)
+    .await?;
+    let queued_newsletters = sqlx::query!(
+        r#"
+        SELECT n.title
+        FROM issue_delivery_queue q
+        JOIN newsletter_issues n USING (newsletter_issue_id)
+        WHERE q.subscriber_email = $1
+        "#,
+        subscriber.email
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| r.title)
+    .collect();
+
+    Ok(Some(SubscriberData {
+        id: subscriber.id,
+        name: subscriber.name,
+        email: subscriber.email,
+        status: subscriber.status,
+        subscribed_at: subscriber.subscribed_at,
+        pending_email: subscriber.pending_email,
+        lists,
+        tags: subscriber.tags,
+        deliveries,
+        failed_deliveries,
+        queued_newsletters,
+    }))
+}
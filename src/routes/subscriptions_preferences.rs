@@ -0,0 +1,680 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use chrono::{DateTime, Utc};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    database::list_db,
+    domain::{
+        Email, Name, ParseSignedTokenError, ParseSubscriptionTokenError, PreferencesToken,
+        SubscriptionToken, Url,
+    },
+    rate_limit::ClientIp,
+    startup::AppState,
+    telemetry, template,
+    utils::{get_success_and_error_flash_message, InternalServerError},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct PreferencesParameters {
+    pub(crate) subscriber_id: Uuid,
+    pub(crate) token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("{0}")]
+    TokenValidationError(#[from] ParseSignedTokenError),
+
+    #[error("Token does not match subscriber")]
+    TokenMismatch,
+
+    #[error("Subscriber not found")]
+    SubscriberNotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        telemetry::error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for PreferencesError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::TokenValidationError(_) | Self::TokenMismatch | Self::SubscriberNotFound => {
+                // User error, ignore logging
+                (
+                    StatusCode::UNAUTHORIZED,
+                    "Preferences token validation error".to_string(),
+                )
+                    .into_response()
+            }
+            Self::UnexpectedError(e) => InternalServerError(e).into_response(),
+        }
+    }
+}
+
+/// Builds the link that lets a subscriber manage their preferences without logging in.
+/// The link should be `<BASE_URL>/subscriptions/preferences?subscriber_id=<ID>&token=<TOKEN>`
+pub fn preferences_link(
+    app_base_url: &Url,
+    subscriber_id: Uuid,
+    hmac_secret: &SecretString,
+) -> Url {
+    let mut link = app_base_url.join("subscriptions/preferences").unwrap(); // safely unwrap since it's proper url
+    link.set_query(Some(&preferences_query(subscriber_id, hmac_secret)));
+    link
+}
+
+fn preferences_query(subscriber_id: Uuid, hmac_secret: &SecretString) -> String {
+    let token = PreferencesToken::generate(subscriber_id, hmac_secret);
+    format!("subscriber_id={}&token={}", subscriber_id, token.as_str())
+}
+
+impl PreferencesParameters {
+    pub(crate) fn verify(&self, hmac_secret: &SecretString) -> Result<(), PreferencesError> {
+        let token = PreferencesToken::parse(&self.token)?;
+        if !token.verify(self.subscriber_id, hmac_secret) {
+            return Err(PreferencesError::TokenMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Path of the preferences page the parameters were submitted from.
+    fn redirect(&self) -> Redirect {
+        Redirect::to(&format!(
+            "/subscriptions/preferences?subscriber_id={}&token={}",
+            self.subscriber_id, self.token
+        ))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriberPreferences {
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    /// New email address waiting to be verified, if any.
+    pub pending_email: Option<String>,
+}
+
+#[tracing::instrument(
+    name = "Show subscriber preferences",
+    skip(db_pool, hmac_secret, params)
+)]
+pub async fn preferences_page(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+    flashes: IncomingFlashes,
+) -> Result<Response, PreferencesError> {
+    params.verify(&hmac_secret)?;
+
+    let preferences = get_subscriber_preferences(&db_pool, params.subscriber_id)
+        .await
+        .context("Failed to retrieve subscriber preferences")?
+        .ok_or(PreferencesError::SubscriberNotFound)?;
+
+    let lists = list_db::get_list_preferences(&db_pool, params.subscriber_id)
+        .await
+        .context("Failed to retrieve list preferences")?;
+
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    Ok((
+        flashes,
+        Html(template::preferences_html(
+            success_msg,
+            error_msg,
+            params.subscriber_id,
+            &params.token,
+            &preferences,
+            &lists,
+        )),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateNameFormData {
+    name: String,
+}
+
+#[tracing::instrument(
+    name = "Update subscriber name",
+    skip(db_pool, hmac_secret, params, flash)
+)]
+pub async fn update_name_with_flash(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+    flash: Flash,
+    Form(data): Form<UpdateNameFormData>,
+) -> Result<Response, PreferencesError> {
+    params.verify(&hmac_secret)?;
+
+    let name = match Name::parse(&data.name) {
+        Ok(name) => name,
+        Err(e) => return Ok((flash.error(e.to_string()), params.redirect()).into_response()),
+    };
+    let found = update_subscriber_name(&db_pool, params.subscriber_id, &name)
+        .await
+        .context("Failed to update subscriber name")?;
+    if !found {
+        return Err(PreferencesError::SubscriberNotFound);
+    }
+
+    Ok((
+        flash.success("Your name has been updated"),
+        params.redirect(),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateListSubscriptionFormData {
+    list_id: Uuid,
+    subscribed: bool,
+}
+
+/// Joins or leaves a single list, leaving the subscriptions to other lists untouched.
+#[tracing::instrument(
+    name = "Update subscriber list subscription",
+    skip(db_pool, hmac_secret, params, flash)
+)]
+pub async fn update_list_subscription_with_flash(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+    flash: Flash,
+    Form(data): Form<UpdateListSubscriptionFormData>,
+) -> Result<Response, PreferencesError> {
+    params.verify(&hmac_secret)?;
+
+    let Some(list) = list_db::get_list(&db_pool, data.list_id)
+        .await
+        .context("Failed to retrieve list")?
+    else {
+        return Ok((flash.error("This list does not exist"), params.redirect()).into_response());
+    };
+
+    let mut transaction = db_pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")?;
+    let message = if data.subscribed {
+        list_db::resubscribe_to_list(&mut transaction, list.list_id, params.subscriber_id)
+            .await
+            .map_err(|e| match e {
+                // The subscriber does not exist
+                sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+                    PreferencesError::SubscriberNotFound
+                }
+                e => anyhow::Error::new(e)
+                    .context("Failed to subscribe to the list")
+                    .into(),
+            })?;
+        format!("You are now subscribed to {}", list.name)
+    } else {
+        let found =
+            list_db::unsubscribe_from_list(&mut transaction, list.list_id, params.subscriber_id)
+                .await
+                .context("Failed to unsubscribe from the list")?;
+        if !found {
+            return Ok((
+                flash.error(format!("You are not subscribed to {}", list.name)),
+                params.redirect(),
+            )
+                .into_response());
+        }
+        format!("You have been unsubscribed from {}", list.name)
+    };
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update list subscription")?;
+
+    Ok((flash.success(message), params.redirect()).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailFormData {
+    email: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum ChangeEmailError {
+    #[error("This is already your email address")]
+    SameEmail,
+
+    #[error("This email address is already subscribed")]
+    EmailTaken,
+
+    #[error("Too many subscription attempts, please try again later")]
+    TooManyAttempts,
+
+    #[error("Something went wrong")]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ChangeEmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        telemetry::error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(
+    name = "Request subscriber email change",
+    skip(app_state, params, flash)
+)]
+pub async fn request_email_change_with_flash(
+    State(app_state): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+    flash: Flash,
+    ClientIp(ip): ClientIp,
+    Form(data): Form<ChangeEmailFormData>,
+) -> Result<Response, PreferencesError> {
+    params.verify(&app_state.hmac_secret)?;
+
+    let email = match Email::parse(&data.email) {
+        Ok(email) => email,
+        Err(e) => return Ok((flash.error(e.to_string()), params.redirect()).into_response()),
+    };
+    // Every request sends an email to an address of the requester's choosing
+    let result = match app_state
+        .subscribe_rate_limiter
+        .try_acquire(email.as_ref(), ip)
+        .await
+    {
+        Ok(true) => request_email_change(&app_state, params.subscriber_id, &email).await,
+        Ok(false) => Err(ChangeEmailError::TooManyAttempts),
+        Err(e) => Err(anyhow::Error::new(e)
+            .context("Failed to count subscription attempt")
+            .into()),
+    };
+
+    match result {
+        Ok(()) => Ok((
+            flash.success(format!(
+                "A verification link has been sent to {}, your email address changes once you open it",
+                email.as_ref()
+            )),
+            params.redirect(),
+        )
+            .into_response()),
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            Ok((flash.error(e.to_string()), params.redirect()).into_response())
+        }
+    }
+}
+
+/// Stores the new email address as pending and sends a verification link to it.
+async fn request_email_change(
+    app_state: &AppState,
+    subscriber_id: Uuid,
+    new_email: &Email,
+) -> Result<(), ChangeEmailError> {
+    let current = get_subscriber_preferences(&app_state.db_pool, subscriber_id)
+        .await
+        .context("Failed to retrieve subscriber preferences")?
+        .context("Subscriber not found")?;
+    if current.email == new_email.as_ref() {
+        return Err(ChangeEmailError::SameEmail);
+    }
+    if is_email_subscribed(&app_state.db_pool, new_email)
+        .await
+        .context("Failed to check whether the email is subscribed")?
+    {
+        return Err(ChangeEmailError::EmailTaken);
+    }
+
+    let verification_token = SubscriptionToken::generate();
+    store_email_change(
+        &app_state.db_pool,
+        subscriber_id,
+        new_email,
+        &verification_token,
+    )
+    .await
+    .context("Failed to store the pending email change")?;
+
+    // The verification link should be `<BASE_URL>/subscriptions/preferences/email?token=<TOKEN>`
+    let mut verification_link = app_state
+        .app_base_url
+        .join("subscriptions/preferences/email")
+        .unwrap(); // safely unwrap since it's proper url
+    verification_link.set_query(Some(&format!("token={}", verification_token.as_str())));
+
+    let html_body = format!(
+        "<p>Hello {},</p>\
+        <p>You asked to receive the Zero2Prod newsletter at this address. \
+        <a href=\"{}\">Click here</a> to confirm the change.</p>\
+        <p>If this was not you, you can ignore this email.</p>",
+        current.name, verification_link
+    );
+    let plain_body = format!(
+        "Hello {},\n\
+        You asked to receive the Zero2Prod newsletter at this address.\n\
+        Visit {} to confirm the change.\n\
+        If this was not you, you can ignore this email.",
+        current.name, verification_link
+    );
+    app_state
+        .email_client
+        .send_email(
+            new_email,
+            "Confirm your new email address",
+            &html_body,
+            &plain_body,
+        )
+        .await
+        .context("Failed to send email change verification email")?;
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmEmailChangeParameters {
+    token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum ConfirmEmailChangeError {
+    #[error("{0}")]
+    TokenValidationError(#[from] ParseSubscriptionTokenError),
+
+    #[error("Token not found")]
+    TokenNotFound,
+
+    #[error("Token expired")]
+    TokenExpired,
+
+    #[error("Email already subscribed")]
+    EmailTaken,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for ConfirmEmailChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        telemetry::error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for ConfirmEmailChangeError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::TokenValidationError(_) | Self::TokenNotFound => {
+                // User error, ignore logging
+                (
+                    StatusCode::UNAUTHORIZED,
+                    "Email change token validation error".to_string(),
+                )
+                    .into_response()
+            }
+            Self::TokenExpired => {
+                // User error, ignore logging
+                (
+                    StatusCode::GONE,
+                    "Email change token has expired, request the change again from your preferences"
+                        .to_string(),
+                )
+                    .into_response()
+            }
+            Self::EmailTaken => {
+                // Probably user error, ignore logging
+                (
+                    StatusCode::CONFLICT,
+                    "Email address already subscribed".to_string(),
+                )
+                    .into_response()
+            }
+            Self::UnexpectedError(e) => InternalServerError(e).into_response(),
+        }
+    }
+}
+
+/// Applies a pending email change once the subscriber follows the link sent to the new address.
+#[tracing::instrument(
+    name = "Confirm subscriber email change",
+    skip(db_pool, hmac_secret, confirmation_token_ttl, params, flash)
+)]
+pub async fn confirm_email_change(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        confirmation_token_ttl,
+        ..
+    }): State<AppState>,
+    Query(params): Query<ConfirmEmailChangeParameters>,
+    flash: Flash,
+) -> Result<Response, ConfirmEmailChangeError> {
+    let token = SubscriptionToken::parse(&params.token)?;
+    let change = get_email_change(&db_pool, &token)
+        .await
+        .context("Failed to retrieve the pending email change")?
+        .ok_or(ConfirmEmailChangeError::TokenNotFound)?;
+    if change.created_at + confirmation_token_ttl < Utc::now() {
+        return Err(ConfirmEmailChangeError::TokenExpired);
+    }
+
+    apply_email_change(&db_pool, &change).await?;
+
+    let redirect = format!(
+        "/subscriptions/preferences?{}",
+        preferences_query(change.subscriber_id, &hmac_secret)
+    );
+    Ok((
+        flash.success("Your email address has been updated"),
+        Redirect::to(&redirect),
+    )
+        .into_response())
+}
+
+#[tracing::instrument(name = "Get subscriber preferences", skip(pool))]
+async fn get_subscriber_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberPreferences>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberPreferences,
+        r#"
+        SELECT s.name, s.email, s.status, c.new_email AS "pending_email?"
+        FROM subscriptions s
+        LEFT JOIN subscription_email_changes c ON c.subscriber_id = s.id
+        WHERE s.id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns `false` if the subscriber does not exist.
+#[tracing::instrument(name = "Update subscriber name in the database", skip(pool, name))]
+async fn update_subscriber_name(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    name: &Name,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query!(
+        "UPDATE subscriptions SET name = $1 WHERE id = $2",
+        name.as_ref(),
+        subscriber_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Check whether email is subscribed", skip(pool, email))]
+async fn is_email_subscribed(pool: &PgPool, email: &Email) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM subscriptions WHERE email = $1) AS "exists!""#,
+        email.as_ref()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result.exists)
+}
+
+/// Replaces any previous pending change of the subscriber.
+#[tracing::instrument(
+    name = "Store pending email change",
+    skip(pool, new_email, verification_token)
+)]
+async fn store_email_change(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    new_email: &Email,
+    verification_token: &SubscriptionToken,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_email_changes (subscriber_id, new_email, verification_token)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (subscriber_id) DO UPDATE
+        SET
+            new_email = EXCLUDED.new_email,
+            verification_token = EXCLUDED.verification_token,
+            created_at = now()
+        "#,
+        subscriber_id,
+        new_email.as_ref(),
+        verification_token.as_str()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+struct EmailChange {
+    subscriber_id: Uuid,
+    new_email: String,
+    created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "Get pending email change using token", skip(pool, token))]
+async fn get_email_change(
+    pool: &PgPool,
+    token: &SubscriptionToken,
+) -> Result<Option<EmailChange>, sqlx::Error> {
+    sqlx::query_as!(
+        EmailChange,
+        r#"
+        SELECT subscriber_id, new_email, created_at
+        FROM subscription_email_changes
+        WHERE verification_token = $1
+        "#,
+        token.as_str()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Apply email change", skip(pool, change))]
+async fn apply_email_change(
+    pool: &PgPool,
+    change: &EmailChange,
+) -> Result<(), ConfirmEmailChangeError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire Postgres connection from the pool")?;
+
+    let old_email = sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET email = $2
+        FROM subscriptions previous
+        WHERE s.id = previous.id AND s.id = $1
+        RETURNING previous.email
+        "#,
+        change.subscriber_id,
+        change.new_email
+    )
+    .fetch_one(&mut *transaction)
+    .await;
+    let old_email = match old_email {
+        Ok(r) => r.email,
+        // Someone subscribed with the address since the change was requested
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Err(ConfirmEmailChangeError::EmailTaken)
+        }
+        Err(e) => {
+            return Err(anyhow::Error::new(e)
+                .context("Failed to update email")
+                .into())
+        }
+    };
+
+    // Deliveries still queued for the old address should go to the new one
+    sqlx::query!(
+        "UPDATE issue_delivery_queue SET subscriber_email = $2 WHERE subscriber_email = $1",
+        old_email,
+        change.new_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move queued deliveries to the new email")?;
+    // Delivery logs follow the subscriber too, so that their data export and erasure find them
+    sqlx::query!(
+        "UPDATE newsletter_delivery SET subscriber_email = $2 WHERE subscriber_email = $1",
+        old_email,
+        change.new_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move delivery logs to the new email")?;
+    sqlx::query!(
+        "UPDATE failed_deliveries SET subscriber_email = $2 WHERE subscriber_email = $1",
+        old_email,
+        change.new_email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to move failed deliveries to the new email")?;
+
+    sqlx::query!(
+        "DELETE FROM subscription_email_changes WHERE subscriber_id = $1",
+        change.subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove the pending email change")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to change email")?;
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect},
+    Form,
+};
+use axum_flash::{Flash, IncomingFlashes};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::list_db,
+    domain::{Email, Name},
+    rate_limit::ClientIp,
+    routes::{rotate_subscription_token, send_confirmation_email},
+    startup::AppState,
+    template,
+    utils::get_success_and_error_flash_message,
+};
+
+pub async fn resend_confirmation_form(flashes: IncomingFlashes) -> impl IntoResponse {
+    let (success_msg, error_msg) = get_success_and_error_flash_message(&flashes);
+    (
+        flashes,
+        Html(template::resend_confirmation_html(success_msg, error_msg)),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendConfirmationFormData {
+    email: String,
+}
+
+pub async fn resend_confirmation_with_flash(
+    State(app_state): State<AppState>,
+    flash: Flash,
+    ClientIp(ip): ClientIp,
+    Form(data): Form<ResendConfirmationFormData>,
+) -> impl IntoResponse {
+    let redirect = Redirect::to("/subscribe/resend");
+    match app_state
+        .subscribe_rate_limiter
+        .try_acquire(&data.email, ip)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                flash.error("Too many subscription attempts, please try again later"),
+                redirect,
+            )
+        }
+        Err(e) => {
+            tracing::error!("{:?}", e);
+            return (flash.error("Something went wrong"), redirect);
+        }
+    }
+
+    if let Err(e) = resend_confirmation(&app_state, &data.email).await {
+        tracing::error!("{:?}", e);
+        return (flash.error("Something went wrong"), redirect);
+    }
+
+    // Same message whether or not anything was sent, so that the page does not reveal who subscribed
+    (
+        flash.success(
+            "If the email address is waiting for confirmation, a new confirmation link has been sent to it",
+        ),
+        redirect,
+    )
+}
+
+/// Sends a subscriber a confirmation email with a newly issued token for every list they have
+/// yet to confirm. Does nothing for unknown emails, subscribers with nothing to confirm, or ones
+/// that were sent an email too recently.
+#[tracing::instrument(name = "Resend confirmation email", skip(app_state))]
+async fn resend_confirmation(app_state: &AppState, email: &str) -> Result<(), anyhow::Error> {
+    let Ok(email) = Email::parse(email) else {
+        return Ok(());
+    };
+    let Some((subscriber_id, name)) = get_subscriber(app_state, &email).await? else {
+        return Ok(());
+    };
+    let pending_lists = list_db::get_pending_lists(&app_state.db_pool, subscriber_id)
+        .await
+        .context("Failed to get the lists waiting for confirmation")?;
+    if pending_lists.is_empty() {
+        return Ok(());
+    }
+    if app_state
+        .subscribe_rate_limiter
+        .in_confirmation_cooldown(subscriber_id)
+        .await
+        .context("Failed to check confirmation email cooldown")?
+    {
+        tracing::info!("Confirmation email sent recently, not sending another one");
+        return Ok(());
+    }
+
+    for list in pending_lists {
+        let mut transaction = app_state
+            .db_pool
+            .begin()
+            .await
+            .context("Failed to acquire Postgres connection from the pool")?;
+        let subscription_token =
+            rotate_subscription_token(&mut transaction, subscriber_id, list.list_id)
+                .await
+                .context("Failed to rotate the subscription token")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction to rotate subscription token")?;
+
+        send_confirmation_email(
+            app_state.email_client.as_ref(),
+            &name,
+            &email,
+            &list,
+            &app_state.app_base_url,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send a new confirmation email")?;
+    }
+    app_state
+        .subscribe_rate_limiter
+        .start_confirmation_cooldown(subscriber_id)
+        .await
+        .context("Failed to start confirmation email cooldown")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get subscriber using email", skip(app_state, email))]
+async fn get_subscriber(
+    app_state: &AppState,
+    email: &Email,
+) -> Result<Option<(Uuid, Name)>, anyhow::Error> {
+    let result = sqlx::query!(
+        "SELECT id, name FROM subscriptions WHERE email = $1",
+        email.as_ref()
+    )
+    .fetch_optional(app_state.db_pool.as_ref())
+    .await?;
+
+    match result {
+        Some(r) => Ok(Some((r.id, Name::parse(&r.name)?))),
+        None => Ok(None),
+    }
+}
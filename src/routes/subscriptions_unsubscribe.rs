@@ -0,0 +1,206 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse},
+};
+use secrecy::SecretString;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    database::list_db,
+    domain::{ParseSignedTokenError, SubscriptionStatus, UnsubscribeToken, Url},
+    startup::AppState,
+    telemetry, template,
+    utils::InternalServerError,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeParameters {
+    subscriber_id: Uuid,
+    /// The list to leave, missing from links sent before there were several lists.
+    list_id: Option<Uuid>,
+    token: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("{0}")]
+    TokenValidationError(#[from] ParseSignedTokenError),
+
+    #[error("Token does not match subscriber")]
+    TokenMismatch,
+
+    #[error("Subscriber not found")]
+    SubscriberNotFound,
+
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        telemetry::error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for UnsubscribeError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            Self::TokenValidationError(_) | Self::TokenMismatch | Self::SubscriberNotFound => {
+                // User error, ignore logging
+                (
+                    StatusCode::UNAUTHORIZED,
+                    "Unsubscribe token validation error".to_string(),
+                )
+                    .into_response()
+            }
+            Self::UnexpectedError(e) => InternalServerError(e).into_response(),
+        }
+    }
+}
+
+/// Builds the link that lets a subscriber leave a list without logging in. The link should be
+/// `<BASE_URL>/subscriptions/unsubscribe?subscriber_id=<ID>&list_id=<LIST_ID>&token=<TOKEN>`
+pub fn unsubscribe_link(
+    app_base_url: &Url,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    hmac_secret: &SecretString,
+) -> Url {
+    let token = UnsubscribeToken::generate(subscriber_id, Some(list_id), hmac_secret);
+    let mut link = app_base_url.join("subscriptions/unsubscribe").unwrap(); // safely unwrap since it's proper url
+    link.set_query(Some(&format!(
+        "subscriber_id={}&list_id={}&token={}",
+        subscriber_id,
+        list_id,
+        token.as_str()
+    )));
+    link
+}
+
+impl UnsubscribeParameters {
+    fn verify(&self, hmac_secret: &SecretString) -> Result<(), UnsubscribeError> {
+        let token = UnsubscribeToken::parse(&self.token)?;
+        if !token.verify(self.subscriber_id, self.list_id, hmac_secret) {
+            return Err(UnsubscribeError::TokenMismatch);
+        }
+
+        Ok(())
+    }
+}
+
+/// Asks the subscriber to confirm, since mail scanners commonly follow links in emails.
+#[tracing::instrument(name = "Show unsubscribe form", skip(db_pool, hmac_secret, params))]
+pub async fn unsubscribe_form(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<UnsubscribeParameters>,
+) -> Result<Html<String>, UnsubscribeError> {
+    params.verify(&hmac_secret)?;
+    let list = get_list(&db_pool, params.list_id).await?;
+
+    Ok(Html(template::unsubscribe_html(
+        params.subscriber_id,
+        list.as_ref(),
+        &params.token,
+        false,
+    )))
+}
+
+/// Handles both our own form and RFC 8058 one-click requests from mail clients,
+/// which POST `List-Unsubscribe=One-Click` to the link in the `List-Unsubscribe` header.
+#[tracing::instrument(name = "Unsubscribe a subscriber", skip(db_pool, hmac_secret, params))]
+pub async fn unsubscribe(
+    State(AppState {
+        db_pool,
+        hmac_secret,
+        ..
+    }): State<AppState>,
+    Query(params): Query<UnsubscribeParameters>,
+) -> Result<Html<String>, UnsubscribeError> {
+    params.verify(&hmac_secret)?;
+    let list = get_list(&db_pool, params.list_id).await?;
+
+    let found = match &list {
+        Some(list) => {
+            mark_subscriber_as_unsubscribed_from_list(&db_pool, list.list_id, params.subscriber_id)
+                .await
+                .context("Failed to unsubscribe subscriber from the list in the database")?
+        }
+        None => mark_subscriber_as_unsubscribed(&db_pool, params.subscriber_id)
+            .await
+            .context("Failed to unsubscribe subscriber in the database")?,
+    };
+    if !found {
+        return Err(UnsubscribeError::SubscriberNotFound);
+    }
+
+    Ok(Html(template::unsubscribe_html(
+        params.subscriber_id,
+        list.as_ref(),
+        &params.token,
+        true,
+    )))
+}
+
+/// Looks up the list the link was signed for, if any.
+async fn get_list(
+    pool: &PgPool,
+    list_id: Option<Uuid>,
+) -> Result<Option<list_db::MailingList>, UnsubscribeError> {
+    let Some(list_id) = list_id else {
+        return Ok(None);
+    };
+    list_db::get_list(pool, list_id)
+        .await
+        .context("Failed to retrieve list")?
+        .ok_or(UnsubscribeError::SubscriberNotFound)
+        .map(Some)
+}
+
+/// Unsubscribes the subscriber from a single list. Returns `false` if the subscriber is not on it.
+#[tracing::instrument(name = "Mark subscriber as unsubscribed from list", skip(pool))]
+async fn mark_subscriber_as_unsubscribed_from_list(
+    pool: &PgPool,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let found = list_db::unsubscribe_from_list(&mut transaction, list_id, subscriber_id).await?;
+    transaction.commit().await?;
+
+    Ok(found)
+}
+
+/// Unsubscribes the subscriber from every list. Returns `false` if the subscriber does not exist.
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip(pool))]
+pub(crate) async fn mark_subscriber_as_unsubscribed(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let updated = sqlx::query!(
+        r#"UPDATE subscriptions SET status = $1 WHERE id = $2"#,
+        SubscriptionStatus::Unsubscribed.to_string(),
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?
+    .rows_affected();
+    sqlx::query!(
+        r#"UPDATE list_subscriptions SET status = $1 WHERE subscriber_id = $2"#,
+        SubscriptionStatus::Unsubscribed.to_string(),
+        subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(updated > 0)
+}
@@ -6,10 +6,14 @@ use axum::{
 use tower_sessions::{session, Session};
 use uuid::Uuid;
 
+use crate::domain::CsrfToken;
+
 pub struct TypedSession(Session);
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const PENDING_TWO_FACTOR_USER_ID_KEY: &'static str = "pending_two_factor_user_id";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
 
     pub async fn renew(&self) -> Result<(), session::Error> {
         self.0.cycle_id().await
@@ -26,6 +30,44 @@ impl TypedSession {
     pub async fn get_user_id(&self) -> Result<Option<Uuid>, session::Error> {
         self.0.get(Self::USER_ID_KEY).await
     }
+
+    /// Remembers a user whose password was verified but who still has to enter their second factor.
+    /// Such a user is not logged in yet.
+    pub async fn insert_pending_two_factor_user_id(
+        &self,
+        user_id: Uuid,
+    ) -> Result<(), session::Error> {
+        self.0
+            .insert(Self::PENDING_TWO_FACTOR_USER_ID_KEY, user_id)
+            .await
+    }
+
+    pub async fn get_pending_two_factor_user_id(&self) -> Result<Option<Uuid>, session::Error> {
+        self.0.get(Self::PENDING_TWO_FACTOR_USER_ID_KEY).await
+    }
+
+    pub async fn remove_pending_two_factor_user_id(&self) -> Result<Option<Uuid>, session::Error> {
+        self.0.remove(Self::PENDING_TWO_FACTOR_USER_ID_KEY).await
+    }
+
+    pub async fn get_csrf_token(&self) -> Result<Option<CsrfToken>, session::Error> {
+        Ok(self
+            .0
+            .get(Self::CSRF_TOKEN_KEY)
+            .await?
+            .map(CsrfToken::from_session))
+    }
+
+    /// Returns the session's CSRF token, generating one for sessions that do not have it yet.
+    pub async fn get_or_insert_csrf_token(&self) -> Result<CsrfToken, session::Error> {
+        if let Some(token) = self.get_csrf_token().await? {
+            return Ok(token);
+        }
+
+        let token = CsrfToken::generate();
+        self.0.insert(Self::CSRF_TOKEN_KEY, token.as_str()).await?;
+        Ok(token)
+    }
 }
 
 #[async_trait]
@@ -1,9 +1,14 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use super::routes;
-use axum::{http::Request, middleware, routing, Router};
-use secrecy::ExposeSecret;
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{HeaderName, Request},
+    middleware, routing, Router,
+};
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
 use tower_http::{
     trace::{DefaultOnResponse, TraceLayer},
     LatencyUnit,
@@ -16,10 +21,15 @@ use tower_sessions_redis_store::{
 use tracing::Level;
 
 use crate::{
-    authentication::reject_anonymous_users,
-    configuration::{get_environment, Environment, Settings},
+    authentication::{
+        reject_anonymous_users, reject_deactivated_users, reject_invalid_api_keys,
+        reject_invalid_csrf_tokens, require_editor, require_owner, MAX_IMPORT_SIZE,
+        MAX_LOGIN_FORM_SIZE,
+    },
+    configuration::Settings,
     domain::Url,
-    email_client::EmailClient,
+    email_client::{build_email_transport, EmailTransport},
+    rate_limit::{LoginRateLimiter, SubscribeRateLimiter},
 };
 
 pub struct Application {
@@ -34,22 +44,195 @@ impl Application {
         session_layer: SessionManagerLayer<RedisStore<RedisPool>>,
     ) -> Self {
         // Normal user routes
-        let mut app_router = Router::new()
+        let app_router = Router::new()
             .route("/health", routing::get(routes::health_check))
             // Index
             .route("/", routing::get(routes::index))
+            // Subscription
+            .route("/subscribe", routing::post(routes::subscribe_with_flash))
+            .route("/subscribe/confirm", routing::get(routes::confirm))
+            .route(
+                "/subscribe/resend",
+                routing::get(routes::resend_confirmation_form),
+            )
+            .route(
+                "/subscribe/resend",
+                routing::post(routes::resend_confirmation_with_flash),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                routing::get(routes::unsubscribe_form),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                routing::post(routes::unsubscribe),
+            )
+            // Preference center
+            .route(
+                "/subscriptions/preferences",
+                routing::get(routes::preferences_page),
+            )
+            .route(
+                "/subscriptions/preferences/name",
+                routing::post(routes::update_name_with_flash),
+            )
+            .route(
+                "/subscriptions/preferences/email",
+                routing::get(routes::confirm_email_change),
+            )
+            .route(
+                "/subscriptions/preferences/email",
+                routing::post(routes::request_email_change_with_flash),
+            )
+            .route(
+                "/subscriptions/preferences/lists",
+                routing::post(routes::update_list_subscription_with_flash),
+            )
+            .route(
+                "/subscriptions/preferences/data",
+                routing::get(routes::download_subscriber_data),
+            )
+            .route(
+                "/subscriptions/preferences/delete",
+                routing::post(routes::erase_subscriber_with_flash),
+            );
+        // Login routes, whose forms are submitted before there is a logged-in user
+        let login_router = Router::new()
             // Login
             .route("/login", routing::get(routes::login_form))
             .route("/login", routing::post(routes::login_with_flash))
-            // Subscription
-            .route("/subscribe", routing::post(routes::subscribe_with_flash))
-            .route("/subscribe/confirm", routing::get(routes::confirm));
-        if let Environment::Local = get_environment() {
-            // Fake email server for local env
-            app_router = app_router.route("/email", routing::post(routes::fake_email))
-        };
-
-        // Admin routes
+            .route("/login/two_factor", routing::get(routes::two_factor_form))
+            .route(
+                "/login/two_factor",
+                routing::post(routes::two_factor_with_flash),
+            )
+            // Password reset
+            .route("/password_reset", routing::get(routes::password_reset_form))
+            .route(
+                "/password_reset",
+                routing::post(routes::request_password_reset_with_flash),
+            )
+            .route(
+                "/password_reset/confirm",
+                routing::get(routes::password_reset_confirm_form),
+            )
+            .route(
+                "/password_reset/confirm",
+                routing::post(routes::reset_password_with_flash),
+            )
+            // Middleware to reject forms not submitted from our own pages
+            .layer(middleware::from_fn(reject_invalid_csrf_tokens))
+            .layer(DefaultBodyLimit::max(MAX_LOGIN_FORM_SIZE));
+        // Subscriber imports, the only forms allowed to upload large files
+        let import_router = Router::new()
+            .route(
+                "/admin/subscribers/import",
+                routing::get(routes::import_subscribers_form).post(routes::import_subscribers),
+            )
+            .layer(middleware::from_fn(reject_invalid_csrf_tokens))
+            .layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE))
+            .layer(middleware::from_fn(require_editor));
+        // Admin routes that only editors and owners may use
+        let editor_router = Router::new()
+            .route(
+                "/admin/newsletters",
+                routing::post(routes::publish_newsletter_with_flash),
+            )
+            .route(
+                "/admin/newsletters/recipients",
+                routing::get(routes::count_newsletter_recipients),
+            )
+            .route(
+                "/admin/newsletters/drafts",
+                routing::post(routes::create_newsletter_draft_with_flash),
+            )
+            .route(
+                "/admin/newsletters/drafts/:newsletter_issue_id",
+                routing::post(routes::update_newsletter_draft_with_flash),
+            )
+            .route(
+                "/admin/newsletters/drafts/:newsletter_issue_id/test",
+                routing::post(routes::send_test_newsletter_draft_with_flash),
+            )
+            .route(
+                "/admin/newsletters/drafts/:newsletter_issue_id/publish",
+                routing::post(routes::publish_newsletter_draft_with_flash),
+            )
+            .route(
+                "/admin/newsletters/:newsletter_issue_id/reschedule",
+                routing::post(routes::reschedule_newsletter_issue_with_flash),
+            )
+            .route(
+                "/admin/newsletters/:newsletter_issue_id/cancel",
+                routing::post(routes::cancel_newsletter_issue_with_flash),
+            )
+            .route(
+                "/admin/newsletters/failures/requeue",
+                routing::post(routes::requeue_failed_delivery_with_flash),
+            )
+            .route(
+                "/admin/newsletters/failures/discard",
+                routing::post(routes::discard_failed_delivery_with_flash),
+            )
+            // Subscribers
+            .route(
+                "/admin/subscribers/:subscriber_id/confirm",
+                routing::post(routes::confirm_subscriber_with_flash),
+            )
+            .route(
+                "/admin/subscribers/:subscriber_id/resend",
+                routing::post(routes::resend_subscriber_confirmation_with_flash),
+            )
+            .route(
+                "/admin/subscribers/:subscriber_id/unsubscribe",
+                routing::post(routes::unsubscribe_subscriber_with_flash),
+            )
+            .route(
+                "/admin/subscribers/:subscriber_id/tags",
+                routing::post(routes::update_subscriber_tags_with_flash),
+            )
+            .route(
+                "/admin/subscribers/:subscriber_id/delete",
+                routing::post(routes::delete_subscriber_with_flash),
+            )
+            .route(
+                "/admin/subscribers/export",
+                routing::get(routes::export_subscribers),
+            )
+            // Lists
+            .route(
+                "/admin/lists",
+                routing::post(routes::create_list_with_flash),
+            )
+            .layer(middleware::from_fn(require_editor));
+        // Admin routes that only owners may use
+        let owner_router = Router::new()
+            // API keys
+            .route("/admin/api_keys", routing::get(routes::api_keys_page))
+            .route(
+                "/admin/api_keys",
+                routing::post(routes::create_api_key_with_flash),
+            )
+            .route(
+                "/admin/api_keys/:api_key_id/revoke",
+                routing::post(routes::revoke_api_key_with_flash),
+            )
+            // Users
+            .route("/admin/users", routing::get(routes::users_page))
+            .route(
+                "/admin/users",
+                routing::post(routes::invite_user_with_flash),
+            )
+            .route(
+                "/admin/users/:user_id/deactivate",
+                routing::post(routes::deactivate_user_with_flash),
+            )
+            .route(
+                "/admin/users/:user_id/delete",
+                routing::post(routes::delete_user_with_flash),
+            )
+            .layer(middleware::from_fn(require_owner));
+        // Admin routes that every user may use
         let admin_router = Router::new()
             // Dashboard
             .route("/admin/dashboard", routing::get(routes::admin_dashboard))
@@ -64,21 +247,93 @@ impl Application {
                 "/admin/password",
                 routing::post(routes::change_password_with_flash),
             )
+            // Two-factor authentication
+            .route("/admin/two_factor", routing::get(routes::two_factor_page))
+            .route(
+                "/admin/two_factor",
+                routing::post(routes::enable_two_factor_with_flash),
+            )
+            .route(
+                "/admin/two_factor/disable",
+                routing::post(routes::disable_two_factor_with_flash),
+            )
             // Newsletters
             .route(
                 "/admin/newsletters",
                 routing::get(routes::publish_newsletter_form),
             )
             .route(
-                "/admin/newsletters",
-                routing::post(routes::publish_newsletter_with_flash),
+                "/admin/newsletters/drafts",
+                routing::get(routes::newsletter_drafts_page),
             )
+            .route(
+                "/admin/newsletters/drafts/:newsletter_issue_id",
+                routing::get(routes::newsletter_draft_page),
+            )
+            .route(
+                "/admin/newsletters/drafts/:newsletter_issue_id/preview",
+                routing::get(routes::preview_newsletter_draft),
+            )
+            .route(
+                "/admin/newsletters/:newsletter_issue_id",
+                routing::get(routes::newsletter_issue_page),
+            )
+            // Failed deliveries
+            .route(
+                "/admin/newsletters/failures",
+                routing::get(routes::delivery_failures_page),
+            )
+            // Subscribers
+            .route("/admin/subscribers", routing::get(routes::subscribers_page))
+            // Lists
+            .route("/admin/lists", routing::get(routes::lists_page))
+            .merge(editor_router)
+            .merge(owner_router)
+            // Middleware to reject forms not submitted from our own pages
+            .layer(middleware::from_fn(reject_invalid_csrf_tokens))
+            // Checks its own CSRF tokens, to buffer larger bodies
+            .merge(import_router)
+            // Middleware to reject users deactivated since logging in, and to look up roles
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                reject_deactivated_users,
+            ))
             // Middleware to reject non-logged-in users
             .layer(middleware::from_fn(reject_anonymous_users));
 
+        // JSON API routes for machine clients
+        let api_router = Router::new()
+            .route(
+                "/api/v1/subscribers",
+                routing::get(routes::api_list_subscribers),
+            )
+            .route(
+                "/api/v1/subscribers",
+                routing::post(routes::api_create_subscriber),
+            )
+            .route(
+                "/api/v1/subscribers/:subscriber_id",
+                routing::get(routes::api_get_subscriber),
+            )
+            .route(
+                "/api/v1/newsletters",
+                routing::get(routes::api_list_newsletters),
+            )
+            .route(
+                "/api/v1/newsletters",
+                routing::post(routes::api_publish_newsletter),
+            )
+            // Middleware to reject requests without a valid API key
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                reject_invalid_api_keys,
+            ));
+
         // Build our application
         let router = app_router
+            .merge(login_router)
             .merge(admin_router)
+            .merge(api_router)
             .with_state(app_state)
             .layer(
                 TraceLayer::new_for_http()
@@ -116,10 +371,17 @@ impl Application {
         Self::new(address, app_state, session_layer)
     }
 
-    pub async fn serve(self) -> Result<(), std::io::Error> {
+    /// Serves requests until `shutdown` is cancelled, then waits for in-flight requests to complete.
+    pub async fn serve(self, shutdown: CancellationToken) -> Result<(), std::io::Error> {
         let listener = tokio::net::TcpListener::bind(self.address).await?;
         tracing::info!("Starting service on {}...", listener.local_addr().unwrap());
-        axum::serve(listener, self.router).await
+        axum::serve(
+            listener,
+            self.router
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await
     }
 
     pub fn router(self) -> Router {
@@ -130,9 +392,14 @@ impl Application {
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: Arc<sqlx::PgPool>,
-    pub email_client: Arc<EmailClient>,
+    pub email_client: Arc<dyn EmailTransport>,
     pub app_base_url: Url,
+    pub hmac_secret: SecretString,
+    pub confirmation_token_ttl: chrono::Duration,
     pub flash_config: axum_flash::Config,
+    pub client_ip_header: Option<HeaderName>,
+    pub login_rate_limiter: LoginRateLimiter,
+    pub subscribe_rate_limiter: SubscribeRateLimiter,
 }
 
 impl axum::extract::FromRef<AppState> for axum_flash::Config {
@@ -150,10 +417,7 @@ pub async fn default_app_state_and_session(
         None => PgPool::connect_lazy_with(settings.database.with_db()),
     };
 
-    let email_client: EmailClient = settings
-        .email_client
-        .clone()
-        .try_into()
+    let email_client = build_email_transport(settings.email_client.clone())
         .expect("Failed to initialize email client.");
 
     let app_base_url = settings
@@ -173,9 +437,14 @@ pub async fn default_app_state_and_session(
         .await
         .expect("Unable to connect to pool.");
 
+    let login_rate_limiter =
+        LoginRateLimiter::new(redis_pool.clone(), settings.login_rate_limit.clone());
+    let subscribe_rate_limiter =
+        SubscribeRateLimiter::new(redis_pool.clone(), settings.subscribe_rate_limit.clone());
     let session_store = RedisStore::new(redis_pool);
     let session_layer = SessionManagerLayer::new(session_store)
-        .with_secure(false)
+        // Only send the session cookie over HTTPS, unless the app itself is served over HTTP
+        .with_secure(app_base_url.scheme() == "https")
         .with_expiry(tower_sessions::Expiry::OnInactivity(
             time::Duration::minutes(10),
         ));
@@ -183,9 +452,16 @@ pub async fn default_app_state_and_session(
     (
         AppState {
             db_pool: Arc::new(db_pool),
-            email_client: Arc::new(email_client),
+            email_client,
             app_base_url,
+            hmac_secret: settings.application.hmac_secret.clone(),
+            confirmation_token_ttl: settings.subscriptions.confirmation_token_ttl(),
             flash_config: axum_flash::Config::new(axum_flash::Key::generate()),
+            client_ip_header: settings.application.client_ip_header.as_ref().map(|h| {
+                HeaderName::try_from(h.as_str()).expect("Failed to parse client IP header.")
+            }),
+            login_rate_limiter,
+            subscribe_rate_limiter,
         },
         session_layer,
     )
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+
+use crate::{configuration::Settings, domain::SubscriptionStatus};
+
+/// Periodically deletes subscribers who never confirmed their subscription until `shutdown`
+/// is cancelled.
+pub async fn run_purger_until_stopped(
+    settings: Settings,
+    overwrite_db_pool: Option<sqlx::PgPool>,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    let db_pool = match overwrite_db_pool {
+        Some(p) => p,
+        None => PgPool::connect_lazy_with(settings.database.with_db()),
+    };
+
+    purger_loop(
+        db_pool,
+        settings.subscriptions.purge_unconfirmed_after(),
+        shutdown,
+    )
+    .await;
+    Ok(())
+}
+
+async fn purger_loop(pool: PgPool, purge_after: chrono::Duration, shutdown: CancellationToken) {
+    while !shutdown.is_cancelled() {
+        let idle_time = match purge_unconfirmed_subscribers(&pool, purge_after).await {
+            Ok(_) => Duration::from_secs(60 * 60),
+            Err(_) => Duration::from_secs(60),
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(idle_time) => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+}
+
+/// Deletes pending subscribers who subscribed more than `purge_after` ago and were not sent a
+/// confirmation link since, along with their subscription tokens.
+/// Returns the number of subscribers deleted.
+#[tracing::instrument(skip(pool), err)]
+pub async fn purge_unconfirmed_subscribers(
+    pool: &PgPool,
+    purge_after: chrono::Duration,
+) -> Result<u64, anyhow::Error> {
+    let subscribed_before = Utc::now() - purge_after;
+    // A single statement, so that a token sent meanwhile fails the purge instead of being orphaned
+    let purged = sqlx::query_scalar!(
+        r#"
+        WITH purged AS (
+            DELETE FROM subscriptions s
+            WHERE
+                s.status = $1 AND
+                s.subscribed_at < $2 AND
+                NOT EXISTS (
+                    SELECT 1 FROM subscription_tokens t
+                    WHERE t.subscriber_id = s.id AND t.created_at >= $2
+                )
+            RETURNING s.id
+        ),
+        purged_tokens AS (
+            DELETE FROM subscription_tokens
+            WHERE subscriber_id IN (SELECT id FROM purged)
+        )
+        SELECT COUNT(*) AS "count!" FROM purged
+        "#,
+        SubscriptionStatus::PendingConfirmation.to_string(),
+        subscribed_before
+    )
+    .fetch_one(pool)
+    .await? as u64;
+
+    if purged > 0 {
+        tracing::info!("Purged {} unconfirmed subscribers", purged);
+    }
+    Ok(purged)
+}
@@ -69,3 +69,16 @@ pub fn error_chain_fmt(
     }
     Ok(())
 }
+
+/// Renders an error and its chain of causes into a string, as formatted by `error_chain_fmt`.
+pub fn error_chain_string(e: &impl std::error::Error) -> String {
+    struct ErrorChain<'a, E>(&'a E);
+
+    impl<E: std::error::Error> std::fmt::Display for ErrorChain<'_, E> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            error_chain_fmt(self.0, f)
+        }
+    }
+
+    ErrorChain(e).to_string()
+}
@@ -2,7 +2,18 @@ use lazy_static::lazy_static;
 use tera::{Context, Tera};
 use uuid::Uuid;
 
-use crate::domain::{Name, Url};
+use crate::{
+    database::{
+        list_db::{ListPreference, ListSummary, MailingList},
+        user_db::ActiveUser,
+    },
+    domain::{ApiKey, ApiKeyScope, CsrfToken, Name, SubscriptionStatus, Url, UserRole},
+    routes::{
+        ApiKeySummary, DeliveryProgress, FailedDelivery, ImportReport, NewsletterDraft,
+        NewsletterIssueSummary, SubscriberFilter, SubscriberPreferences, SubscriberSummary,
+        TwoFactorEnrollment, UserSummary,
+    },
+};
 
 lazy_static! {
     static ref TEMPLATES: Tera = {
@@ -19,11 +30,15 @@ lazy_static! {
 /// Renders index page with either success message or error message or none if both are `None`.
 /// Success message takes precedence.
 pub fn index_html(
+    csrf_token: &CsrfToken,
     user_id: Option<Uuid>,
+    lists: &[MailingList],
     success_msg: Option<String>,
     error_msg: Option<String>,
 ) -> String {
     let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("lists", lists);
     if let Some(user_id) = user_id {
         context.insert("user_id", &user_id.to_string());
     }
@@ -38,9 +53,10 @@ pub fn index_html(
 }
 
 /// Renders confirmation email with name and confirmation link.
-pub fn confirmation_email_html(name: &Name, link: &Url) -> String {
+pub fn confirmation_email_html(name: &Name, list_name: &str, link: &Url) -> String {
     let mut context = Context::new();
     context.insert("name", name.as_ref());
+    context.insert("list_name", list_name);
     context.insert("confirmation_link", link.as_str());
 
     TEMPLATES
@@ -48,9 +64,67 @@ pub fn confirmation_email_html(name: &Name, link: &Url) -> String {
         .unwrap()
 }
 
+/// Renders unsubscribe page, either asking for confirmation or confirming that it is done.
+pub fn unsubscribe_html(
+    subscriber_id: Uuid,
+    list: Option<&MailingList>,
+    token: &str,
+    unsubscribed: bool,
+) -> String {
+    let mut context = Context::new();
+    context.insert("subscriber_id", &subscriber_id.to_string());
+    context.insert("list", &list);
+    context.insert("token", token);
+    context.insert("unsubscribed", &unsubscribed);
+
+    TEMPLATES.render("unsubscribe.html", &context).unwrap()
+}
+
+/// Renders the preference center of a subscriber, carrying the signed token along in its forms.
+pub fn preferences_html(
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    subscriber_id: Uuid,
+    token: &str,
+    preferences: &SubscriberPreferences,
+    lists: &[ListPreference],
+) -> String {
+    let mut context = Context::new();
+    context.insert("subscriber_id", &subscriber_id.to_string());
+    context.insert("token", token);
+    context.insert("preferences", preferences);
+    context.insert("lists", lists);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("preferences.html", &context).unwrap()
+}
+
+/// Renders the page to request another confirmation email, with optional success or error message.
+pub fn resend_confirmation_html(success_msg: Option<String>, error_msg: Option<String>) -> String {
+    let mut context = Context::new();
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("resend_confirmation.html", &context)
+        .unwrap()
+}
+
 /// Renders login page with optional error message.
-pub fn login_html(success_msg: Option<String>, error_msg: Option<String>) -> String {
+pub fn login_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+) -> String {
     let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
     if let Some(msg) = success_msg {
         context.insert("success_msg", &msg);
     } else if let Some(msg) = error_msg {
@@ -60,20 +134,89 @@ pub fn login_html(success_msg: Option<String>, error_msg: Option<String>) -> Str
     TEMPLATES.render("login.html", &context).unwrap()
 }
 
-/// Renders admin dashboard with username.
-pub fn admin_dashboard_html(username: &Name) -> String {
+/// Renders the second login step, asking for an authentication or recovery code.
+pub fn login_two_factor_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("login_two_factor.html", &context).unwrap()
+}
+
+/// Renders forgot password page with optional success or error message.
+pub fn password_reset_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("password_reset.html", &context).unwrap()
+}
+
+/// Renders the form to set a new password, carrying the reset token along.
+pub fn password_reset_confirm_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    token: &str,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("token", token);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("password_reset_confirm.html", &context)
+        .unwrap()
+}
+
+/// Renders admin dashboard with username, only linking to the pages the user's role can use.
+/// Editors and owners are reminded to enable two-factor authentication, which their pages require.
+pub fn admin_dashboard_html(
+    csrf_token: &CsrfToken,
+    username: &Name,
+    active_user: ActiveUser,
+) -> String {
     let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
     context.insert("username", username.as_ref());
+    context.insert("is_editor", &active_user.role.includes(UserRole::Editor));
+    context.insert("is_owner", &active_user.role.includes(UserRole::Owner));
+    context.insert(
+        "two_factor_required",
+        &(active_user.role.includes(UserRole::Editor) && !active_user.two_factor_enabled),
+    );
 
     TEMPLATES.render("admin/dashboard.html", &context).unwrap()
 }
 
 /// Renders admin change password form with optional error message.
 pub fn admin_change_password_html(
+    csrf_token: &CsrfToken,
     success_msg: Option<String>,
     error_msg: Option<String>,
 ) -> String {
     let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
     if let Some(msg) = success_msg {
         context.insert("success_msg", &msg);
     } else if let Some(msg) = error_msg {
@@ -87,12 +230,18 @@ pub fn admin_change_password_html(
 
 /// Renders admin publish newsletter form with optional error message.
 pub fn admin_newsletter_html(
+    csrf_token: &CsrfToken,
     success_msg: Option<String>,
     error_msg: Option<String>,
     idempotency_key: String,
+    lists: &[MailingList],
+    recent_issues: &[NewsletterIssueSummary],
 ) -> String {
     let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
     context.insert("idempotency_key", &idempotency_key);
+    context.insert("lists", lists);
+    context.insert("recent_issues", recent_issues);
     if let Some(msg) = success_msg {
         context.insert("success_msg", &msg);
     } else if let Some(msg) = error_msg {
@@ -102,40 +251,619 @@ pub fn admin_newsletter_html(
     TEMPLATES.render("admin/newsletter.html", &context).unwrap()
 }
 
+/// Renders admin newsletter issue page with its delivery progress and optional success or error message.
+pub fn admin_newsletter_issue_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    issue: &NewsletterIssueSummary,
+    progress: &DeliveryProgress,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("issue", issue);
+    context.insert("progress", progress);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("admin/newsletter_issue.html", &context)
+        .unwrap()
+}
+
+/// Renders admin newsletter drafts page with optional success or error message.
+pub fn admin_newsletter_drafts_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    drafts: &[NewsletterDraft],
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("drafts", drafts);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("admin/newsletter_drafts.html", &context)
+        .unwrap()
+}
+
+/// Renders admin edit newsletter draft form with optional success or error message.
+pub fn admin_newsletter_draft_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    draft: &NewsletterDraft,
+    lists: &[MailingList],
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("draft", draft);
+    context.insert("lists", lists);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("admin/newsletter_draft.html", &context)
+        .unwrap()
+}
+
+/// Renders admin newsletter draft preview with both HTML and text content.
+pub fn admin_newsletter_draft_preview_html(
+    csrf_token: &CsrfToken,
+    draft: &NewsletterDraft,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("draft", draft);
+
+    TEMPLATES
+        .render("admin/newsletter_draft_preview.html", &context)
+        .unwrap()
+}
+
+/// Renders admin failed deliveries page with optional success or error message.
+pub fn admin_delivery_failures_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    failures: &[FailedDelivery],
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("failures", failures);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("admin/delivery_failures.html", &context)
+        .unwrap()
+}
+
+/// Renders admin API keys page with optional success or error message.
+/// A newly minted key is shown in full, since it cannot be displayed again later.
+pub fn admin_api_keys_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    api_keys: &[ApiKeySummary],
+    new_api_key: Option<&ApiKey>,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("api_keys", api_keys);
+    let all_scopes: Vec<_> = ApiKeyScope::ALL.iter().map(ToString::to_string).collect();
+    context.insert("all_scopes", &all_scopes);
+    if let Some(api_key) = new_api_key {
+        context.insert("new_api_key", api_key.expose_secret());
+    }
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("admin/api_keys.html", &context).unwrap()
+}
+
+/// Renders admin lists page with how many subscribers each list has, and optional success or
+/// error message.
+pub fn admin_lists_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    lists: &[ListSummary],
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("lists", lists);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("admin/lists.html", &context).unwrap()
+}
+
+/// Renders admin two-factor authentication page, either to enroll an authenticator app
+/// or to manage it once enabled. Newly generated recovery codes are shown in full,
+/// since they cannot be displayed again later.
+pub fn admin_two_factor_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    enrollment: Option<&TwoFactorEnrollment>,
+    unused_recovery_codes: i64,
+    new_recovery_codes: &[String],
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    if let Some(enrollment) = enrollment {
+        context.insert("enrollment", enrollment);
+    }
+    context.insert("unused_recovery_codes", &unused_recovery_codes);
+    context.insert("new_recovery_codes", new_recovery_codes);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("admin/two_factor.html", &context).unwrap()
+}
+
+/// Renders admin users page with optional success or error message.
+/// The logged-in user cannot deactivate or delete themselves, so no actions are shown for them.
+pub fn admin_users_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    users: &[UserSummary],
+    current_user_id: Uuid,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("users", users);
+    context.insert("current_user_id", &current_user_id);
+    let all_roles: Vec<_> = UserRole::ALL.iter().map(ToString::to_string).collect();
+    context.insert("all_roles", &all_roles);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES.render("admin/users.html", &context).unwrap()
+}
+
+/// Renders admin subscribers page with optional success or error message.
+/// `next_after` is the id to continue from on the next page, if there is one.
+pub fn admin_subscribers_html(
+    csrf_token: &CsrfToken,
+    success_msg: Option<String>,
+    error_msg: Option<String>,
+    subscribers: &[SubscriberSummary],
+    filter: &SubscriberFilter,
+    next_after: Option<Uuid>,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("subscribers", subscribers);
+    context.insert("filter", filter);
+    // Page links keep the search and filters
+    if filter.after.is_some() {
+        let first_page = SubscriberFilter {
+            after: None,
+            ..filter.clone()
+        };
+        context.insert(
+            "first_page_query",
+            &serde_urlencoded::to_string(&first_page).unwrap(),
+        );
+    }
+    if let Some(next_after) = next_after {
+        let next_page = SubscriberFilter {
+            after: Some(next_after),
+            ..filter.clone()
+        };
+        context.insert(
+            "next_page_query",
+            &serde_urlencoded::to_string(&next_page).unwrap(),
+        );
+    }
+    let all_statuses: Vec<_> = SubscriptionStatus::ALL
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    context.insert("all_statuses", &all_statuses);
+    if let Some(msg) = success_msg {
+        context.insert("success_msg", &msg);
+    } else if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("admin/subscribers.html", &context)
+        .unwrap()
+}
+
+pub fn admin_subscribers_import_html(
+    csrf_token: &CsrfToken,
+    lists: &[MailingList],
+    report: Option<&ImportReport>,
+    error_msg: Option<String>,
+) -> String {
+    let mut context = Context::new();
+    context.insert("csrf_token", csrf_token.as_str());
+    context.insert("lists", lists);
+    if let Some(report) = report {
+        context.insert("report", report);
+    }
+    if let Some(msg) = error_msg {
+        context.insert("error_msg", &msg);
+    }
+
+    TEMPLATES
+        .render("admin/subscribers_import.html", &context)
+        .unwrap()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn index_template_works() {
-        index_html(Some(Uuid::new_v4()), None, Some("something".into()));
+        index_html(
+            &CsrfToken::generate(),
+            Some(Uuid::new_v4()),
+            &[],
+            None,
+            Some("something".into()),
+        );
     }
 
     #[test]
     fn confirmation_email_template_works() {
         let name = Name::parse("Mamamia").unwrap();
         let link = Url::parse("https://hecomundo-bleach.com").unwrap();
-        confirmation_email_html(&name, &link);
+        confirmation_email_html(&name, "Newsletter", &link);
+    }
+
+    #[test]
+    fn unsubscribe_template_works() {
+        let list = MailingList {
+            list_id: Uuid::new_v4(),
+            slug: "release-notes".into(),
+            name: "Release Notes".into(),
+        };
+        unsubscribe_html(Uuid::new_v4(), None, "token", false);
+        unsubscribe_html(Uuid::new_v4(), None, "token", true);
+        let html = unsubscribe_html(Uuid::new_v4(), Some(&list), "token", false);
+        assert!(html.contains(&format!("list_id={}", list.list_id)));
+        unsubscribe_html(Uuid::new_v4(), Some(&list), "token", true);
+    }
+
+    #[test]
+    fn preferences_template_shows_pending_email() {
+        let preferences = SubscriberPreferences {
+            name: "Mamamia".into(),
+            email: "mamamia@example.com".into(),
+            status: "confirmed".into(),
+            pending_email: Some("new@example.com".into()),
+        };
+        let html = preferences_html(None, None, Uuid::new_v4(), "token", &preferences, &[]);
+        assert!(html.contains("mamamia@example.com"));
+        assert!(html.contains("new@example.com"));
+    }
+
+    #[test]
+    fn preferences_template_shows_list_toggles() {
+        let preferences = SubscriberPreferences {
+            name: "Mamamia".into(),
+            email: "mamamia@example.com".into(),
+            status: "confirmed".into(),
+            pending_email: None,
+        };
+        let lists = [
+            ListPreference {
+                list_id: Uuid::new_v4(),
+                name: "Newsletter".into(),
+                status: Some("confirmed".into()),
+            },
+            ListPreference {
+                list_id: Uuid::new_v4(),
+                name: "Release Notes".into(),
+                status: None,
+            },
+        ];
+        let html = preferences_html(None, None, Uuid::new_v4(), "token", &preferences, &lists);
+        assert!(html.contains("Unsubscribe from Newsletter"));
+        assert!(html.contains("Subscribe to Release Notes"));
+        assert!(html.contains("not subscribed"));
+    }
+
+    #[test]
+    fn resend_confirmation_template_works() {
+        resend_confirmation_html(Some("something".into()), None);
     }
 
     #[test]
     fn login_template_works() {
-        login_html(None, Some("something".into()));
+        login_html(&CsrfToken::generate(), None, Some("something".into()));
+    }
+
+    #[test]
+    fn login_template_embeds_csrf_token() {
+        let csrf_token = CsrfToken::generate();
+        let html = login_html(&csrf_token, None, None);
+        assert!(html.contains(&format!(r#"value="{}""#, csrf_token.as_str())));
+    }
+
+    #[test]
+    fn login_two_factor_template_works() {
+        login_two_factor_html(&CsrfToken::generate(), None, Some("something".into()));
+    }
+
+    #[test]
+    fn admin_two_factor_template_shows_enrollment_or_recovery_codes() {
+        let enrollment = TwoFactorEnrollment {
+            otpauth_uri: "otpauth://totp/Zero2Prod:admin?secret=ABC".into(),
+            secret: "ABC".into(),
+        };
+        let html = admin_two_factor_html(
+            &CsrfToken::generate(),
+            None,
+            None,
+            Some(&enrollment),
+            0,
+            &[],
+        );
+        assert!(html.contains(r#"href="otpauth:&#x2F;&#x2F;totp&#x2F;Zero2Prod:admin?secret=ABC""#));
+
+        let codes = vec!["abcde-12345".to_string()];
+        let html = admin_two_factor_html(
+            &CsrfToken::generate(),
+            Some("good".into()),
+            None,
+            None,
+            1,
+            &codes,
+        );
+        assert!(html.contains("abcde-12345"));
+    }
+
+    #[test]
+    fn password_reset_templates_work() {
+        password_reset_html(&CsrfToken::generate(), Some("good".into()), None);
+        let html = password_reset_confirm_html(
+            &CsrfToken::generate(),
+            None,
+            Some("something".into()),
+            "abc123",
+        );
+        assert!(html.contains(r#"value="abc123""#));
     }
 
     #[test]
     fn admin_dashboard_template_works() {
         let name = Name::parse("Capoo").unwrap();
-        admin_dashboard_html(&name);
+        let active_user = ActiveUser {
+            role: UserRole::Owner,
+            two_factor_enabled: true,
+        };
+        admin_dashboard_html(&CsrfToken::generate(), &name, active_user);
+    }
+
+    #[test]
+    fn admin_dashboard_template_hides_pages_beyond_role() {
+        let name = Name::parse("Capoo").unwrap();
+        let active_user = ActiveUser {
+            role: UserRole::Viewer,
+            two_factor_enabled: false,
+        };
+        let html = admin_dashboard_html(&CsrfToken::generate(), &name, active_user);
+        assert!(html.contains("/admin/newsletters/drafts"));
+        assert!(!html.contains("/admin/users"));
+        assert!(!html.contains("/admin/api_keys"));
     }
 
     #[test]
     fn admin_change_password_template_works() {
-        admin_change_password_html(Some("good".into()), Some("something".into()));
+        admin_change_password_html(
+            &CsrfToken::generate(),
+            Some("good".into()),
+            Some("something".into()),
+        );
     }
 
     #[test]
     fn admin_newsletter_template_works() {
-        admin_newsletter_html(Some("yeah".into()), None, Uuid::new_v4().to_string());
+        let recent_issues = vec![NewsletterIssueSummary {
+            newsletter_issue_id: Uuid::new_v4(),
+            title: "Title".into(),
+            status: "published".into(),
+            scheduled_for: None,
+            published_at: Some(chrono::Utc::now()),
+        }];
+        admin_newsletter_html(
+            &CsrfToken::generate(),
+            Some("yeah".into()),
+            None,
+            Uuid::new_v4().to_string(),
+            &[],
+            &recent_issues,
+        );
+    }
+
+    #[test]
+    fn admin_newsletter_issue_template_works() {
+        let issue = NewsletterIssueSummary {
+            newsletter_issue_id: Uuid::new_v4(),
+            title: "Title".into(),
+            status: "published".into(),
+            scheduled_for: None,
+            published_at: Some(chrono::Utc::now()),
+        };
+        admin_newsletter_issue_html(
+            &CsrfToken::generate(),
+            Some("good".into()),
+            None,
+            &issue,
+            &DeliveryProgress::new(3, 1, 6),
+        );
+    }
+
+    fn sample_draft() -> NewsletterDraft {
+        NewsletterDraft {
+            newsletter_issue_id: Uuid::new_v4(),
+            title: "Title".into(),
+            text_content: "Text".into(),
+            html_content: "<p>HTML</p>".into(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn admin_newsletter_drafts_template_works() {
+        admin_newsletter_drafts_html(
+            &CsrfToken::generate(),
+            Some("good".into()),
+            None,
+            &[sample_draft()],
+        );
+    }
+
+    #[test]
+    fn admin_newsletter_draft_template_works() {
+        admin_newsletter_draft_html(
+            &CsrfToken::generate(),
+            None,
+            Some("something".into()),
+            &sample_draft(),
+            &[],
+        );
+    }
+
+    #[test]
+    fn admin_newsletter_draft_preview_escapes_html_into_iframe() {
+        let html = admin_newsletter_draft_preview_html(&CsrfToken::generate(), &sample_draft());
+        assert!(html.contains(r#"srcdoc="&lt;p&gt;HTML&lt;&#x2F;p&gt;""#));
+    }
+
+    #[test]
+    fn admin_delivery_failures_template_works() {
+        let failures = vec![FailedDelivery {
+            newsletter_issue_id: Uuid::new_v4(),
+            newsletter_title: "Title".into(),
+            subscriber_email: "ursula_le_guin@gmail.com".into(),
+            last_error: "Something failed".into(),
+            n_attempts: 3,
+            failed_at: chrono::Utc::now(),
+        }];
+        admin_delivery_failures_html(
+            &CsrfToken::generate(),
+            None,
+            Some("something".into()),
+            &failures,
+        );
+    }
+
+    #[test]
+    fn admin_api_keys_template_shows_new_key() {
+        let api_keys = vec![ApiKeySummary {
+            api_key_id: Uuid::new_v4(),
+            name: "CI".into(),
+            key_prefix: "z2p_abcdefgh".into(),
+            scopes: vec!["newsletters:write".into()],
+            created_at: chrono::Utc::now(),
+            last_used_at: None,
+            revoked_at: None,
+        }];
+        let new_api_key = ApiKey::generate();
+        let html = admin_api_keys_html(
+            &CsrfToken::generate(),
+            Some("good".into()),
+            None,
+            &api_keys,
+            Some(&new_api_key),
+        );
+        assert!(html.contains(new_api_key.expose_secret()));
+        assert!(html.contains("z2p_abcdefgh"));
+    }
+
+    #[test]
+    fn admin_users_template_hides_actions_for_current_user() {
+        let user = |username: &str| UserSummary {
+            user_id: Uuid::new_v4(),
+            username: username.into(),
+            email: None,
+            role: "editor".into(),
+            created_at: chrono::Utc::now(),
+            deactivated_at: None,
+        };
+        let users = vec![user("alice"), user("bob")];
+        let html = admin_users_html(&CsrfToken::generate(), None, None, &users, users[0].user_id);
+        assert!(!html.contains(&format!("/admin/users/{}/delete", users[0].user_id)));
+        assert!(html.contains(&format!("/admin/users/{}/delete", users[1].user_id)));
+    }
+    #[test]
+    fn admin_subscribers_import_template_lists_invalid_rows() {
+        let report = ImportReport {
+            imported: 2,
+            added_existing: 0,
+            skipped_existing: 1,
+            skipped_erased: 0,
+            invalid_rows: vec![crate::routes::ImportRowError {
+                line: 3,
+                error: "not-an-email is not a valid email".into(),
+            }],
+            confirmation_emails_sent: false,
+        };
+        let html = admin_subscribers_import_html(&CsrfToken::generate(), &[], Some(&report), None);
+        assert!(html.contains("not-an-email is not a valid email"));
+    }
+    #[test]
+    fn admin_subscribers_template_links_next_page_with_filters() {
+        let subscribers = vec![SubscriberSummary {
+            id: Uuid::new_v4(),
+            name: "Ursula".into(),
+            email: "ursula_le_guin@gmail.com".into(),
+            status: "confirmed".into(),
+            subscribed_at: chrono::Utc::now(),
+            tags: vec!["beta-tester".into(), "vip".into()],
+        }];
+        let filter = SubscriberFilter {
+            search: "le guin".into(),
+            status: "confirmed".into(),
+            after: None,
+        };
+        let html = admin_subscribers_html(
+            &CsrfToken::generate(),
+            None,
+            None,
+            &subscribers,
+            &filter,
+            Some(subscribers[0].id),
+        );
+        assert!(html.contains(&format!(
+            "?search=le+guin&amp;status=confirmed&amp;after={}",
+            subscribers[0].id
+        )));
+        assert!(html.contains(r#"value="beta-tester, vip""#));
     }
 }
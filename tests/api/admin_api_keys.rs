@@ -0,0 +1,192 @@
+use axum::http::{header, HeaderValue, StatusCode};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::helpers::{self, assert_is_redirect_to};
+use zero2prod::domain::{ApiKeyScope, UserRole};
+
+async fn get_api_key_id(test_app: &helpers::TestApp, name: &str) -> Uuid {
+    sqlx::query!("SELECT api_key_id FROM api_keys WHERE name = $1", name)
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch saved API key.")
+        .api_key_id
+}
+
+async fn get_subscribers_with_key(test_app: &helpers::TestApp, api_key: &str) -> StatusCode {
+    test_app
+        .app_server
+        .get("/api/v1/subscribers")
+        .add_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", api_key)).unwrap(),
+        )
+        .await
+        .status_code()
+}
+
+#[sqlx::test]
+async fn must_be_logged_in_to_manage_api_keys(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let page = test_app.get_admin_api_keys().await;
+    let create = test_app
+        .post_admin_api_keys(&[("name", "CI"), ("scopes", "newsletters:write")])
+        .await;
+    let revoke = test_app.post_admin_revoke_api_key(Uuid::new_v4()).await;
+
+    // Assert
+    assert_is_redirect_to(&page, "/login");
+    assert_is_redirect_to(&create, "/login");
+    assert_is_redirect_to(&revoke, "/login");
+}
+
+#[sqlx::test]
+async fn minted_api_key_is_shown_once_and_authenticates_requests(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app
+        .post_admin_api_keys(&[
+            ("name", "Release CI"),
+            ("scopes", "subscribers:read"),
+            ("scopes", "newsletters:write"),
+        ])
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    let html_page = response.text();
+    assert!(html_page.contains("API key Release CI created"));
+    let api_key = html_page
+        .split(r#"<div class="new-api-key">"#)
+        .nth(1)
+        .and_then(|s| s.split("</div>").next())
+        .expect("New API key is not shown.")
+        .to_string();
+
+    assert_eq!(
+        get_subscribers_with_key(&test_app, &api_key).await,
+        StatusCode::OK
+    );
+    let last_used = sqlx::query!("SELECT last_used_at FROM api_keys WHERE name = 'Release CI'")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch saved API key.");
+    assert!(last_used.last_used_at.is_some());
+
+    // The key is never shown again
+    let html_page = test_app.get_admin_api_keys().await.text();
+    assert!(html_page.contains("Release CI"));
+    assert!(!html_page.contains(&api_key));
+}
+
+#[sqlx::test]
+async fn api_key_requires_a_name_and_a_scope(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act & Assert 1 - Missing scope
+    let response = test_app.post_admin_api_keys(&[("name", "CI")]).await;
+    assert_is_redirect_to(&response, "/admin/api_keys");
+    let html_page = test_app.get_admin_api_keys().await.text();
+    assert!(html_page.contains("API key must be granted at least one scope"));
+
+    // Act & Assert 2 - Blank name
+    let response = test_app
+        .post_admin_api_keys(&[("name", " "), ("scopes", "newsletters:read")])
+        .await;
+    assert_is_redirect_to(&response, "/admin/api_keys");
+    let html_page = test_app.get_admin_api_keys().await.text();
+    assert!(html_page.contains("API key name must not be empty"));
+}
+
+#[sqlx::test]
+async fn revoked_api_key_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let api_key_id = get_api_key_id(&test_app, "Test key").await;
+
+    // Act
+    let response = test_app.post_admin_revoke_api_key(api_key_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/api_keys");
+    let html_page = test_app.get_admin_api_keys().await.text();
+    assert!(html_page.contains("API key successfully revoked"));
+    assert_eq!(
+        get_subscribers_with_key(&test_app, &test_app.api_key).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Revoking twice is reported as an error
+    test_app.post_admin_revoke_api_key(api_key_id).await;
+    let html_page = test_app.get_admin_api_keys().await.text();
+    assert!(html_page.contains("API key not found or already revoked"));
+}
+
+#[sqlx::test]
+async fn api_key_of_a_demoted_owner_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    assert_eq!(
+        get_subscribers_with_key(&test_app, &test_app.api_key).await,
+        StatusCode::OK
+    );
+
+    // Act
+    sqlx::query!(
+        "UPDATE users SET role = $1 WHERE user_id = $2",
+        UserRole::Viewer.to_string(),
+        test_app.test_user.user_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(
+        get_subscribers_with_key(&test_app, &test_app.api_key).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[sqlx::test]
+async fn api_key_without_required_scope_is_forbidden(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let read_only_key = test_app
+        .test_user
+        .store_api_key(&test_app.app_state.db_pool, &[ApiKeyScope::SubscribersRead])
+        .await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/api/v1/newsletters")
+        .add_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", read_only_key)).unwrap(),
+        )
+        .json(&serde_json::json!({
+            "title": "Release notes",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+        }))
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::FORBIDDEN);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["error"]["code"], "forbidden");
+    assert_eq!(
+        get_subscribers_with_key(&test_app, &read_only_key).await,
+        StatusCode::OK
+    );
+}
@@ -41,12 +41,7 @@ async fn new_password_fields_must_match(pool: PgPool) {
     let new_password = Uuid::new_v4().to_string();
     let another_new_password = Uuid::new_v4().to_string();
     // Login
-    test_app
-        .post_login(&serde_json::json!({
-            "username": &test_app.test_user.username,
-            "password": &test_app.test_user.password
-        }))
-        .await;
+    test_app.login_as_test_user().await;
 
     // Act
     let response = test_app
@@ -70,12 +65,7 @@ async fn current_password_must_be_valid(pool: PgPool) {
     let new_password = Uuid::new_v4().to_string();
     let wrong_password = Uuid::new_v4().to_string();
     // Login
-    test_app
-        .post_login(&serde_json::json!({
-            "username": &test_app.test_user.username,
-            "password": &test_app.test_user.password
-        }))
-        .await;
+    test_app.login_as_test_user().await;
 
     // Act
     let response = test_app
@@ -104,6 +94,9 @@ async fn changing_password_works(pool: PgPool) {
         "password": &test_app.test_user.password
     });
     let response = test_app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/login/two_factor");
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+    let response = test_app.post_login_two_factor(&code).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 
     // Act & Assert 2 - Change password
@@ -134,5 +127,8 @@ async fn changing_password_works(pool: PgPool) {
         "password": &new_password
     });
     let response = test_app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/login/two_factor");
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+    let response = test_app.post_login_two_factor(&code).await;
     assert_is_redirect_to(&response, "/admin/dashboard")
 }
@@ -20,12 +20,7 @@ async fn logout_clears_session_state(pool: PgPool) {
     let test_app = helpers::TestApp::setup(pool).await;
 
     // Act & Assert 1 - Login
-    let response = test_app
-        .post_login(&serde_json::json!({
-            "username": &test_app.test_user.username,
-            "password": &test_app.test_user.password,
-        }))
-        .await;
+    let response = test_app.login_as_test_user().await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 
     // Act & Assert 2 - Follow redirect
@@ -0,0 +1,150 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to, create_subscriber};
+
+/// Publish a newsletter that can never be delivered and drain the queue,
+/// leaving a single entry in the failed deliveries table.
+async fn create_failed_delivery(test_app: &helpers::TestApp) -> (Uuid, String) {
+    test_app.login_as_test_user().await;
+    create_subscriber(test_app, true).await;
+
+    let _mock_guard = Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .named("Failing delivery")
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+
+    let response = test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    test_app.dispatch_all_pending_emails().await;
+
+    let failure =
+        sqlx::query!("SELECT newsletter_issue_id, subscriber_email FROM failed_deliveries")
+            .fetch_one(&*test_app.app_state.db_pool)
+            .await
+            .expect("Failed to fetch failed delivery.");
+    (failure.newsletter_issue_id, failure.subscriber_email)
+}
+
+#[sqlx::test]
+async fn must_be_logged_in_to_see_delivery_failures(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app.get_admin_delivery_failures().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn exhausted_deliveries_are_listed_as_failures(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let (_, subscriber_email) = create_failed_delivery(&test_app).await;
+
+    // Assert
+    let html_page = test_app.get_admin_delivery_failures().await.text();
+    assert!(html_page.contains(&subscriber_email));
+    assert!(html_page.contains("500 Internal Server Error"));
+
+    let remaining = sqlx::query!("SELECT COUNT(*) AS count FROM issue_delivery_queue")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to count queued deliveries.");
+    assert_eq!(remaining.count, Some(0));
+}
+
+#[sqlx::test]
+async fn requeued_failures_are_delivered(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let (newsletter_issue_id, subscriber_email) = create_failed_delivery(&test_app).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_requeue_failed_delivery(&serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+            "subscriber_email": subscriber_email,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/failures");
+    let html_page = test_app.get_admin_delivery_failures().await.text();
+    assert!(html_page.contains("has been requeued"));
+    assert!(html_page.contains("There are no failed deliveries."));
+
+    test_app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that the requeued newsletter was delivered
+}
+
+#[sqlx::test]
+async fn discarded_failures_are_removed(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let (newsletter_issue_id, subscriber_email) = create_failed_delivery(&test_app).await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_discard_failed_delivery(&serde_json::json!({
+            "newsletter_issue_id": newsletter_issue_id,
+            "subscriber_email": subscriber_email,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/failures");
+    let html_page = test_app.get_admin_delivery_failures().await.text();
+    assert!(html_page.contains("has been discarded"));
+    assert!(html_page.contains("There are no failed deliveries."));
+
+    test_app.dispatch_all_pending_emails().await;
+}
+
+#[sqlx::test]
+async fn requeuing_a_failure_twice_reports_it_was_already_requeued(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let (newsletter_issue_id, subscriber_email) = create_failed_delivery(&test_app).await;
+    let body = serde_json::json!({
+        "newsletter_issue_id": newsletter_issue_id,
+        "subscriber_email": subscriber_email,
+    });
+    test_app.post_admin_requeue_failed_delivery(&body).await;
+
+    // Act
+    let response = test_app.post_admin_requeue_failed_delivery(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/failures");
+    let html_page = test_app.get_admin_delivery_failures().await.text();
+    assert!(html_page.contains("was already requeued"));
+    assert!(!html_page.contains(r#"class="error_msg""#));
+}
@@ -0,0 +1,174 @@
+use sqlx::PgPool;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to};
+
+async fn get_list_status(test_app: &helpers::TestApp, email: &str, list: &str) -> Option<String> {
+    sqlx::query!(
+        r#"
+        SELECT ls.status
+        FROM list_subscriptions ls
+        JOIN lists l USING (list_id)
+        JOIN subscriptions s ON s.id = ls.subscriber_id
+        WHERE s.email = $1 AND l.slug = $2
+        "#,
+        email,
+        list
+    )
+    .fetch_optional(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to fetch list subscription.")
+    .map(|r| r.status)
+}
+
+/// Subscribes to the list and follows the link of the confirmation email.
+async fn subscribe_and_confirm(test_app: &helpers::TestApp, email: &str, list: &str) {
+    let response = test_app
+        .post_subscriptions_to_list("Le Guin", email, list)
+        .await;
+    assert_is_redirect_to(&response, "/");
+    let confirmation_links = test_app.confirmation_links_from_latest_email().await;
+    test_app
+        .query_link_with_params(&confirmation_links.html)
+        .await
+        .assert_status_ok();
+}
+
+#[sqlx::test]
+async fn editors_can_create_lists(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = test_app.get_admin_lists().await.text();
+    assert!(html_page.contains("List Release Notes has been created"));
+    assert!(html_page.contains("release-notes"));
+
+    let response = test_app.post_admin_lists("release-notes", "Again").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = test_app.get_admin_lists().await.text();
+    assert!(html_page.contains("A list with slug release-notes already exists"));
+}
+
+#[sqlx::test]
+async fn lists_with_invalid_slugs_are_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app
+        .post_admin_lists("Release Notes", "Release Notes")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/lists");
+    let html_page = test_app.get_admin_lists().await.text();
+    assert!(html_page.contains("is not a valid list slug"));
+}
+
+#[sqlx::test]
+async fn subscriptions_are_confirmed_per_list(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+    let email = helpers::unique_email("lists");
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    subscribe_and_confirm(&test_app, &email, "release-notes").await;
+    let response = test_app
+        .post_subscriptions_to_list("Le Guin", &email, "newsletter")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/");
+    assert_eq!(
+        get_list_status(&test_app, &email, "release-notes").await,
+        Some("confirmed".to_string())
+    );
+    assert_eq!(
+        get_list_status(&test_app, &email, "newsletter").await,
+        Some("pending_confirmation".to_string())
+    );
+}
+
+#[sqlx::test]
+async fn subscribing_to_an_unknown_list_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_subscriptions_to_list("Le Guin", "ursula@example.com", "no-such-list")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("Unknown mailing list"));
+}
+
+#[sqlx::test]
+async fn newsletters_are_only_queued_for_the_target_list(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+    let release_email = helpers::unique_email("release");
+    let newsletter_email = helpers::unique_email("newsletter");
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    subscribe_and_confirm(&test_app, &release_email, "release-notes").await;
+    subscribe_and_confirm(&test_app, &newsletter_email, "newsletter").await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Release 1.0",
+            "text_content": "Release notes as plain text",
+            "html_content": "<p>Release notes as HTML</p>",
+            "idempotency_key": uuid::Uuid::new_v4().to_string(),
+            "list": "release-notes",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let queued: Vec<_> = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.subscriber_email)
+        .collect();
+    assert_eq!(queued, vec![release_email]);
+}
@@ -1,13 +1,9 @@
 use axum::http::StatusCode;
 use axum_test::TestResponse;
-use fake::{
-    faker::{internet::en::SafeEmail, name::en::Name},
-    Fake,
-};
 use sqlx::PgPool;
 use wiremock::{matchers, Mock, ResponseTemplate};
 
-use crate::helpers::{self, assert_is_redirect_to};
+use crate::helpers::{self, assert_is_redirect_to, create_subscriber};
 
 fn sample_newsletter_request_body() -> impl serde::Serialize {
     serde_json::json!({
@@ -32,33 +28,6 @@ async fn unauthorized_requests_are_redirected_to_login(pool: PgPool) {
     assert_is_redirect_to(&response, "/login");
 }
 
-/// Use the public API of the application under test to create a subscriber.
-async fn create_subscriber(test_app: &helpers::TestApp, confirm: bool) {
-    // Scoped mock to assert that subscription will send confirmation email
-    let _mock_guard = Mock::given(matchers::path("/email"))
-        .and(matchers::method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
-        .expect(1)
-        .mount_as_scoped(&test_app.email_server)
-        .await;
-
-    let name = Some(Name().fake());
-    let email = Some(SafeEmail().fake());
-
-    // Whether to confirm the subscriber or not
-    if confirm {
-        let response = test_app
-            .post_subscriptions_and_try_confirm(name, email)
-            .await;
-        response.assert_status_ok();
-    } else {
-        let _ = test_app
-            .post_subscriptions_and_extract_confirmation_link(name, email)
-            .await;
-    }
-}
-
 async fn assert_newsletter_successfully_published(
     test_app: &helpers::TestApp,
     response: &TestResponse,
@@ -139,9 +108,9 @@ async fn newsletters_are_delivered_to_confirmed_subscribers(pool: PgPool) {
     test_app.login_as_test_user().await;
     create_subscriber(&test_app, true).await;
 
-    Mock::given(matchers::path("/email"))
+    Mock::given(matchers::path("/email/batch"))
         .and(matchers::method("POST"))
-        .respond_with(ResponseTemplate::new(200))
+        .respond_with(helpers::PostmarkBatchResponder::default())
         // We assert that 1 request is fired at Postmark!
         .expect(1)
         .mount(&test_app.email_server)
@@ -159,6 +128,101 @@ async fn newsletters_are_delivered_to_confirmed_subscribers(pool: PgPool) {
     // Mock verifies on Drop that we have sent the newsletter email
 }
 
+#[sqlx::test]
+async fn newsletters_are_delivered_to_all_subscribers_in_a_single_batch(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    for _ in 0..3 {
+        create_subscriber(&test_app, true).await;
+    }
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    assert_newsletter_successfully_published(&test_app, &response).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let batch_request = test_app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let messages: Vec<serde_json::Value> = serde_json::from_slice(&batch_request.body).unwrap();
+    assert_eq!(messages.len(), 3);
+
+    let sent = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM newsletter_delivery WHERE status = 'sent'"
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to count logged deliveries.");
+    assert_eq!(sent.count, 3);
+}
+
+#[sqlx::test]
+async fn rejected_recipients_in_a_batch_are_retried_individually(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+    create_subscriber(&test_app, true).await;
+    let rejected = sqlx::query!("SELECT email FROM subscriptions LIMIT 1")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.")
+        .email;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::rejecting(&rejected))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    assert_newsletter_successfully_published(&test_app, &response).await;
+    let outcome = zero2prod::issue_delivery_worker::try_execute_task(
+        &test_app.app_state.db_pool,
+        test_app.app_state.email_client.as_ref(),
+        &test_app.worker_settings,
+        &test_app.app_state.app_base_url,
+        &test_app.app_state.hmac_secret,
+    )
+    .await;
+
+    // Assert
+    assert!(outcome.is_ok());
+    let delivered = sqlx::query!("SELECT subscriber_email, status FROM newsletter_delivery")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch logged delivery.");
+    assert_ne!(delivered.subscriber_email, rejected);
+    assert_eq!(delivered.status, "sent");
+
+    let queued = sqlx::query!("SELECT subscriber_email, n_retries FROM issue_delivery_queue")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch queued delivery.");
+    assert_eq!(queued.subscriber_email, rejected);
+    assert_eq!(queued.n_retries, 1);
+}
+
 #[sqlx::test]
 async fn newsletter_creation_is_idempotent(pool: PgPool) {
     // Arrange
@@ -166,9 +230,9 @@ async fn newsletter_creation_is_idempotent(pool: PgPool) {
     test_app.login_as_test_user().await;
     create_subscriber(&test_app, true).await;
 
-    Mock::given(matchers::path("/email"))
+    Mock::given(matchers::path("/email/batch"))
         .and(matchers::method("POST"))
-        .respond_with(ResponseTemplate::new(200))
+        .respond_with(helpers::PostmarkBatchResponder::default())
         .expect(1)
         .mount(&test_app.email_server)
         .await;
@@ -193,11 +257,14 @@ async fn concurrent_form_submission_is_handled_gracefully(pool: PgPool) {
     test_app.login_as_test_user().await;
     create_subscriber(&test_app, true).await;
 
-    Mock::given(matchers::path("/email"))
+    Mock::given(matchers::path("/email/batch"))
         .and(matchers::method("POST"))
         // Setting a long delay to ensure that the second request
         // arrives before the first one completes
-        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(2)))
+        .respond_with(
+            helpers::PostmarkBatchResponder::default()
+                .with_delay(std::time::Duration::from_secs(2)),
+        )
         .expect(1)
         .mount(&test_app.email_server)
         .await;
@@ -216,3 +283,102 @@ async fn concurrent_form_submission_is_handled_gracefully(pool: PgPool) {
     test_app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we have sent the newsletter email **once**
 }
+
+#[sqlx::test]
+async fn failed_deliveries_are_retried(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+
+    // First attempt fails, the retry succeeds
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    assert_newsletter_successfully_published(&test_app, &response).await;
+
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that the newsletter was attempted twice
+}
+
+#[sqlx::test]
+async fn delivery_is_dropped_after_exhausting_retries(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+
+    let max_attempts = u64::from(test_app.worker_settings.max_retries) + 1;
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(max_attempts)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    assert_newsletter_successfully_published(&test_app, &response).await;
+
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let remaining = sqlx::query!("SELECT COUNT(*) AS count FROM issue_delivery_queue")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to count queued deliveries.");
+    assert_eq!(remaining.count, Some(0));
+}
+
+#[sqlx::test]
+async fn failed_delivery_is_not_retried_before_backoff_elapses(pool: PgPool) {
+    // Arrange
+    let mut test_app = helpers::TestApp::setup(pool).await;
+    test_app.worker_settings.retry_base_delay_ms = 60_000;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    assert_newsletter_successfully_published(&test_app, &response).await;
+
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = sqlx::query!(
+        "SELECT n_retries, execute_after > now() AS \"in_future!\" FROM issue_delivery_queue"
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to fetch queued delivery.");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.in_future);
+}
@@ -0,0 +1,321 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to, create_subscriber};
+
+fn sample_draft_request_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Draft title",
+        "text_content": "Draft body as plain text",
+        "html_content": "<p>Draft body as HTML</p>",
+    })
+}
+
+/// Saves a new draft through the admin form and returns its id.
+async fn create_draft(test_app: &helpers::TestApp) -> Uuid {
+    test_app
+        .post_admin_newsletter_drafts(&sample_draft_request_body())
+        .await;
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+async fn count_queued_deliveries(test_app: &helpers::TestApp) -> i64 {
+    sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM issue_delivery_queue"#)
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn must_be_logged_in_to_manage_drafts(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let get_response = test_app.get_admin_newsletter_drafts().await;
+    let post_response = test_app
+        .post_admin_newsletter_drafts(&sample_draft_request_body())
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&get_response, "/login");
+    assert_is_redirect_to(&post_response, "/login");
+}
+
+#[sqlx::test]
+async fn saving_a_draft_does_not_deliver_it(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletter_drafts(&sample_draft_request_body())
+        .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let newsletter_issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", newsletter_issue_id),
+    );
+    let html_page = test_app.get_admin_newsletter_drafts().await.text();
+    assert!(html_page.contains("Draft title"));
+    // Drafts are not listed among published issues
+    let html_page = test_app.get_admin_newsletters().await.text();
+    assert!(!html_page.contains("Draft title"));
+    assert_eq!(count_queued_deliveries(&test_app).await, 0);
+}
+
+#[sqlx::test]
+async fn draft_can_be_updated(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletter_draft(
+            newsletter_issue_id,
+            &serde_json::json!({
+                "title": "Updated title",
+                "text_content": "Updated text",
+                "html_content": "<p>Updated HTML</p>",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", newsletter_issue_id),
+    );
+    let html_page = test_app
+        .get_admin_newsletter_draft(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Draft successfully saved"));
+    assert!(html_page.contains("Updated title"));
+    assert!(html_page.contains("Updated text"));
+}
+
+#[sqlx::test]
+async fn draft_preview_renders_both_contents(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    // Act
+    let response = test_app
+        .get_admin_newsletter_draft_preview(newsletter_issue_id)
+        .await;
+
+    // Assert
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let html_page = response.text();
+    assert!(html_page.contains("Draft body as plain text"));
+    assert!(html_page.contains("srcdoc="));
+    assert!(html_page.contains("Draft body as HTML"));
+}
+
+#[sqlx::test]
+async fn unknown_draft_returns_404(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app.get_admin_newsletter_draft(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn test_email_is_sent_only_to_the_logged_in_user(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .and(matchers::body_partial_json(serde_json::json!({
+            "To": test_app.test_user.email,
+            "Subject": "Draft title",
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletter_draft_test(newsletter_issue_id)
+        .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/drafts/{}", newsletter_issue_id),
+    );
+    let html_page = test_app
+        .get_admin_newsletter_draft(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains(&format!("Test email sent to {}", test_app.test_user.email)));
+    assert_eq!(count_queued_deliveries(&test_app).await, 0);
+}
+
+#[sqlx::test]
+async fn test_email_is_rejected_when_user_has_no_email(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+    sqlx::query!(
+        "UPDATE users SET email = NULL WHERE user_id = $1",
+        test_app.test_user.user_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app
+        .post_admin_newsletter_draft_test(newsletter_issue_id)
+        .await;
+
+    // Assert
+    let html_page = test_app
+        .get_admin_newsletter_draft(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Your account has no email address"));
+}
+
+#[sqlx::test]
+async fn published_draft_is_delivered_to_confirmed_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletter_draft_publish(newsletter_issue_id, &serde_json::json!({}))
+        .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Newsletter successfully published"));
+    assert!(html_page.contains("Status: published"));
+    // Published issues are no longer editable drafts
+    let response = test_app
+        .get_admin_newsletter_draft(newsletter_issue_id)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn draft_cannot_be_published_twice(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app
+        .post_admin_newsletter_draft_publish(newsletter_issue_id, &serde_json::json!({}))
+        .await;
+    let response = test_app
+        .post_admin_newsletter_draft_publish(newsletter_issue_id, &serde_json::json!({}))
+        .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/drafts");
+    let html_page = test_app.get_admin_newsletter_drafts().await.text();
+    assert!(html_page.contains("Only drafts can be published"));
+}
+
+#[sqlx::test]
+async fn draft_can_be_scheduled(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = create_draft(&test_app).await;
+    let scheduled_for = (chrono::Utc::now() + chrono::Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string();
+
+    // Act
+    test_app
+        .post_admin_newsletter_draft_publish(
+            newsletter_issue_id,
+            &serde_json::json!({ "scheduled_for": scheduled_for }),
+        )
+        .await;
+
+    // Assert
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Newsletter successfully scheduled for"));
+    assert!(html_page.contains("Status: scheduled"));
+    assert_eq!(count_queued_deliveries(&test_app).await, 0);
+}
@@ -0,0 +1,132 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to, create_subscriber};
+
+/// Publish a newsletter through the admin form and return its issue id.
+async fn publish_newsletter(test_app: &helpers::TestApp) -> Uuid {
+    let response = test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch newsletter issue.")
+        .newsletter_issue_id
+}
+
+#[sqlx::test]
+async fn must_be_logged_in_to_see_newsletter_issue(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app.get_admin_newsletter_issue(Uuid::new_v4()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn unknown_newsletter_issue_returns_404(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app.get_admin_newsletter_issue(Uuid::new_v4()).await;
+
+    // Assert
+    response.assert_status(StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn newsletter_issue_page_tracks_delivery_progress(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                "ErrorCode": 0,
+                "Message": "OK",
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+            }])),
+        )
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    let newsletter_issue_id = publish_newsletter(&test_app).await;
+
+    // Act & Assert 1 - Nothing has been sent yet
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Newsletter title"));
+    assert!(html_page.contains("0% complete"));
+
+    // Act & Assert 2 - Everything has been sent
+    test_app.dispatch_all_pending_emails().await;
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("100% complete"));
+
+    let delivery =
+        sqlx::query!("SELECT status, sent_at, provider_message_id FROM newsletter_delivery")
+            .fetch_one(&*test_app.app_state.db_pool)
+            .await
+            .expect("Failed to fetch logged delivery.");
+    assert_eq!(delivery.status, "sent");
+    assert!(delivery.sent_at.is_some());
+    assert_eq!(
+        delivery.provider_message_id.as_deref(),
+        Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+    );
+}
+
+#[sqlx::test]
+async fn failed_deliveries_are_logged(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&test_app.email_server)
+        .await;
+    let newsletter_issue_id = publish_newsletter(&test_app).await;
+
+    // Act
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("100% complete"));
+
+    let delivery = sqlx::query!("SELECT status, sent_at FROM newsletter_delivery")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch logged delivery.");
+    assert_eq!(delivery.status, "failed");
+    assert!(delivery.sent_at.is_none());
+}
@@ -0,0 +1,220 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to, create_subscriber};
+
+fn future_time() -> String {
+    (chrono::Utc::now() + chrono::Duration::days(1))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
+
+/// Schedules a newsletter issue through the admin form and returns its id.
+async fn schedule_newsletter(test_app: &helpers::TestApp) -> Uuid {
+    let response = test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Scheduled newsletter",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "scheduled_for": future_time(),
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id
+}
+
+/// Pretends that the scheduled time of the issue has already passed.
+async fn make_issue_due(test_app: &helpers::TestApp, newsletter_issue_id: Uuid) {
+    sqlx::query!(
+        "UPDATE newsletter_issues SET scheduled_for = now() - interval '1 minute' WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+}
+
+#[sqlx::test]
+async fn scheduled_newsletter_is_not_delivered_before_its_time(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    schedule_newsletter(&test_app).await;
+    test_app.publish_all_due_scheduled_issues().await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = test_app.get_admin_newsletters().await.text();
+    assert!(html_page.contains("Newsletter successfully scheduled for"));
+}
+
+#[sqlx::test]
+async fn scheduled_newsletter_is_delivered_once_due(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = schedule_newsletter(&test_app).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    make_issue_due(&test_app, newsletter_issue_id).await;
+    test_app.publish_all_due_scheduled_issues().await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Status: published"));
+}
+
+#[sqlx::test]
+async fn newsletter_cannot_be_scheduled_in_the_past(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Scheduled newsletter",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "scheduled_for": "2020-01-01T10:00",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let html_page = test_app.get_admin_newsletters().await.text();
+    assert!(html_page.contains("Scheduled time must be in the future"));
+    let n_issues = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM newsletter_issues"#)
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_issues, 0);
+}
+
+#[sqlx::test]
+async fn cancelled_newsletter_is_never_delivered(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = schedule_newsletter(&test_app).await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_cancel_newsletter_issue(newsletter_issue_id)
+        .await;
+    make_issue_due(&test_app, newsletter_issue_id).await;
+    test_app.publish_all_due_scheduled_issues().await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Newsletter successfully cancelled"));
+    assert!(html_page.contains("Status: cancelled"));
+}
+
+#[sqlx::test]
+async fn scheduled_newsletter_can_be_rescheduled(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = schedule_newsletter(&test_app).await;
+    let new_time = (chrono::Utc::now() + chrono::Duration::days(7))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string();
+
+    // Act
+    let response = test_app
+        .post_admin_reschedule_newsletter_issue(
+            newsletter_issue_id,
+            &serde_json::json!({ "scheduled_for": new_time }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/admin/newsletters/{}", newsletter_issue_id),
+    );
+    let html_page = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await
+        .text();
+    assert!(html_page.contains("Newsletter successfully rescheduled for"));
+    let scheduled_for = sqlx::query_scalar!(
+        "SELECT scheduled_for FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(scheduled_for.format("%Y-%m-%dT%H:%M").to_string(), new_time);
+}
+
+#[sqlx::test]
+async fn published_newsletter_cannot_be_cancelled(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let newsletter_issue_id = schedule_newsletter(&test_app).await;
+    make_issue_due(&test_app, newsletter_issue_id).await;
+    test_app.publish_all_due_scheduled_issues().await;
+
+    // Act
+    test_app
+        .post_admin_cancel_newsletter_issue(newsletter_issue_id)
+        .await;
+
+    // Assert
+    let response = test_app
+        .get_admin_newsletter_issue(newsletter_issue_id)
+        .await;
+    assert_eq!(response.status_code(), StatusCode::OK);
+    let html_page = response.text();
+    assert!(html_page.contains("Only scheduled newsletters can be cancelled"));
+    assert!(html_page.contains("Status: published"));
+}
@@ -0,0 +1,232 @@
+use axum::http::StatusCode;
+use chrono::{TimeZone, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::helpers::{self, assert_is_redirect_to};
+
+async fn get_subscriber_tags(test_app: &helpers::TestApp, email: &str) -> Vec<String> {
+    sqlx::query!(
+        r#"
+        SELECT t.tag
+        FROM subscriber_tags t
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        WHERE s.email = $1
+        ORDER BY t.tag
+        "#,
+        email
+    )
+    .fetch_all(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to fetch subscriber tags.")
+    .into_iter()
+    .map(|r| r.tag)
+    .collect()
+}
+
+/// Stores a confirmed subscriber of the default list with the given tags.
+async fn store_tagged_subscriber(
+    test_app: &helpers::TestApp,
+    email: &str,
+    subscribed_at: chrono::DateTime<Utc>,
+    tags: &str,
+) -> Uuid {
+    let (subscriber_id, _) = test_app
+        .store_pending_subscriber(email, subscribed_at)
+        .await;
+    test_app.confirm_stored_subscriber(subscriber_id).await;
+    let response = test_app
+        .post_admin_subscriber_tags(subscriber_id, tags)
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    subscriber_id
+}
+
+#[sqlx::test]
+async fn editors_can_tag_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("tagged");
+    let (subscriber_id, _) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+
+    // Act
+    let response = test_app
+        .post_admin_subscriber_tags(subscriber_id, " VIP, beta-tester,, vip")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = test_app.get_admin_subscribers(&[]).await.text();
+    assert!(html_page.contains("Subscriber tags have been updated"));
+    assert!(html_page.contains(r#"value="beta-tester, vip""#));
+    assert_eq!(
+        get_subscriber_tags(&test_app, &email).await,
+        vec!["beta-tester", "vip"]
+    );
+
+    let response = test_app.post_admin_subscriber_tags(subscriber_id, "").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert!(get_subscriber_tags(&test_app, &email).await.is_empty());
+}
+
+#[sqlx::test]
+async fn invalid_tags_are_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("tagged");
+    let (subscriber_id, _) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+    let too_long = "a".repeat(51);
+
+    // Act
+    let response = test_app
+        .post_admin_subscriber_tags(subscriber_id, &too_long)
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html_page = test_app.get_admin_subscribers(&[]).await.text();
+    assert!(html_page.contains("is not a valid tag"));
+    assert!(get_subscriber_tags(&test_app, &email).await.is_empty());
+}
+
+#[sqlx::test]
+async fn import_sets_tags_from_the_tags_column(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let tagged_email = helpers::unique_email("tagged");
+    let untagged_email = helpers::unique_email("untagged");
+    let csv = format!(
+        "name,email,tags\n\
+        Tagged,{tagged_email},\"Beta-Tester, vip\"\n\
+        Untagged,{untagged_email},\n"
+    );
+
+    // Act
+    let response = test_app
+        .post_admin_subscribers_import(&csv, "confirmed")
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response.text().contains("Imported 2 subscribers"));
+    assert_eq!(
+        get_subscriber_tags(&test_app, &tagged_email).await,
+        vec!["beta-tester", "vip"]
+    );
+    assert!(get_subscriber_tags(&test_app, &untagged_email)
+        .await
+        .is_empty());
+    let export = test_app.get_admin_subscribers_export().await.text();
+    assert!(export
+        .lines()
+        .any(|l| l.starts_with(&tagged_email) && l.ends_with(",\"beta-tester,vip\"")));
+}
+
+#[sqlx::test]
+async fn recipient_count_follows_the_segment(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let early = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+    let late = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+    store_tagged_subscriber(&test_app, &helpers::unique_email("a"), early, "beta-tester").await;
+    store_tagged_subscriber(
+        &test_app,
+        &helpers::unique_email("b"),
+        late,
+        "beta-tester, vip",
+    )
+    .await;
+    store_tagged_subscriber(&test_app, &helpers::unique_email("c"), late, "").await;
+    test_app
+        .store_pending_subscriber(&helpers::unique_email("pending"), early)
+        .await;
+
+    let cases = [
+        (vec![], 3),
+        (vec![("tags", "beta-tester")], 2),
+        (vec![("tags", "beta-tester"), ("excluded_tags", "vip")], 1),
+        (vec![("subscribed_before", "2024-01-01")], 1),
+        (vec![("subscribed_after", "2024-01-01"), ("tags", "vip")], 1),
+    ];
+    for (query, expected) in cases {
+        // Act
+        let response = test_app.get_admin_newsletter_recipients(&query).await;
+
+        // Assert
+        response.assert_status_ok();
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["recipients"], expected,
+            "Unexpected count for {:?}",
+            query
+        );
+    }
+}
+
+#[sqlx::test]
+async fn recipient_count_rejects_invalid_segments(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let invalid_date = test_app
+        .get_admin_newsletter_recipients(&[("subscribed_before", "last year")])
+        .await;
+    let unknown_list = test_app
+        .get_admin_newsletter_recipients(&[("list", "no-such-list")])
+        .await;
+
+    // Assert
+    invalid_date.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(invalid_date
+        .text()
+        .contains("last year is not a valid date"));
+    unknown_list.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test]
+async fn newsletters_are_only_queued_for_the_segment(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let early = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+    let late = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+    let target_email = helpers::unique_email("target");
+    store_tagged_subscriber(&test_app, &target_email, early, "beta-tester").await;
+    store_tagged_subscriber(
+        &test_app,
+        &helpers::unique_email("late"),
+        late,
+        "beta-tester",
+    )
+    .await;
+    store_tagged_subscriber(&test_app, &helpers::unique_email("untagged"), early, "").await;
+
+    // Act
+    let response = test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Beta 2",
+            "text_content": "Beta notes as plain text",
+            "html_content": "<p>Beta notes as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "tags": "beta-tester",
+            "subscribed_before": "2024-01-01",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let queued: Vec<_> = sqlx::query!("SELECT subscriber_email FROM issue_delivery_queue")
+        .fetch_all(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.subscriber_email)
+        .collect();
+    assert_eq!(queued, vec![target_email]);
+}
@@ -0,0 +1,563 @@
+use axum::http::{header, StatusCode};
+use axum_test::multipart::{MultipartForm, Part};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to, TestUser};
+use zero2prod::domain::UserRole;
+
+async fn get_subscriber_status(test_app: &helpers::TestApp, email: &str) -> Option<String> {
+    sqlx::query!("SELECT status FROM subscriptions WHERE email = $1", email)
+        .fetch_optional(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch subscriber.")
+        .map(|r| r.status)
+}
+
+#[sqlx::test]
+async fn must_be_logged_in_to_import_or_export_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let page = test_app.get_admin_subscribers_import().await;
+    let export = test_app.get_admin_subscribers_export().await;
+
+    // Assert
+    assert_is_redirect_to(&page, "/login");
+    assert_is_redirect_to(&export, "/login");
+}
+
+#[sqlx::test]
+async fn confirmed_import_reports_invalid_and_existing_rows(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let existing_email = helpers::unique_email("existing");
+    test_app
+        .store_pending_subscriber(&existing_email, Utc::now())
+        .await;
+    let new_email = helpers::unique_email("new");
+    let csv = format!(
+        "name,email\n\
+        New Subscriber,{new_email}\n\
+        Existing Subscriber,{existing_email}\n\
+        Bad Email,not-an-email\n\
+        Same Subscriber,{new_email}\n"
+    );
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_subscribers_import(&csv, "confirmed")
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    let html_page = response.text();
+    assert!(html_page.contains("Imported 1 subscribers, skipped 1 already"));
+    assert!(html_page.contains("<td>4</td>"));
+    assert!(html_page.contains("<td>5</td>"));
+    assert!(html_page.contains("Email already appears on line 2"));
+    assert_eq!(
+        get_subscriber_status(&test_app, &new_email)
+            .await
+            .as_deref(),
+        Some("confirmed")
+    );
+    assert_eq!(
+        get_subscriber_status(&test_app, &existing_email)
+            .await
+            .as_deref(),
+        Some("pending_confirmation")
+    );
+}
+
+#[sqlx::test]
+async fn import_adds_existing_subscribers_to_the_list_with_their_tags(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+    let existing_email = helpers::unique_email("existing");
+    let (subscriber_id, _) = test_app
+        .store_pending_subscriber(&existing_email, Utc::now())
+        .await;
+    test_app.confirm_stored_subscriber(subscriber_id).await;
+    let csv = format!("name,email,tags\nExisting Subscriber,{existing_email},beta-tester\n");
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act 1 - Import into a list the subscriber is not on
+    let response = test_app
+        .post_admin_subscribers_import_to_list(&csv, "confirmed", "release-notes")
+        .await;
+
+    // Assert 1
+    response.assert_status_ok();
+    let html_page = response.text();
+    assert!(html_page.contains("Imported 0 subscribers, added 1"));
+    assert!(html_page.contains("who already existed to the list, skipped 0 already"));
+    let memberships = sqlx::query!(
+        r#"
+        SELECT l.slug, ls.status,
+            ARRAY(SELECT tag FROM subscriber_tags WHERE subscriber_id = $1) AS "tags!"
+        FROM list_subscriptions ls
+        JOIN lists l USING (list_id)
+        WHERE ls.subscriber_id = $1
+        ORDER BY l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    let statuses: Vec<_> = memberships
+        .iter()
+        .map(|m| (m.slug.as_str(), m.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![("newsletter", "confirmed"), ("release-notes", "confirmed")]
+    );
+    assert_eq!(memberships[0].tags, vec!["beta-tester"]);
+
+    // Act 2 - Import again
+    let response = test_app
+        .post_admin_subscribers_import_to_list(&csv, "confirmed", "release-notes")
+        .await;
+
+    // Assert 2
+    assert!(response
+        .text()
+        .contains("Imported 0 subscribers, skipped 1 already"));
+}
+
+#[sqlx::test]
+async fn import_skips_existing_subscribers_who_unsubscribed(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+    let unsubscribed_email = helpers::unique_email("unsubscribed");
+    let (subscriber_id, _) = test_app
+        .store_pending_subscriber(&unsubscribed_email, Utc::now())
+        .await;
+    sqlx::query!(
+        "UPDATE list_subscriptions SET status = 'unsubscribed' WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1",
+        subscriber_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    let csv = format!("name,email,tags\nUnsubscribed Subscriber,{unsubscribed_email},vip\n");
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_subscribers_import_to_list(&csv, "confirmed", "release-notes")
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response
+        .text()
+        .contains("Imported 0 subscribers, skipped 1 already"));
+    assert_eq!(
+        get_subscriber_status(&test_app, &unsubscribed_email)
+            .await
+            .as_deref(),
+        Some("unsubscribed")
+    );
+    let n_memberships = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM list_subscriptions
+        WHERE subscriber_id = $1 AND status <> 'unsubscribed'
+        "#,
+        subscriber_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_memberships, 0);
+    let n_tags = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM subscriber_tags WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(n_tags, 0);
+}
+
+#[sqlx::test]
+async fn pending_import_sends_confirmation_emails(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let emails = [
+        helpers::unique_email("first"),
+        helpers::unique_email("second"),
+    ];
+    let csv = format!("email,name\n{},First\n{},Second\n", emails[0], emails[1]);
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_admin_subscribers_import(&csv, "pending_confirmation")
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response
+        .text()
+        .contains("confirmation emails are being sent"));
+    for email in &emails {
+        assert_eq!(
+            get_subscriber_status(&test_app, email).await.as_deref(),
+            Some("pending_confirmation")
+        );
+    }
+    // The emails are sent by the delivery worker
+    test_app.dispatch_all_pending_emails().await;
+    let confirmation_link = test_app.confirmation_links_from_latest_email().await.html;
+    test_app
+        .query_link_with_params(&confirmation_link)
+        .await
+        .assert_status_ok();
+}
+
+#[sqlx::test]
+async fn failed_import_confirmation_emails_are_retried(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("retried");
+    let csv = format!("email,name\n{email},Retried\n");
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app
+        .post_admin_subscribers_import(&csv, "pending_confirmation")
+        .await
+        .assert_status_ok();
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let confirmation_link = test_app.confirmation_links_from_latest_email().await.html;
+    test_app
+        .query_link_with_params(&confirmation_link)
+        .await
+        .assert_status_ok();
+    let n_queued =
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM confirmation_email_queue"#)
+            .fetch_one(&*test_app.app_state.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(n_queued, 0);
+}
+
+#[sqlx::test]
+async fn import_without_name_and_email_columns_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app
+        .post_admin_subscribers_import("first,last\nUrsula,Le Guin\n", "confirmed")
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response
+        .text()
+        .contains("needs a header row with `name` and `email` columns"));
+}
+
+#[sqlx::test]
+async fn import_without_csrf_token_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("forged");
+    let form = MultipartForm::new()
+        .add_text("initial_status", "confirmed")
+        .add_part(
+            "file",
+            Part::bytes(format!("name,email\nForged,{}\n", email).into_bytes())
+                .file_name("subscribers.csv"),
+        );
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/admin/subscribers/import")
+        .multipart(form)
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(get_subscriber_status(&test_app, &email).await, None);
+}
+
+#[sqlx::test]
+async fn export_streams_every_subscriber_as_csv(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("exported");
+    test_app.store_pending_subscriber(&email, Utc::now()).await;
+
+    // Act
+    let response = test_app.get_admin_subscribers_export().await;
+
+    // Assert
+    response.assert_status_ok();
+    assert_eq!(
+        response.header(header::CONTENT_TYPE),
+        "text/csv; charset=utf-8"
+    );
+    assert!(response
+        .header(header::CONTENT_DISPOSITION)
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let csv = response.text();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("email,name,status,subscribed_at,tags"));
+    assert!(lines.any(|l| l.starts_with(&format!(
+        "{},Pending Subscriber,pending_confirmation,",
+        email
+    ))));
+}
+
+#[sqlx::test]
+async fn viewers_cannot_import_or_export_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let viewer = TestUser::generate_with_role(UserRole::Viewer);
+    viewer.store(&test_app.app_state.db_pool).await;
+    test_app.login_as(&viewer).await;
+
+    // Act
+    let import = test_app
+        .post_admin_subscribers_import("name,email\n", "confirmed")
+        .await;
+    let export = test_app.get_admin_subscribers_export().await;
+
+    // Assert
+    import.assert_status(StatusCode::FORBIDDEN);
+    export.assert_status(StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn subscribers_can_be_searched_and_filtered_by_status(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let ursula = helpers::unique_email("ursula");
+    let octavia = helpers::unique_email("octavia");
+    let csv = format!("name,email\nUrsula,{ursula}\nOctavia,{octavia}\n");
+    test_app
+        .post_admin_subscribers_import(&csv, "confirmed")
+        .await;
+    let pending = helpers::unique_email("pending");
+    test_app
+        .store_pending_subscriber(&pending, Utc::now())
+        .await;
+
+    // Act
+    let by_name = test_app
+        .get_admin_subscribers(&[("search", "ursu")])
+        .await
+        .text();
+    let by_status = test_app
+        .get_admin_subscribers(&[("status", "pending_confirmation")])
+        .await
+        .text();
+
+    // Assert
+    assert!(by_name.contains(&ursula));
+    assert!(!by_name.contains(&octavia));
+    assert!(by_status.contains(&pending));
+    assert!(!by_status.contains(&ursula));
+}
+
+#[sqlx::test]
+async fn subscribers_are_paginated_from_newest_to_oldest(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let emails: Vec<_> = (0..55)
+        .map(|i| helpers::unique_email(&format!("page{}", i)))
+        .collect();
+    let mut csv = "name,email\n".to_string();
+    for email in &emails {
+        csv.push_str(&format!("Subscriber,{}\n", email));
+    }
+    test_app
+        .post_admin_subscribers_import(&csv, "confirmed")
+        .await;
+    let ids: Vec<Uuid> = sqlx::query!("SELECT id FROM subscriptions ORDER BY id DESC")
+        .fetch_all(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+
+    // Act 1 - First page
+    let first_page = test_app.get_admin_subscribers(&[]).await.text();
+
+    // Assert 1
+    assert_eq!(first_page.matches("<td>Subscriber</td>").count(), 50);
+    assert!(first_page.contains(&format!("after={}", ids[49])));
+
+    // Act 2 - Follow the link to older subscribers
+    let after = ids[49].to_string();
+    let second_page = test_app
+        .get_admin_subscribers(&[("after", &after)])
+        .await
+        .text();
+
+    // Assert 2
+    assert_eq!(second_page.matches("<td>Subscriber</td>").count(), 5);
+    assert!(!second_page.contains("after="));
+}
+
+#[sqlx::test]
+async fn editors_can_confirm_unsubscribe_and_delete_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("managed");
+    let (subscriber_id, _) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+
+    // Act & Assert 1 - Confirm
+    let response = test_app
+        .post_admin_subscriber_action(subscriber_id, "confirm")
+        .await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    assert_eq!(
+        get_subscriber_status(&test_app, &email).await.as_deref(),
+        Some("confirmed")
+    );
+
+    // Act & Assert 2 - Unsubscribe
+    test_app
+        .post_admin_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+    assert_eq!(
+        get_subscriber_status(&test_app, &email).await.as_deref(),
+        Some("unsubscribed")
+    );
+
+    // Act & Assert 3 - Delete
+    test_app
+        .post_admin_subscriber_action(subscriber_id, "delete")
+        .await;
+    assert_eq!(get_subscriber_status(&test_app, &email).await, None);
+    let html_page = test_app.get_admin_subscribers(&[]).await.text();
+    assert!(html_page.contains(&format!("{} has been deleted", email)));
+}
+
+#[sqlx::test]
+async fn editors_can_resend_confirmation_emails(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = helpers::unique_email("resend");
+    let (subscriber_id, old_token) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    test_app
+        .post_admin_subscriber_action(subscriber_id, "resend")
+        .await;
+
+    // Assert
+    let html_page = test_app.get_admin_subscribers(&[]).await.text();
+    assert!(html_page.contains(&format!(
+        "A new confirmation email has been sent to {}",
+        email
+    )));
+    let confirmation_link = test_app.confirmation_links_from_latest_email().await.html;
+    assert!(!confirmation_link.as_str().contains(old_token.as_str()));
+}
+
+#[sqlx::test]
+async fn viewers_can_list_subscribers_but_not_manage_them(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let viewer = TestUser::generate_with_role(UserRole::Viewer);
+    viewer.store(&test_app.app_state.db_pool).await;
+    test_app.login_as(&viewer).await;
+    let email = helpers::unique_email("viewed");
+    let (subscriber_id, _) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+
+    // Act
+    let page = test_app.get_admin_subscribers(&[]).await;
+    let delete = test_app
+        .post_admin_subscriber_action(subscriber_id, "delete")
+        .await;
+
+    // Assert
+    page.assert_status_ok();
+    assert!(page.text().contains(&email));
+    delete.assert_status(StatusCode::FORBIDDEN);
+    assert!(get_subscriber_status(&test_app, &email).await.is_some());
+}
@@ -0,0 +1,289 @@
+use axum::http::{header, HeaderValue, StatusCode};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::helpers::{self, assert_is_redirect_to, TestUser};
+use zero2prod::domain::{ApiKeyScope, UserRole};
+
+async fn store_user_with_role(test_app: &helpers::TestApp, role: UserRole) -> TestUser {
+    let user = TestUser::generate_with_role(role);
+    user.store(&test_app.app_state.db_pool).await;
+    user
+}
+
+fn sample_newsletter_request_body() -> impl serde::Serialize {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    })
+}
+
+#[sqlx::test]
+async fn must_be_logged_in_to_manage_users(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let page = test_app.get_admin_users().await;
+    let invite = test_app
+        .post_admin_users(&[
+            ("username", "ada"),
+            ("email", "ada@example.com"),
+            ("password", "pw"),
+            ("role", "editor"),
+        ])
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&page, "/login");
+    assert_is_redirect_to(&invite, "/login");
+}
+
+#[sqlx::test]
+async fn invited_user_can_log_in_with_the_initial_password(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act - Part 1 - Invite
+    let response = test_app
+        .post_admin_users(&[
+            ("username", "ada"),
+            ("email", "ada@example.com"),
+            ("password", "initial-password"),
+            ("role", "editor"),
+        ])
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    // Act - Part 2 - Follow redirect
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains("User ada invited as editor"));
+    assert!(html_page.contains(&test_app.test_user.username));
+
+    // Act - Part 3 - Log in as the new user
+    test_app.post_admin_logout().await;
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": "ada",
+            "password": "initial-password",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[sqlx::test]
+async fn inviting_a_taken_username_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let username = test_app.test_user.username.clone();
+
+    // Act
+    let response = test_app
+        .post_admin_users(&[
+            ("username", username.as_str()),
+            ("email", "someone.else@example.com"),
+            ("password", "initial-password"),
+            ("role", "viewer"),
+        ])
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains(&format!("Username {} is already taken", username)));
+}
+
+#[sqlx::test]
+async fn inviting_a_taken_email_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let email = test_app.test_user.email.clone();
+
+    // Act
+    test_app
+        .post_admin_users(&[
+            ("username", "ada"),
+            ("email", email.as_str()),
+            ("password", "initial-password"),
+            ("role", "viewer"),
+        ])
+        .await;
+
+    // Assert
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains(&format!("Email {} is already taken", email)));
+}
+
+#[sqlx::test]
+async fn inviting_with_an_unknown_role_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    test_app
+        .post_admin_users(&[
+            ("username", "ada"),
+            ("email", "ada@example.com"),
+            ("password", "initial-password"),
+            ("role", "superuser"),
+        ])
+        .await;
+
+    // Assert
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains("superuser is not a valid user role"));
+}
+
+#[sqlx::test]
+async fn deactivated_user_is_logged_out_and_cannot_log_in(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let editor = store_user_with_role(&test_app, UserRole::Editor).await;
+
+    // Act - Part 1 - Owner deactivates the editor
+    test_app.login_as_test_user().await;
+    let response = test_app.post_admin_deactivate_user(editor.user_id).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains("User successfully deactivated"));
+
+    // Act - Part 2 - Editor tries to log in
+    test_app.post_admin_logout().await;
+    let response = test_app.login_as(&editor).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn user_deactivated_while_logged_in_is_logged_out(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let editor = store_user_with_role(&test_app, UserRole::Editor).await;
+    test_app.login_as(&editor).await;
+    test_app.get_admin_dashboard().await.assert_status_ok();
+
+    // Act
+    sqlx::query!(
+        "UPDATE users SET deactivated_at = now() WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    let response = test_app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn deleted_user_and_their_api_keys_are_removed(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let editor = store_user_with_role(&test_app, UserRole::Editor).await;
+    let api_key = editor
+        .store_api_key(&test_app.app_state.db_pool, &[ApiKeyScope::SubscribersRead])
+        .await;
+    test_app.login_as_test_user().await;
+
+    // Act
+    let response = test_app.post_admin_delete_user(editor.user_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains("User successfully deleted"));
+    assert!(!html_page.contains(&editor.username));
+    test_app
+        .app_server
+        .get("/api/v1/subscribers")
+        .add_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", api_key)).unwrap(),
+        )
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn owners_cannot_deactivate_or_delete_themselves(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act & Assert 1 - Deactivate
+    test_app
+        .post_admin_deactivate_user(test_app.test_user.user_id)
+        .await;
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains("You cannot deactivate your own account"));
+
+    // Act & Assert 2 - Delete
+    test_app
+        .post_admin_delete_user(test_app.test_user.user_id)
+        .await;
+    let html_page = test_app.get_admin_users().await.text();
+    assert!(html_page.contains("You cannot delete your own account"));
+}
+
+#[sqlx::test]
+async fn viewers_can_see_issues_but_not_publish(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let viewer = store_user_with_role(&test_app, UserRole::Viewer).await;
+    test_app.login_as(&viewer).await;
+
+    // Act & Assert 1 - Read-only pages are available
+    test_app.get_admin_dashboard().await.assert_status_ok();
+    test_app.get_admin_newsletters().await.assert_status_ok();
+    test_app
+        .get_admin_newsletter_drafts()
+        .await
+        .assert_status_ok();
+
+    // Act & Assert 2 - Publishing is forbidden
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    response.assert_status(StatusCode::FORBIDDEN);
+    assert!(response
+        .text()
+        .contains("You need the editor role to do this"));
+
+    // Act & Assert 3 - Owner pages are forbidden
+    test_app
+        .get_admin_users()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    test_app
+        .get_admin_api_keys()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn editors_can_publish_but_not_manage_users(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let editor = store_user_with_role(&test_app, UserRole::Editor).await;
+    test_app.login_as(&editor).await;
+
+    // Act
+    let publish = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    let users = test_app.get_admin_users().await;
+
+    // Assert
+    assert_is_redirect_to(&publish, "/admin/newsletters");
+    users.assert_status(StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,351 @@
+use axum::http::{header, HeaderValue, StatusCode};
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, create_subscriber};
+
+fn assert_api_error(response: &axum_test::TestResponse, status: StatusCode, code: &str) {
+    response.assert_status(status);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["error"]["code"], code);
+    assert!(body["error"]["message"].is_string());
+}
+
+#[sqlx::test]
+async fn requests_without_api_key_are_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    for path in ["/api/v1/subscribers", "/api/v1/newsletters"] {
+        // Act
+        let response = test_app.app_server.get(path).await;
+
+        // Assert
+        assert_api_error(&response, StatusCode::UNAUTHORIZED, "unauthorized");
+    }
+}
+
+#[sqlx::test]
+async fn requests_with_invalid_api_key_are_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .get("/api/v1/subscribers")
+        .add_header(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer not-the-api-key"),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(&response, StatusCode::UNAUTHORIZED, "unauthorized");
+}
+
+#[sqlx::test]
+async fn create_subscriber_stores_pending_subscriber_and_sends_confirmation(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_api(
+            "/api/v1/subscribers",
+            &serde_json::json!({ "name": "le guin", "email": "ursula_le_guin@gmail.com" }),
+        )
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::CREATED);
+    let body: serde_json::Value = response.json();
+    assert_eq!(body["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(body["status"], "pending_confirmation");
+
+    let subscriber_id = body["id"].as_str().unwrap();
+    let response = test_app
+        .get_api(&format!("/api/v1/subscribers/{}", subscriber_id))
+        .await;
+    response.assert_status_ok();
+    assert_eq!(response.json::<serde_json::Value>()["name"], "le guin");
+}
+
+#[sqlx::test]
+async fn create_subscriber_with_invalid_data_returns_validation_error(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .post_api(
+            "/api/v1/subscribers",
+            &serde_json::json!({ "name": "le guin", "email": "definitely-not-an-email" }),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(
+        &response,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "validation_error",
+    );
+}
+
+#[sqlx::test]
+async fn create_subscriber_with_malformed_body_returns_json_error(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .post_api(
+            "/api/v1/subscribers",
+            &serde_json::json!({ "name": "le guin" }),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(
+        &response,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_request",
+    );
+}
+
+#[sqlx::test]
+async fn create_already_confirmed_subscriber_returns_conflict(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.")
+        .email;
+
+    // Act
+    let response = test_app
+        .post_api(
+            "/api/v1/subscribers",
+            &serde_json::json!({ "name": "le guin", "email": email }),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(&response, StatusCode::CONFLICT, "already_confirmed");
+}
+
+#[sqlx::test]
+async fn unknown_subscriber_returns_404(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .get_api(&format!("/api/v1/subscribers/{}", Uuid::new_v4()))
+        .await;
+
+    // Assert
+    assert_api_error(&response, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[sqlx::test]
+async fn subscribers_can_be_listed_by_status(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    create_subscriber(&test_app, false).await;
+
+    // Act
+    let all = test_app.get_api("/api/v1/subscribers").await;
+    let confirmed = test_app
+        .get_api("/api/v1/subscribers?status=confirmed")
+        .await;
+    let invalid = test_app.get_api("/api/v1/subscribers?status=bogus").await;
+
+    // Assert
+    all.assert_status_ok();
+    let all: serde_json::Value = all.json();
+    assert_eq!(all["subscribers"].as_array().unwrap().len(), 2);
+
+    confirmed.assert_status_ok();
+    let confirmed: serde_json::Value = confirmed.json();
+    let confirmed = confirmed["subscribers"].as_array().unwrap();
+    assert_eq!(confirmed.len(), 1);
+    assert_eq!(confirmed[0]["status"], "confirmed");
+
+    assert_api_error(
+        &invalid,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "validation_error",
+    );
+}
+
+#[sqlx::test]
+async fn published_newsletter_is_delivered_and_listed(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_api(
+            "/api/v1/newsletters",
+            &serde_json::json!({
+                "title": "Release notes",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+        )
+        .await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    response.assert_status(StatusCode::CREATED);
+    let issue: serde_json::Value = response.json();
+    assert_eq!(issue["title"], "Release notes");
+    assert_eq!(issue["status"], "published");
+
+    let newsletters: serde_json::Value = test_app.get_api("/api/v1/newsletters").await.json();
+    assert_eq!(
+        newsletters["newsletters"][0]["newsletter_issue_id"],
+        issue["newsletter_issue_id"]
+    );
+}
+
+#[sqlx::test]
+async fn newsletter_cannot_be_scheduled_in_the_past(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .post_api(
+            "/api/v1/newsletters",
+            &serde_json::json!({
+                "title": "Release notes",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+                "scheduled_for": "2000-01-01T00:00:00Z",
+            }),
+        )
+        .await;
+
+    // Assert
+    assert_api_error(
+        &response,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "validation_error",
+    );
+}
+
+#[sqlx::test]
+async fn publish_retried_with_the_same_idempotency_key_is_delivered_once(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    let body = serde_json::json!({
+        "title": "Release notes",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+    let (name, value) = test_app.api_authorization();
+
+    // Act
+    let mut responses = vec![];
+    for _ in 0..2 {
+        let response = test_app
+            .app_server
+            .post("/api/v1/newsletters")
+            .add_header(name.clone(), value.clone())
+            .add_header(
+                header::HeaderName::from_static("idempotency-key"),
+                HeaderValue::from_str(&idempotency_key).unwrap(),
+            )
+            .json(&body)
+            .await;
+        responses.push(response);
+    }
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    for response in &responses {
+        response.assert_status(StatusCode::CREATED);
+    }
+    let first: serde_json::Value = responses[0].json();
+    let second: serde_json::Value = responses[1].json();
+    assert_eq!(first["newsletter_issue_id"], second["newsletter_issue_id"]);
+    let newsletters: serde_json::Value = test_app.get_api("/api/v1/newsletters").await.json();
+    assert_eq!(newsletters["newsletters"].as_array().unwrap().len(), 1);
+}
+
+#[sqlx::test]
+async fn newsletter_without_title_or_content_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let test_cases = [
+        (
+            serde_json::json!({
+                "title": " ",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+            "empty title",
+        ),
+        (
+            serde_json::json!({
+                "title": "Release notes",
+                "text_content": "",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+            "empty text content",
+        ),
+        (
+            serde_json::json!({
+                "title": "Release notes",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "",
+            }),
+            "empty HTML content",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        // Act
+        let response = test_app.post_api("/api/v1/newsletters", &body).await;
+
+        // Assert
+        assert_eq!(
+            response.status_code(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "The API did not reject a newsletter with {}",
+            description
+        );
+        assert_api_error(
+            &response,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "validation_error",
+        );
+    }
+}
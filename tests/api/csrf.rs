@@ -0,0 +1,140 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::helpers::{self, assert_is_redirect_to};
+
+#[sqlx::test]
+async fn login_form_embeds_the_session_csrf_token(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let html_page = test_app.get_login().await.text();
+
+    // Assert
+    let csrf_token = test_app.csrf_token().await;
+    assert!(html_page.contains(&format!(r#"name="csrf_token" value="{}""#, csrf_token)));
+}
+
+#[sqlx::test]
+async fn login_without_csrf_token_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.get_login().await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/login")
+        .form(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::FORBIDDEN);
+    assert_eq!(response.text(), "Invalid CSRF token");
+}
+
+#[sqlx::test]
+async fn login_with_csrf_token_form_field_is_accepted(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let csrf_token = test_app.csrf_token().await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/login")
+        .form(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+            "csrf_token": &csrf_token,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[sqlx::test]
+async fn admin_form_with_wrong_csrf_token_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/admin/password")
+        .form(&serde_json::json!({
+            "current_password": &test_app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+            "csrf_token": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::FORBIDDEN);
+    // The password is left unchanged
+    test_app.post_admin_logout().await;
+    let response = test_app.login_as_test_user().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[sqlx::test]
+async fn anonymous_admin_requests_are_redirected_before_checking_csrf_token(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app.app_server.post("/admin/logout").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[sqlx::test]
+async fn login_without_session_is_rejected_before_reading_the_body(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/login")
+        .form(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "x".repeat(1024 * 1024),
+            "csrf_token": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn oversize_login_form_is_rejected_as_too_large(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let csrf_token = test_app.csrf_token().await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/login")
+        .form(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": "x".repeat(1024 * 1024),
+            "csrf_token": &csrf_token,
+        }))
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+}
@@ -1,15 +1,35 @@
+use std::net::{IpAddr, Ipv6Addr};
+
 use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
-use axum::http::StatusCode;
-use axum_test::{TestResponse, TestServer};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum_test::{
+    multipart::{MultipartForm, Part},
+    TestRequest, TestResponse, TestServer,
+};
+use chrono::{DateTime, Utc};
+use fake::{
+    faker::{internet::en::SafeEmail, name::en::Name},
+    Fake,
+};
 use once_cell::sync::Lazy;
+use secrecy::Secret;
 use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{matchers, Mock, MockServer, ResponseTemplate};
 
 use zero2prod::{
-    configuration::get_configuration,
-    domain::Url,
-    issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    configuration::{get_configuration, EmailTransportSettings, Settings, WorkerSettings},
+    database::api_key_db::insert_api_key,
+    domain::{
+        ApiKey, ApiKeyScope, ListSlug, SubscriptionStatus, SubscriptionToken, TotpSecret, Url,
+        UserRole,
+    },
+    issue_delivery_worker::{
+        run_worker_until_stopped, try_execute_task, try_send_confirmation_emails, ExecutionOutcome,
+    },
+    newsletter_scheduler::try_publish_scheduled_issue,
     startup::{default_app_state_and_session, AppState},
     telemetry::{get_subscriber, init_subscriber},
 };
@@ -31,19 +51,31 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
+    pub email: String,
     pub password: String,
+    pub role: UserRole,
+    /// Editors and owners need two-factor authentication, so they are enrolled by default.
+    pub totp_secret: Option<TotpSecret>,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role(UserRole::Owner)
+    }
+
+    pub fn generate_with_role(role: UserRole) -> Self {
+        let username = Uuid::new_v4().to_string();
         Self {
             user_id: Uuid::new_v4(),
-            username: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", username),
+            username,
             password: Uuid::new_v4().to_string(),
+            role,
+            totp_secret: role.includes(UserRole::Editor).then(TotpSecret::generate),
         }
     }
 
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             argon2::Algorithm::Argon2id,
@@ -55,15 +87,38 @@ impl TestUser {
         .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (user_id, username, email, password_hash, role)
+            VALUES ($1, $2, $3, $4, $5)",
             self.user_id,
             self.username,
-            password_hash
+            self.email,
+            password_hash,
+            self.role.to_string()
         )
         .execute(pool)
         .await
         .expect("Failed to create test users.");
+
+        if let Some(secret) = &self.totp_secret {
+            sqlx::query!(
+                "INSERT INTO two_factor_credentials (user_id, secret, confirmed_at)
+                VALUES ($1, $2, now())",
+                self.user_id,
+                secret.expose_base32()
+            )
+            .execute(pool)
+            .await
+            .expect("Failed to enroll test user in two-factor authentication.");
+        }
+    }
+
+    /// Mints an API key for the user with the given scopes, returning the key.
+    pub async fn store_api_key(&self, pool: &PgPool, scopes: &[ApiKeyScope]) -> String {
+        let api_key = ApiKey::generate();
+        insert_api_key(pool, self.user_id, "Test key", &api_key, scopes)
+            .await
+            .expect("Failed to create test API key.");
+        api_key.expose_secret().to_string()
     }
 }
 
@@ -77,6 +132,9 @@ pub struct TestApp {
     pub app_state: AppState,
     pub email_server: MockServer,
     pub test_user: TestUser,
+    pub worker_settings: WorkerSettings,
+    pub settings: Settings,
+    pub api_key: String,
 }
 
 impl TestApp {
@@ -88,8 +146,17 @@ impl TestApp {
 
         let config = {
             let mut c = get_configuration().expect("Failed to read configuration.");
-            // Overwrite email client URL to use mock server
-            c.email_client.base_url = email_server.uri();
+            // Overwrite email client to send through the mock server
+            c.email_client.transport = EmailTransportSettings::Postmark {
+                base_url: email_server.uri(),
+                authorization_token: Secret::new("some_token".to_string()),
+            };
+            // Retry failed deliveries immediately so tests do not have to wait
+            c.worker.retry_base_delay_ms = 0;
+            // The test server has no peer address, so tests pass the client IP in a header
+            c.application.client_ip_header = Some("x-forwarded-for".to_string());
+            // Every failed login costs a password hash, so keep the per-IP limit quick to reach
+            c.login_rate_limit.max_failures_per_ip = 10;
             c
         };
 
@@ -106,12 +173,18 @@ impl TestApp {
         // Setup test user
         let test_user = TestUser::generate();
         test_user.store(&app_state.db_pool.clone()).await;
+        let api_key = test_user
+            .store_api_key(&app_state.db_pool, &ApiKeyScope::ALL)
+            .await;
 
         Self {
             app_server,
             app_state,
             email_server,
             test_user,
+            worker_settings: config.worker.clone(),
+            settings: config,
+            api_key,
         }
     }
 
@@ -133,16 +206,45 @@ impl TestApp {
         email: Option<String>,
     ) -> TestResponse {
         let mut data = vec![];
-        if name.is_some() {
-            data.push(("name", name.unwrap()))
+        if let Some(name) = name {
+            data.push(("name", name))
         }
-        if email.is_some() {
-            data.push(("email", email.unwrap()))
+        if let Some(email) = email {
+            data.push(("email", email))
         }
 
         self.app_server.post("/subscribe").form(&data).await
     }
 
+    /// Send POST request to `/subscribe` with name, email and the slug of a list.
+    pub async fn post_subscriptions_to_list(
+        &self,
+        name: &str,
+        email: &str,
+        list: &str,
+    ) -> TestResponse {
+        self.app_server
+            .post("/subscribe")
+            .form(&[("name", name), ("email", email), ("list", list)])
+            .await
+    }
+
+    pub async fn post_subscriptions_from_ip(
+        &self,
+        name: &str,
+        email: &str,
+        ip: IpAddr,
+    ) -> TestResponse {
+        self.app_server
+            .post("/subscribe")
+            .add_header(
+                HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(&ip.to_string()).unwrap(),
+            )
+            .form(&[("name", name), ("email", email)])
+            .await
+    }
+
     /// Send POST request to `/subscribe` with name and email.
     /// Asserts that the response for the request is OK and extracts confirmation links
     /// from the response body.
@@ -159,6 +261,11 @@ impl TestApp {
         let html_page = self.get_index().await.text();
         assert!(html_page.contains("Thanks for subscribing!"));
 
+        self.confirmation_links_from_latest_email().await
+    }
+
+    /// Extracts confirmation links from the latest email received by the mock email server.
+    pub async fn confirmation_links_from_latest_email(&self) -> ConfirmationLinks {
         let email_requests = &self.email_server.received_requests().await.unwrap();
         let latest_email_request = email_requests
             .last()
@@ -172,13 +279,13 @@ impl TestApp {
                 .filter(|l| *l.kind() == linkify::LinkKind::Url)
                 .collect();
             // There should be at least 1 link for confirmation
-            assert!(links.len() > 0);
+            assert!(!links.is_empty());
             // The link is always the last one
             links.last().unwrap().as_str().to_string()
         };
 
-        let html_link = get_link(&body["HtmlBody"].as_str().unwrap());
-        let text_link = get_link(&body["TextBody"].as_str().unwrap());
+        let html_link = get_link(body["HtmlBody"].as_str().unwrap());
+        let text_link = get_link(body["TextBody"].as_str().unwrap());
 
         ConfirmationLinks {
             html: Url::parse(&html_link).expect("Failed to parse html confirmation link."),
@@ -199,27 +306,287 @@ impl TestApp {
         self.query_link_with_params(&confirmation_links.html).await
     }
 
+    /// Stores a subscriber waiting for confirmation, whose token was issued at `issued_at`.
+    /// Returns the id of the subscriber and the token.
+    pub async fn store_pending_subscriber(
+        &self,
+        email: &str,
+        issued_at: DateTime<Utc>,
+    ) -> (Uuid, SubscriptionToken) {
+        let subscriber_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, name, email, subscribed_at, status)
+            VALUES ($1, 'Pending Subscriber', $2, $3, $4)
+            "#,
+            subscriber_id,
+            email,
+            issued_at,
+            SubscriptionStatus::PendingConfirmation.to_string()
+        )
+        .execute(&*self.app_state.db_pool)
+        .await
+        .expect("Failed to store pending subscriber.");
+        sqlx::query!(
+            r#"
+            INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+            SELECT list_id, $2, $3, $4 FROM lists WHERE slug = $1
+            "#,
+            ListSlug::DEFAULT,
+            subscriber_id,
+            SubscriptionStatus::PendingConfirmation.to_string(),
+            issued_at
+        )
+        .execute(&*self.app_state.db_pool)
+        .await
+        .expect("Failed to store pending list subscription.");
+
+        let token = SubscriptionToken::generate();
+        sqlx::query!(
+            r#"
+            INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id, created_at)
+            SELECT $2, $3, list_id, $4 FROM lists WHERE slug = $1
+            "#,
+            ListSlug::DEFAULT,
+            token.as_str(),
+            subscriber_id,
+            issued_at
+        )
+        .execute(&*self.app_state.db_pool)
+        .await
+        .expect("Failed to store subscription token.");
+
+        (subscriber_id, token)
+    }
+
+    /// Confirms a subscriber stored with `store_pending_subscriber`, as if they had followed
+    /// the confirmation link.
+    pub async fn confirm_stored_subscriber(&self, subscriber_id: Uuid) {
+        sqlx::query!(
+            "UPDATE list_subscriptions SET status = $1 WHERE subscriber_id = $2",
+            SubscriptionStatus::Confirmed.to_string(),
+            subscriber_id
+        )
+        .execute(&*self.app_state.db_pool)
+        .await
+        .expect("Failed to confirm list subscription.");
+        sqlx::query!(
+            "UPDATE subscriptions SET status = $1 WHERE id = $2",
+            SubscriptionStatus::Confirmed.to_string(),
+            subscriber_id
+        )
+        .execute(&*self.app_state.db_pool)
+        .await
+        .expect("Failed to confirm subscriber.");
+    }
+
+    pub async fn get_list_id(&self, slug: &str) -> Uuid {
+        sqlx::query!("SELECT list_id FROM lists WHERE slug = $1", slug)
+            .fetch_one(&*self.app_state.db_pool)
+            .await
+            .expect("Failed to fetch list.")
+            .list_id
+    }
+
+    /// Adds a confirmed subscription to the list for a subscriber that already exists.
+    pub async fn store_confirmed_list_subscription(&self, subscriber_id: Uuid, slug: &str) {
+        sqlx::query!(
+            r#"
+            INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+            SELECT list_id, $2, $3, now() FROM lists WHERE slug = $1
+            "#,
+            slug,
+            subscriber_id,
+            SubscriptionStatus::Confirmed.to_string()
+        )
+        .execute(&*self.app_state.db_pool)
+        .await
+        .expect("Failed to store list subscription.");
+    }
+
+    pub async fn get_subscribe_resend(&self) -> TestResponse {
+        self.app_server.get("/subscribe/resend").await
+    }
+
+    pub async fn post_subscribe_resend(&self, email: &str) -> TestResponse {
+        self.app_server
+            .post("/subscribe/resend")
+            .add_header(
+                HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(&random_ip().to_string()).unwrap(),
+            )
+            .form(&[("email", email)])
+            .await
+    }
+
     pub async fn get_login(&self) -> TestResponse {
         self.app_server.get("/login").await
     }
 
     pub async fn login_as_test_user(&self) -> TestResponse {
-        self.post_login(&serde_json::json!({
-            "username": self.test_user.username,
-            "password": self.test_user.password,
-        }))
+        self.login_as(&self.test_user).await
+    }
+
+    /// Logs in with the user's password, then with a TOTP code if the app asks for one.
+    pub async fn login_as(&self, user: &TestUser) -> TestResponse {
+        let response = self
+            .post_login(&serde_json::json!({
+                "username": user.username,
+                "password": user.password,
+            }))
+            .await;
+        if response.maybe_header("Location") != Some(HeaderValue::from_static("/login/two_factor"))
+        {
+            return response;
+        }
+
+        let code = self.two_factor_code(user).await;
+        self.post_login_two_factor(&code).await
+    }
+
+    /// Reads the CSRF token of the current session from a page that embeds it into its forms.
+    pub async fn csrf_token(&self) -> String {
+        // Prefer the dashboard, since the login page consumes flash messages
+        let response = self.get_admin_dashboard().await;
+        let html_page = if response.status_code().is_redirection() {
+            self.get_login().await.text()
+        } else {
+            response.text()
+        };
+
+        html_page
+            .split(r#"name="csrf_token" value=""#)
+            .nth(1)
+            .and_then(|s| s.split('"').next())
+            .expect("No CSRF token found in page")
+            .to_string()
+    }
+
+    /// Starts a POST request that carries the session's CSRF token, like the forms of our pages.
+    async fn post_with_csrf_token(&self, path: &str) -> TestRequest {
+        let csrf_token = self.csrf_token().await;
+        self.app_server.post(path).add_header(
+            HeaderName::from_static("x-csrf-token"),
+            HeaderValue::from_str(&csrf_token).unwrap(),
+        )
+    }
+
+    pub async fn get_login_two_factor(&self) -> TestResponse {
+        self.app_server.get("/login/two_factor").await
+    }
+
+    pub async fn post_login_two_factor(&self, code: &str) -> TestResponse {
+        self.post_with_csrf_token("/login/two_factor")
+            .await
+            .form(&[("code", code)])
+            .await
+    }
+
+    /// Computes a TOTP code for the user that the app has not accepted yet, since codes cannot be
+    /// reused. This allows up to two logins per 30-second period.
+    pub async fn two_factor_code(&self, user: &TestUser) -> String {
+        let secret = user
+            .totp_secret
+            .as_ref()
+            .expect("Test user has no two-factor authentication.");
+        let last_used_step = sqlx::query!(
+            "SELECT last_used_step FROM two_factor_credentials WHERE user_id = $1",
+            user.user_id
+        )
+        .fetch_one(&*self.app_state.db_pool)
         .await
+        .expect("Failed to fetch two-factor credential.")
+        .last_used_step;
+
+        let current_step = TotpSecret::step_at(chrono::Utc::now());
+        let step = last_used_step.map_or(current_step, |s| (s + 1).max(current_step));
+        assert!(
+            step <= current_step + 1,
+            "Too many logins within a single TOTP period."
+        );
+        secret.code_at(step)
     }
 
     pub async fn post_login<Body>(&self, body: &Body) -> TestResponse
     where
         Body: serde::Serialize,
     {
-        self.app_server.post("/login").form(body).await
+        self.post_with_csrf_token("/login").await.form(body).await
+    }
+
+    pub async fn post_login_from_ip<Body>(&self, body: &Body, ip: IpAddr) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/login")
+            .await
+            .add_header(
+                HeaderName::from_static("x-forwarded-for"),
+                HeaderValue::from_str(&ip.to_string()).unwrap(),
+            )
+            .form(body)
+            .await
+    }
+
+    pub async fn get_password_reset(&self) -> TestResponse {
+        self.app_server.get("/password_reset").await
+    }
+
+    pub async fn post_password_reset(&self, username: &str) -> TestResponse {
+        self.post_with_csrf_token("/password_reset")
+            .await
+            .form(&[("username", username)])
+            .await
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/password_reset/confirm")
+            .await
+            .form(body)
+            .await
+    }
+
+    /// Waits for a password reset email, which is sent in the background, and extracts its link.
+    pub async fn wait_for_password_reset_link(&self) -> Url {
+        for _ in 0..50 {
+            let email_requests = self.email_server.received_requests().await.unwrap();
+            if let Some(request) = email_requests.last() {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                let text_body = body["TextBody"].as_str().unwrap();
+                let link = linkify::LinkFinder::new()
+                    .links(text_body)
+                    .find(|l| *l.kind() == linkify::LinkKind::Url)
+                    .expect("No link in password reset email");
+                return Url::parse(link.as_str()).expect("Failed to parse password reset link.");
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("No password reset email received on mock email server");
+    }
+
+    pub async fn get_admin_two_factor(&self) -> TestResponse {
+        self.app_server.get("/admin/two_factor").await
+    }
+
+    pub async fn post_admin_two_factor(&self, code: &str) -> TestResponse {
+        self.post_with_csrf_token("/admin/two_factor")
+            .await
+            .form(&[("code", code)])
+            .await
+    }
+
+    pub async fn post_admin_disable_two_factor(&self, code: &str) -> TestResponse {
+        self.post_with_csrf_token("/admin/two_factor/disable")
+            .await
+            .form(&[("code", code)])
+            .await
     }
 
     pub async fn post_admin_logout(&self) -> TestResponse {
-        self.app_server.post("/admin/logout").await
+        self.post_with_csrf_token("/admin/logout").await.await
     }
 
     pub async fn get_admin_dashboard(&self) -> TestResponse {
@@ -234,7 +601,10 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.app_server.post("/admin/password").form(body).await
+        self.post_with_csrf_token("/admin/password")
+            .await
+            .form(body)
+            .await
     }
 
     pub async fn get_admin_newsletters(&self) -> TestResponse {
@@ -246,13 +616,289 @@ impl TestApp {
     where
         Body: serde::Serialize,
     {
-        self.app_server.post("/admin/newsletters").form(body).await
+        self.post_with_csrf_token("/admin/newsletters")
+            .await
+            .form(body)
+            .await
     }
 
-    pub async fn dispatch_all_pending_emails(&self) {
+    pub async fn get_admin_newsletter_issue(&self, newsletter_issue_id: Uuid) -> TestResponse {
+        self.app_server
+            .get(&format!("/admin/newsletters/{}", newsletter_issue_id))
+            .await
+    }
+
+    pub async fn get_admin_users(&self) -> TestResponse {
+        self.app_server.get("/admin/users").await
+    }
+
+    pub async fn post_admin_users<Body>(&self, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/admin/users")
+            .await
+            .form(body)
+            .await
+    }
+
+    pub async fn post_admin_deactivate_user(&self, user_id: Uuid) -> TestResponse {
+        self.post_with_csrf_token(&format!("/admin/users/{}/deactivate", user_id))
+            .await
+            .await
+    }
+
+    pub async fn post_admin_delete_user(&self, user_id: Uuid) -> TestResponse {
+        self.post_with_csrf_token(&format!("/admin/users/{}/delete", user_id))
+            .await
+            .await
+    }
+
+    pub async fn get_admin_subscribers(&self, query: &[(&str, &str)]) -> TestResponse {
+        self.app_server
+            .get("/admin/subscribers")
+            .add_query_params(query)
+            .await
+    }
+
+    /// Posts one of the per-subscriber actions of the subscribers page, e.g. `confirm`.
+    pub async fn post_admin_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> TestResponse {
+        self.post_with_csrf_token(&format!("/admin/subscribers/{}/{}", subscriber_id, action))
+            .await
+            .await
+    }
+
+    pub async fn post_admin_subscriber_tags(
+        &self,
+        subscriber_id: Uuid,
+        tags: &str,
+    ) -> TestResponse {
+        self.post_with_csrf_token(&format!("/admin/subscribers/{}/tags", subscriber_id))
+            .await
+            .form(&[("tags", tags)])
+            .await
+    }
+
+    pub async fn get_admin_newsletter_recipients(&self, query: &[(&str, &str)]) -> TestResponse {
+        self.app_server
+            .get("/admin/newsletters/recipients")
+            .add_query_params(query)
+            .await
+    }
+
+    pub async fn get_admin_lists(&self) -> TestResponse {
+        self.app_server.get("/admin/lists").await
+    }
+
+    pub async fn post_admin_lists(&self, slug: &str, name: &str) -> TestResponse {
+        self.post_with_csrf_token("/admin/lists")
+            .await
+            .form(&[("slug", slug), ("name", name)])
+            .await
+    }
+
+    pub async fn get_admin_subscribers_import(&self) -> TestResponse {
+        self.app_server.get("/admin/subscribers/import").await
+    }
+
+    /// Uploads the CSV like the import page does, with the CSRF token as a form field.
+    pub async fn post_admin_subscribers_import(
+        &self,
+        csv: &str,
+        initial_status: &str,
+    ) -> TestResponse {
+        self.post_admin_subscribers_import_to_list(csv, initial_status, ListSlug::DEFAULT)
+            .await
+    }
+
+    pub async fn post_admin_subscribers_import_to_list(
+        &self,
+        csv: &str,
+        initial_status: &str,
+        list: &str,
+    ) -> TestResponse {
+        let form = MultipartForm::new()
+            .add_text("csrf_token", self.csrf_token().await)
+            .add_text("initial_status", initial_status)
+            .add_text("list", list)
+            .add_part(
+                "file",
+                Part::bytes(csv.as_bytes().to_vec())
+                    .file_name("subscribers.csv")
+                    .mime_type("text/csv"),
+            );
+        self.app_server
+            .post("/admin/subscribers/import")
+            .multipart(form)
+            .await
+    }
+
+    pub async fn get_admin_subscribers_export(&self) -> TestResponse {
+        self.app_server.get("/admin/subscribers/export").await
+    }
+
+    pub async fn get_admin_api_keys(&self) -> TestResponse {
+        self.app_server.get("/admin/api_keys").await
+    }
+
+    pub async fn post_admin_api_keys<Body>(&self, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/admin/api_keys")
+            .await
+            .form(body)
+            .await
+    }
+
+    pub async fn post_admin_revoke_api_key(&self, api_key_id: Uuid) -> TestResponse {
+        self.post_with_csrf_token(&format!("/admin/api_keys/{}/revoke", api_key_id))
+            .await
+            .await
+    }
+
+    pub async fn get_admin_delivery_failures(&self) -> TestResponse {
+        self.app_server.get("/admin/newsletters/failures").await
+    }
+
+    pub async fn post_admin_requeue_failed_delivery<Body>(&self, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/admin/newsletters/failures/requeue")
+            .await
+            .form(body)
+            .await
+    }
+
+    pub async fn post_admin_discard_failed_delivery<Body>(&self, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/admin/newsletters/failures/discard")
+            .await
+            .form(body)
+            .await
+    }
+
+    pub async fn get_admin_newsletter_drafts(&self) -> TestResponse {
+        self.app_server.get("/admin/newsletters/drafts").await
+    }
+
+    pub async fn post_admin_newsletter_drafts<Body>(&self, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token("/admin/newsletters/drafts")
+            .await
+            .form(body)
+            .await
+    }
+
+    pub async fn get_admin_newsletter_draft(&self, newsletter_issue_id: Uuid) -> TestResponse {
+        self.app_server
+            .get(&format!(
+                "/admin/newsletters/drafts/{}",
+                newsletter_issue_id
+            ))
+            .await
+    }
+
+    pub async fn post_admin_newsletter_draft<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token(&format!(
+            "/admin/newsletters/drafts/{}",
+            newsletter_issue_id
+        ))
+        .await
+        .form(body)
+        .await
+    }
+
+    pub async fn get_admin_newsletter_draft_preview(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> TestResponse {
+        self.app_server
+            .get(&format!(
+                "/admin/newsletters/drafts/{}/preview",
+                newsletter_issue_id
+            ))
+            .await
+    }
+
+    pub async fn post_admin_newsletter_draft_test(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> TestResponse {
+        self.post_with_csrf_token(&format!(
+            "/admin/newsletters/drafts/{}/test",
+            newsletter_issue_id
+        ))
+        .await
+        .await
+    }
+
+    pub async fn post_admin_newsletter_draft_publish<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token(&format!(
+            "/admin/newsletters/drafts/{}/publish",
+            newsletter_issue_id
+        ))
+        .await
+        .form(body)
+        .await
+    }
+
+    pub async fn post_admin_reschedule_newsletter_issue<Body>(
+        &self,
+        newsletter_issue_id: Uuid,
+        body: &Body,
+    ) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        self.post_with_csrf_token(&format!(
+            "/admin/newsletters/{}/reschedule",
+            newsletter_issue_id
+        ))
+        .await
+        .form(body)
+        .await
+    }
+
+    pub async fn post_admin_cancel_newsletter_issue(
+        &self,
+        newsletter_issue_id: Uuid,
+    ) -> TestResponse {
+        self.post_with_csrf_token(&format!(
+            "/admin/newsletters/{}/cancel",
+            newsletter_issue_id
+        ))
+        .await
+        .await
+    }
+
+    pub async fn publish_all_due_scheduled_issues(&self) {
         loop {
             if let ExecutionOutcome::EmptyQueue =
-                try_execute_task(&self.app_state.db_pool, &self.app_state.email_client)
+                try_publish_scheduled_issue(&self.app_state.db_pool)
                     .await
                     .unwrap()
             {
@@ -260,6 +906,165 @@ impl TestApp {
             }
         }
     }
+
+    /// Bearer token header accepted by the JSON API.
+    pub fn api_authorization(&self) -> (HeaderName, HeaderValue) {
+        let value = format!("Bearer {}", self.api_key);
+        (
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&value).unwrap(),
+        )
+    }
+
+    /// Send authenticated GET request to the JSON API.
+    pub async fn get_api(&self, path: &str) -> TestResponse {
+        let (name, value) = self.api_authorization();
+        self.app_server.get(path).add_header(name, value).await
+    }
+
+    /// Send authenticated POST request with a JSON body to the JSON API.
+    pub async fn post_api<Body>(&self, path: &str, body: &Body) -> TestResponse
+    where
+        Body: serde::Serialize,
+    {
+        let (name, value) = self.api_authorization();
+        self.app_server
+            .post(path)
+            .add_header(name, value)
+            .json(body)
+            .await
+    }
+
+    /// Runs the delivery worker pool in the background until `shutdown` is cancelled.
+    pub fn spawn_worker_pool(
+        &self,
+        shutdown: CancellationToken,
+    ) -> JoinHandle<Result<(), anyhow::Error>> {
+        tokio::spawn(run_worker_until_stopped(
+            self.settings.clone(),
+            Some((*self.app_state.db_pool).clone()),
+            shutdown,
+        ))
+    }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.app_state.db_pool,
+                self.app_state.email_client.as_ref(),
+                &self.worker_settings,
+                &self.app_state.app_base_url,
+                &self.app_state.hmac_secret,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_send_confirmation_emails(
+                &self.app_state.db_pool,
+                self.app_state.email_client.as_ref(),
+                &self.worker_settings,
+                &self.app_state.app_base_url,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// Stands in for Postmark's batch API, accepting every message in the batch
+/// except the ones addressed to a rejected recipient.
+#[derive(Default)]
+pub struct PostmarkBatchResponder {
+    rejected_recipients: Vec<String>,
+    delay: std::time::Duration,
+}
+
+impl PostmarkBatchResponder {
+    pub fn rejecting(recipient: &str) -> Self {
+        Self {
+            rejected_recipients: vec![recipient.to_string()],
+            ..Default::default()
+        }
+    }
+
+    pub fn with_delay(self, delay: std::time::Duration) -> Self {
+        Self { delay, ..self }
+    }
+}
+
+impl wiremock::Respond for PostmarkBatchResponder {
+    fn respond(&self, request: &wiremock::Request) -> ResponseTemplate {
+        let messages: Vec<serde_json::Value> =
+            serde_json::from_slice(&request.body).expect("Batch request body is not an array.");
+        let results: Vec<_> = messages
+            .iter()
+            .map(|message| {
+                let recipient = message["To"].as_str().unwrap_or_default();
+                if self.rejected_recipients.iter().any(|r| r == recipient) {
+                    serde_json::json!({
+                        "ErrorCode": 406,
+                        "Message": "You tried to send to a recipient that has been marked as inactive.",
+                        "To": recipient,
+                    })
+                } else {
+                    serde_json::json!({
+                        "ErrorCode": 0,
+                        "Message": "OK",
+                        "MessageID": Uuid::new_v4().to_string(),
+                        "To": recipient,
+                    })
+                }
+            })
+            .collect();
+
+        ResponseTemplate::new(200)
+            .set_body_json(results)
+            .set_delay(self.delay)
+    }
+}
+
+/// Use the public API of the application under test to create a subscriber.
+/// Email address no other test uses, since subscription rate limits are kept in Redis across runs.
+/// Random address, so that tests sharing the Redis instance do not rate limit each other.
+pub fn random_ip() -> IpAddr {
+    IpAddr::V6(Ipv6Addr::from(rand::random::<u128>()))
+}
+
+pub fn unique_email(local_part: &str) -> String {
+    format!("{}+{}@gmail.com", local_part, Uuid::new_v4().simple())
+}
+
+pub async fn create_subscriber(test_app: &TestApp, confirm: bool) {
+    // Scoped mock to assert that subscription will send confirmation email
+    let _mock_guard = Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .named("Create unconfirmed subscriber")
+        .expect(1)
+        .mount_as_scoped(&test_app.email_server)
+        .await;
+
+    let name = Some(Name().fake());
+    let email = Some(SafeEmail().fake());
+
+    // Whether to confirm the subscriber or not
+    if confirm {
+        let response = test_app
+            .post_subscriptions_and_try_confirm(name, email)
+            .await;
+        response.assert_status_ok();
+    } else {
+        let _ = test_app
+            .post_subscriptions_and_extract_confirmation_link(name, email)
+            .await;
+    }
 }
 
 pub fn assert_is_redirect_to(response: &TestResponse, location: &str) {
@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use wiremock::{matchers, Mock};
+
+use crate::helpers::{self, create_subscriber};
+
+async fn count_queued_deliveries(test_app: &helpers::TestApp) -> i64 {
+    sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to count queued deliveries.")
+        .count
+}
+
+#[sqlx::test]
+async fn worker_pool_delivers_queued_issues_and_stops_on_shutdown(pool: PgPool) {
+    // Arrange
+    let mut test_app = helpers::TestApp::setup(pool).await;
+    test_app.settings.worker.concurrency = 2;
+    test_app.settings.worker.batch_size = 1;
+    test_app.login_as_test_user().await;
+    for _ in 0..4 {
+        create_subscriber(&test_app, true).await;
+    }
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(4)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Act
+    let shutdown = CancellationToken::new();
+    let worker_pool = test_app.spawn_worker_pool(shutdown.clone());
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while count_queued_deliveries(&test_app).await > 0 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Queue was not drained in time.");
+    shutdown.cancel();
+
+    // Assert
+    let outcome = tokio::time::timeout(Duration::from_secs(5), worker_pool)
+        .await
+        .expect("Worker pool did not stop after shutdown.");
+    assert!(outcome.unwrap().is_ok());
+}
+
+#[sqlx::test]
+async fn idle_worker_pool_stops_without_waiting_for_next_poll(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let shutdown = CancellationToken::new();
+    let worker_pool = test_app.spawn_worker_pool(shutdown.clone());
+    // Give workers time to find the queue empty and go idle
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Act
+    shutdown.cancel();
+
+    // Assert
+    let outcome = tokio::time::timeout(Duration::from_secs(1), worker_pool)
+        .await
+        .expect("Worker pool did not stop after shutdown.");
+    assert!(outcome.unwrap().is_ok());
+}
+
+#[sqlx::test]
+async fn idle_worker_pool_is_woken_up_by_newly_published_issues(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    create_subscriber(&test_app, true).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let shutdown = CancellationToken::new();
+    let worker_pool = test_app.spawn_worker_pool(shutdown.clone());
+    // Give workers time to find the queue empty and go idle
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Act
+    test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    // Assert - well within the 10 seconds workers would otherwise sleep for
+    tokio::time::timeout(Duration::from_secs(3), async {
+        while count_queued_deliveries(&test_app).await > 0 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("Idle workers were not woken up by the new issue.");
+
+    shutdown.cancel();
+    worker_pool.await.unwrap().unwrap();
+}
@@ -1,4 +1,5 @@
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::helpers;
 
@@ -9,7 +10,7 @@ async fn an_error_flash_message_is_set_on_failure(pool: PgPool) {
 
     // Act & Assert 1 - Login
     let login_body = serde_json::json!({
-        "username": "some-username",
+        "username": Uuid::new_v4().to_string(),
         "password": "some-password"
     });
     let response = test_app.post_login(&login_body).await;
@@ -35,9 +36,101 @@ async fn redirect_to_admin_dashboard_after_login_success(pool: PgPool) {
         "password": &test_app.test_user.password,
     });
     let response = test_app.post_login(&login_body).await;
+    helpers::assert_is_redirect_to(&response, "/login/two_factor");
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+    let response = test_app.post_login_two_factor(&code).await;
     helpers::assert_is_redirect_to(&response, "/admin/dashboard");
 
     // Act & Assert 2 - Follow redirect
     let html_page = test_app.get_admin_dashboard().await.text();
     assert!(html_page.contains(&format!("Welcome {}!", test_app.test_user.username)));
 }
+
+#[sqlx::test]
+async fn username_is_locked_out_after_too_many_failures(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let max_failures = test_app.settings.login_rate_limit.max_failures_per_username;
+    for _ in 0..max_failures {
+        test_app
+            .post_login(&serde_json::json!({
+                "username": &test_app.test_user.username,
+                "password": Uuid::new_v4().to_string(),
+            }))
+            .await;
+    }
+
+    // Act - Even the right password is rejected
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    helpers::assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login().await.text();
+    assert!(html_page.contains("Too many failed login attempts, please try again in"));
+}
+
+#[sqlx::test]
+async fn ip_is_locked_out_after_too_many_failures_across_usernames(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let ip = helpers::random_ip();
+    let max_failures = test_app.settings.login_rate_limit.max_failures_per_ip;
+    for _ in 0..max_failures {
+        test_app
+            .post_login_from_ip(
+                &serde_json::json!({
+                    "username": Uuid::new_v4().to_string(),
+                    "password": Uuid::new_v4().to_string(),
+                }),
+                ip,
+            )
+            .await;
+    }
+    let login_body = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": &test_app.test_user.password,
+    });
+
+    // Act & Assert 1 - Locked out from the same IP
+    let response = test_app.post_login_from_ip(&login_body, ip).await;
+    helpers::assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login().await.text();
+    assert!(html_page.contains("Too many failed login attempts"));
+
+    // Act & Assert 2 - Other IPs are unaffected
+    let response = test_app
+        .post_login_from_ip(&login_body, helpers::random_ip())
+        .await;
+    helpers::assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[sqlx::test]
+async fn successful_login_resets_failures(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let max_failures = test_app.settings.login_rate_limit.max_failures_per_username;
+    let wrong_login_body = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": Uuid::new_v4().to_string(),
+    });
+    for _ in 1..max_failures {
+        test_app.post_login(&wrong_login_body).await;
+    }
+    let response = test_app.login_as_test_user().await;
+    helpers::assert_is_redirect_to(&response, "/admin/dashboard");
+    test_app.post_admin_logout().await;
+
+    // Act
+    for _ in 1..max_failures {
+        test_app.post_login(&wrong_login_body).await;
+    }
+    let response = test_app.login_as_test_user().await;
+
+    // Assert
+    helpers::assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,8 +1,26 @@
+mod admin_api_keys;
 mod admin_change_password;
 mod admin_dashboard;
+mod admin_delivery_failures;
+mod admin_lists;
 mod admin_newsletter;
+mod admin_newsletter_drafts;
+mod admin_newsletter_issue;
+mod admin_newsletter_schedule;
+mod admin_segments;
+mod admin_subscribers;
+mod admin_users;
+mod api_v1;
+mod csrf;
 mod health;
 mod helpers;
+mod issue_delivery_worker;
 mod login;
+mod password_reset;
 mod subscribe;
 mod subscribe_confirm;
+mod subscribe_resend;
+mod subscription_purger;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod two_factor;
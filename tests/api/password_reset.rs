@@ -0,0 +1,194 @@
+use sqlx::PgPool;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to, TestUser};
+use zero2prod::domain::{Url, UserRole};
+
+const RESET_REQUESTED_MESSAGE: &str =
+    "If the account exists, a password reset link has been sent to its email address";
+
+async fn mount_email_mock(test_app: &helpers::TestApp, n_emails: u64) {
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(n_emails)
+        .mount(&test_app.email_server)
+        .await;
+}
+
+fn reset_token(link: &Url) -> String {
+    link.query_params()
+        .into_iter()
+        .find(|(k, _)| k == "token")
+        .map(|(_, v)| v)
+        .expect("No token in password reset link")
+}
+
+#[sqlx::test]
+async fn forgot_password_page_is_linked_from_login(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let login_page = test_app.get_login().await.text();
+    let response = test_app.get_password_reset().await;
+
+    // Assert
+    assert!(login_page.contains(r#"href="/password_reset""#));
+    response.assert_status_ok();
+}
+
+#[sqlx::test]
+async fn password_can_be_reset_through_the_emailed_link(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    mount_email_mock(&test_app, 1).await;
+
+    // Act - Part 1 - Request a reset
+    let response = test_app
+        .post_password_reset(&test_app.test_user.username)
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login().await.text();
+    assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+
+    // Act - Part 2 - Follow the emailed link
+    let link = test_app.wait_for_password_reset_link().await;
+    let response = test_app.query_link_with_params(&link).await;
+    response.assert_status_ok();
+    assert!(response.text().contains("Reset Password"));
+
+    // Act - Part 3 - Choose a new password
+    let new_password = uuid::Uuid::new_v4().to_string();
+    let response = test_app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": reset_token(&link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login().await.text();
+    assert!(html_page.contains("Your password has been reset, you can now log in"));
+
+    // Act - Part 4 - Log in with the new password, which still requires the second factor
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[sqlx::test]
+async fn reset_link_can_only_be_used_once(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    mount_email_mock(&test_app, 1).await;
+    test_app
+        .post_password_reset(&test_app.test_user.username)
+        .await;
+    let link = test_app.wait_for_password_reset_link().await;
+    let body = serde_json::json!({
+        "token": reset_token(&link),
+        "new_password": "first-new-password",
+        "new_password_check": "first-new-password",
+    });
+    test_app.post_password_reset_confirm(&body).await;
+
+    // Act
+    let response = test_app.post_password_reset_confirm(&body).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password_reset");
+    let html_page = test_app.get_password_reset().await.text();
+    assert!(html_page.contains("The password reset link is invalid or has expired"));
+    assert_is_redirect_to(
+        &test_app.query_link_with_params(&link).await,
+        "/password_reset",
+    );
+}
+
+#[sqlx::test]
+async fn expired_reset_link_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    mount_email_mock(&test_app, 1).await;
+    test_app
+        .post_password_reset(&test_app.test_user.username)
+        .await;
+    let link = test_app.wait_for_password_reset_link().await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&*test_app.app_state.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = test_app.query_link_with_params(&link).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/password_reset");
+    let html_page = test_app.get_password_reset().await.text();
+    assert!(html_page.contains("The password reset link is invalid or has expired"));
+}
+
+#[sqlx::test]
+async fn mismatched_new_passwords_keep_the_link_usable(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    mount_email_mock(&test_app, 1).await;
+    test_app
+        .post_password_reset(&test_app.test_user.username)
+        .await;
+    let link = test_app.wait_for_password_reset_link().await;
+    let token = reset_token(&link);
+
+    // Act
+    let response = test_app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": &token,
+            "new_password": "new-password",
+            "new_password_check": "another-new-password",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(
+        &response,
+        &format!("/password_reset/confirm?token={}", token),
+    );
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("You entered two different new passwords"));
+}
+
+#[sqlx::test]
+async fn unknown_and_deactivated_users_get_the_same_response_but_no_email(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    mount_email_mock(&test_app, 0).await;
+    let deactivated = TestUser::generate_with_role(UserRole::Editor);
+    deactivated.store(&test_app.app_state.db_pool).await;
+    sqlx::query!(
+        "UPDATE users SET deactivated_at = now() WHERE user_id = $1",
+        deactivated.user_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+
+    for username in ["no-such-user", deactivated.username.as_str()] {
+        // Act
+        let response = test_app.post_password_reset(username).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/login");
+        let html_page = test_app.get_login().await.text();
+        assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+    }
+
+    // Give the background tasks a chance to (not) send anything
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+}
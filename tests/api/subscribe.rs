@@ -1,7 +1,8 @@
 use axum::http::StatusCode;
+use chrono::Utc;
 use sqlx::PgPool;
 use wiremock::{matchers, Mock, ResponseTemplate};
-use zero2prod::domain::{SubscriptionStatus, Url};
+use zero2prod::domain::SubscriptionStatus;
 
 use crate::helpers::{self, assert_is_redirect_to};
 
@@ -10,7 +11,7 @@ async fn subscribe_works_for_valid_form_data(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Bob Banjo";
-    let email = "bob_banjo@gmail.com";
+    let email = &helpers::unique_email("bob_banjo");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -35,7 +36,7 @@ async fn subscribe_persists_new_subscriber(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Naruto";
-    let email = "naruto@konoha.co.jp";
+    let email = &helpers::unique_email("naruto");
 
     // Act
     test_app
@@ -49,7 +50,7 @@ async fn subscribe_persists_new_subscriber(pool: PgPool) {
         .expect("Failed to fetch saved subscription");
 
     assert_eq!(saved.name, name, "Name not equal");
-    assert_eq!(saved.email, email, "Email not equal");
+    assert_eq!(&saved.email, email, "Email not equal");
     assert_eq!(
         saved.status,
         SubscriptionStatus::PendingConfirmation.to_string(),
@@ -118,7 +119,7 @@ async fn subscribe_returns_error_when_fields_are_present_but_invalid(pool: PgPoo
             "empty name",
             TestCase {
                 name: Some("".into()),
-                email: Some("booboo@yahoo.com".into()),
+                email: Some(helpers::unique_email("booboo")),
             },
         ),
         (
@@ -157,7 +158,7 @@ async fn subscribe_sends_a_confirmation_email_for_valid_data(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Le Mao".to_string();
-    let email = "lemao@gmail.com".to_string();
+    let email = helpers::unique_email("lemao");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -180,7 +181,7 @@ async fn subscribe_sends_confirmation_email_with_link(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Le Mao".to_string();
-    let email = "lemao@gmail.com".to_string();
+    let email = helpers::unique_email("lemao");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -205,55 +206,29 @@ async fn subscribe_sends_confirmation_email_with_link(pool: PgPool) {
 }
 
 #[sqlx::test]
-async fn subscribe_sends_two_identical_confirmation_emails_if_called_twice(pool: PgPool) {
+async fn subscribe_does_not_resend_confirmation_email_during_cooldown(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Le Mao".to_string();
-    let email = "lemao@gmail.com".to_string();
+    let email = helpers::unique_email("lemao");
 
-    // Act
-    let first_html_link: Url;
-    let second_html_link: Url;
-
-    {
-        let _mock_guard = Mock::given(matchers::path("/email"))
-            .and(matchers::method("POST"))
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount_as_scoped(&test_app.email_server)
-            .await;
-
-        let confirmation_links = test_app
-            .post_subscriptions_and_extract_confirmation_link(
-                Some(name.clone()),
-                Some(email.clone()),
-            )
-            .await;
-        first_html_link = confirmation_links.html.clone();
-    }
-
-    {
-        let _mock_guard = Mock::given(matchers::path("/email"))
-            .and(matchers::method("POST"))
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount_as_scoped(&test_app.email_server)
-            .await;
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
 
-        let confirmation_links = test_app
-            .post_subscriptions_and_extract_confirmation_link(
-                Some(name.clone()),
-                Some(email.clone()),
-            )
-            .await;
-        second_html_link = confirmation_links.html.clone();
-    }
+    // Act
+    test_app
+        .post_subscriptions_and_extract_confirmation_link(Some(name.clone()), Some(email.clone()))
+        .await;
+    let response = test_app.post_subscriptions(Some(name), Some(email)).await;
 
-    assert_eq!(
-        first_html_link.as_str(),
-        second_html_link.as_str(),
-        "HTML links from 2 separate"
-    )
+    // Assert
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("Thanks for subscribing!"));
 }
 
 #[sqlx::test]
@@ -261,7 +236,7 @@ async fn subscribe_returns_error_if_subscription_already_confirmed(pool: PgPool)
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Le Mao".to_string();
-    let email = "lemao@gmail.com".to_string();
+    let email = helpers::unique_email("lemao");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -297,7 +272,7 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error(pool: PgPool) {
     let response = test_app
         .post_subscriptions(
             Some("le guin".into()),
-            Some("ursula_le_guin@gmail.com".into()),
+            Some(helpers::unique_email("ursula_le_guin")),
         )
         .await;
 
@@ -306,3 +281,141 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error(pool: PgPool) {
     let html_page = test_app.get_index().await.text();
     assert!(html_page.contains("Something went wrong"));
 }
+
+#[sqlx::test]
+async fn subscribe_ignores_submissions_with_filled_in_honeypot(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/subscribe")
+        .form(&[
+            ("name", "Bot Botson"),
+            ("email", &helpers::unique_email("bot")),
+            ("website", "https://spam.example.com"),
+        ])
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("Thanks for subscribing!"));
+    let n_subscribers = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM subscriptions")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(n_subscribers, 0);
+}
+
+#[sqlx::test]
+async fn subscribe_rejects_too_many_attempts_for_the_same_email(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("impatient");
+    let max_attempts = test_app
+        .settings
+        .subscribe_rate_limit
+        .max_attempts_per_email;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    for _ in 0..max_attempts {
+        test_app
+            .post_subscriptions(Some("Impatient".into()), Some(email.clone()))
+            .await;
+    }
+
+    // Act
+    let response = test_app
+        .post_subscriptions(Some("Impatient".into()), Some(email))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("Too many subscription attempts, please try again later"));
+}
+
+#[sqlx::test]
+async fn subscribe_rejects_too_many_attempts_from_the_same_ip(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    // Random address, so that tests sharing the Redis instance do not limit each other
+    let ip = helpers::random_ip();
+    let max_attempts = test_app.settings.subscribe_rate_limit.max_attempts_per_ip;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    for _ in 0..max_attempts {
+        test_app
+            .post_subscriptions_from_ip("Spammer", &helpers::unique_email("victim"), ip)
+            .await;
+    }
+
+    // Act & Assert 1 - Rejected from the same IP
+    let response = test_app
+        .post_subscriptions_from_ip("Spammer", &helpers::unique_email("victim"), ip)
+        .await;
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("Too many subscription attempts"));
+
+    // Act & Assert 2 - Other IPs are unaffected
+    let other_ip = helpers::random_ip();
+    let response = test_app
+        .post_subscriptions_from_ip("Someone", &helpers::unique_email("someone"), other_ip)
+        .await;
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("Thanks for subscribing!"));
+}
+
+#[sqlx::test]
+async fn subscribing_again_replaces_an_expired_confirmation_token(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("stale");
+    let issued_at = Utc::now() - test_app.settings.subscriptions.confirmation_token_ttl();
+    let (_, old_token) = test_app.store_pending_subscriber(&email, issued_at).await;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let confirmation_links = test_app
+        .post_subscriptions_and_extract_confirmation_link(
+            Some("Pending Subscriber".into()),
+            Some(email),
+        )
+        .await;
+
+    // Assert
+    assert!(!confirmation_links
+        .html
+        .as_str()
+        .contains(old_token.as_str()));
+    test_app
+        .query_link_with_params(&confirmation_links.html)
+        .await
+        .assert_status_ok();
+}
@@ -1,4 +1,5 @@
 use axum::http::StatusCode;
+use chrono::Utc;
 use sqlx::PgPool;
 use wiremock::{matchers, Mock, ResponseTemplate};
 
@@ -10,7 +11,7 @@ async fn the_link_returned_by_subscribe_returns_ok_if_called(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Adaya";
-    let email = "adayayadaya@yaya.com";
+    let email = &helpers::unique_email("adayayadaya");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -51,7 +52,7 @@ async fn clicking_on_confirmation_link_confirms_a_subscriber(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Adaya";
-    let email = "adayayadaya@yaya.com";
+    let email = &helpers::unique_email("adayayadaya");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -72,7 +73,7 @@ async fn clicking_on_confirmation_link_confirms_a_subscriber(pool: PgPool) {
         .expect("Failed to fetch saved subscription.");
 
     assert_eq!(saved.name, name, "Name not equal");
-    assert_eq!(saved.email, email, "Email not equal");
+    assert_eq!(&saved.email, email, "Email not equal");
     assert_eq!(
         saved.status,
         SubscriptionStatus::Confirmed.to_string(),
@@ -81,11 +82,11 @@ async fn clicking_on_confirmation_link_confirms_a_subscriber(pool: PgPool) {
 }
 
 #[sqlx::test]
-async fn confirm_returns_error_if_subscription_already_confirmed(pool: PgPool) {
+async fn confirmation_link_cannot_be_used_twice(pool: PgPool) {
     // Arrange
     let test_app = helpers::TestApp::setup(pool).await;
     let name = "Ayaya";
-    let email = "ayaya@gachi.com";
+    let email = &helpers::unique_email("ayaya");
 
     Mock::given(matchers::path("/email"))
         .and(matchers::method("POST"))
@@ -111,5 +112,71 @@ async fn confirm_returns_error_if_subscription_already_confirmed(pool: PgPool) {
         .await;
 
     // Assert
-    response.assert_status(StatusCode::CONFLICT);
+    response.assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn confirmation_link_does_not_undo_an_unsubscribe(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("unsubscribed");
+    let (subscriber_id, token) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+    sqlx::query!(
+        "UPDATE list_subscriptions SET status = $1 WHERE subscriber_id = $2",
+        SubscriptionStatus::Unsubscribed.to_string(),
+        subscriber_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = test_app
+        .app_server
+        .get("/subscribe/confirm")
+        .add_query_param("subscription_token", token.as_str())
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::UNAUTHORIZED);
+    let status = sqlx::query!(
+        "SELECT status FROM list_subscriptions WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .unwrap()
+    .status;
+    assert_eq!(status, SubscriptionStatus::Unsubscribed.to_string());
+}
+
+#[sqlx::test]
+async fn confirmation_with_expired_token_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("expired");
+    let issued_at = Utc::now() - test_app.settings.subscriptions.confirmation_token_ttl();
+    let (subscriber_id, token) = test_app.store_pending_subscriber(&email, issued_at).await;
+
+    // Act
+    let response = test_app
+        .app_server
+        .get("/subscribe/confirm")
+        .add_query_param("subscription_token", token.as_str())
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::GONE);
+    assert!(response.text().contains("/subscribe/resend"));
+    let saved = sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to fetch saved subscription.");
+    assert_eq!(
+        saved.status,
+        SubscriptionStatus::PendingConfirmation.to_string()
+    );
 }
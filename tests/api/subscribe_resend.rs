@@ -0,0 +1,99 @@
+use axum::http::StatusCode;
+use chrono::Utc;
+use sqlx::PgPool;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to};
+
+#[sqlx::test]
+async fn resend_confirmation_form_is_public(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app.get_subscribe_resend().await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response.text().contains("Resend Confirmation Email"));
+}
+
+#[sqlx::test]
+async fn resend_confirmation_sends_a_fresh_link(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("resend");
+    let issued_at = Utc::now() - test_app.settings.subscriptions.confirmation_token_ttl();
+    let (_, old_token) = test_app.store_pending_subscriber(&email, issued_at).await;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_subscribe_resend(&email).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/subscribe/resend");
+    let html_page = test_app.get_subscribe_resend().await.text();
+    assert!(html_page.contains("a new confirmation link has been sent"));
+
+    let confirmation_links = test_app.confirmation_links_from_latest_email().await;
+    test_app
+        .query_link_with_params(&confirmation_links.html)
+        .await
+        .assert_status_ok();
+    // The old token was replaced
+    let response = test_app
+        .app_server
+        .get("/subscribe/confirm")
+        .add_query_param("subscription_token", old_token.as_str())
+        .await;
+    response.assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn resend_confirmation_does_not_reveal_unknown_emails(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app
+        .post_subscribe_resend(&helpers::unique_email("nobody"))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/subscribe/resend");
+    let html_page = test_app.get_subscribe_resend().await.text();
+    assert!(html_page.contains("a new confirmation link has been sent"));
+}
+
+#[sqlx::test]
+async fn resend_confirmation_skips_confirmed_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("confirmed");
+    let (subscriber_id, _) = test_app.store_pending_subscriber(&email, Utc::now()).await;
+    test_app.confirm_stored_subscriber(subscriber_id).await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response = test_app.post_subscribe_resend(&email).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/subscribe/resend");
+}
@@ -0,0 +1,90 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use wiremock::{matchers, Mock, ResponseTemplate};
+use zero2prod::subscription_purger::purge_unconfirmed_subscribers;
+
+use crate::helpers;
+
+#[sqlx::test]
+async fn purge_deletes_only_old_unconfirmed_subscribers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let purge_after = test_app.settings.subscriptions.purge_unconfirmed_after();
+    let long_ago = Utc::now() - purge_after - chrono::Duration::hours(1);
+    let (old_pending, _) = test_app
+        .store_pending_subscriber(&helpers::unique_email("old"), long_ago)
+        .await;
+    let (old_confirmed, _) = test_app
+        .store_pending_subscriber(&helpers::unique_email("confirmed"), long_ago)
+        .await;
+    test_app.confirm_stored_subscriber(old_confirmed).await;
+    let (recent_pending, _) = test_app
+        .store_pending_subscriber(&helpers::unique_email("recent"), Utc::now())
+        .await;
+
+    // Act
+    let purged = purge_unconfirmed_subscribers(&test_app.app_state.db_pool, purge_after)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(purged, 1);
+    let remaining: Vec<_> = sqlx::query!("SELECT id FROM subscriptions ORDER BY email")
+        .fetch_all(&*test_app.app_state.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.id)
+        .collect();
+    assert!(!remaining.contains(&old_pending));
+    assert!(remaining.contains(&old_confirmed));
+    assert!(remaining.contains(&recent_pending));
+    let tokens = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM subscription_tokens WHERE subscriber_id = $1",
+        old_pending
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tokens.count, 0);
+}
+
+#[sqlx::test]
+async fn purge_keeps_subscribers_sent_a_fresh_link(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let purge_after = test_app.settings.subscriptions.purge_unconfirmed_after();
+    let long_ago = Utc::now() - purge_after - chrono::Duration::hours(1);
+    let email = helpers::unique_email("resent");
+    let (subscriber_id, _) = test_app.store_pending_subscriber(&email, long_ago).await;
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    test_app.post_subscribe_resend(&email).await;
+
+    // Act
+    let purged = purge_unconfirmed_subscribers(&test_app.app_state.db_pool, purge_after)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(purged, 0);
+    let confirmation_links = test_app.confirmation_links_from_latest_email().await;
+    test_app
+        .query_link_with_params(&confirmation_links.html)
+        .await
+        .assert_status_ok();
+    let status = sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .unwrap()
+    .status;
+    assert_eq!(status, "confirmed");
+}
@@ -0,0 +1,483 @@
+use axum::http::StatusCode;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, assert_is_redirect_to};
+use zero2prod::{
+    domain::{UnsubscribeToken, Url},
+    routes::preferences_link,
+};
+
+async fn store_confirmed_subscriber(test_app: &helpers::TestApp, email: &str) -> Uuid {
+    let (subscriber_id, _) = test_app.store_pending_subscriber(email, Utc::now()).await;
+    test_app.confirm_stored_subscriber(subscriber_id).await;
+    subscriber_id
+}
+
+async fn get_subscriber(test_app: &helpers::TestApp, subscriber_id: Uuid) -> (String, String) {
+    let saved = sqlx::query!(
+        "SELECT name, email FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to fetch saved subscription.");
+    (saved.name, saved.email)
+}
+
+fn signed_preferences_link(test_app: &helpers::TestApp, subscriber_id: Uuid) -> Url {
+    preferences_link(
+        &test_app.app_state.app_base_url,
+        subscriber_id,
+        &test_app.app_state.hmac_secret,
+    )
+}
+
+/// Stores a published newsletter that was delivered to the email and is queued for it again.
+async fn store_delivered_newsletter(test_app: &helpers::TestApp, email: &str) {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues
+            (newsletter_issue_id, title, text_content, html_content, published_at)
+        VALUES ($1, 'Issue #1', 'Text', '<p>HTML</p>', now())
+        "#,
+        newsletter_issue_id
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to store newsletter issue.");
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_delivery (newsletter_issue_id, subscriber_email, status, sent_at)
+        VALUES ($1, $2, 'sent', now())
+        "#,
+        newsletter_issue_id,
+        email
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to store newsletter delivery.");
+    sqlx::query!(
+        "INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email) VALUES ($1, $2)",
+        newsletter_issue_id,
+        email
+    )
+    .execute(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to queue newsletter delivery.");
+}
+
+/// Posts a preferences form to the path below `/subscriptions/preferences`, like the page does.
+async fn post_preferences_form(
+    test_app: &helpers::TestApp,
+    link: &Url,
+    action: &str,
+    form: &[(&str, &str)],
+) -> axum_test::TestResponse {
+    test_app
+        .app_server
+        .post(&format!("{}/{}", link.path(), action))
+        .add_query_params(link.query_params())
+        .form(&form)
+        .await
+}
+
+#[sqlx::test]
+async fn preferences_page_shows_subscriber_details(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("preferences");
+    let subscriber_id = store_confirmed_subscriber(&test_app, &email).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    // Act
+    let response = test_app.query_link_with_params(&link).await;
+
+    // Assert
+    response.assert_status_ok();
+    let html_page = response.text();
+    assert!(html_page.contains(&email));
+    assert!(html_page.contains("confirmed"));
+    assert!(html_page.contains("Pending Subscriber"));
+}
+
+#[sqlx::test]
+async fn preferences_with_unsubscribe_token_are_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id =
+        store_confirmed_subscriber(&test_app, &helpers::unique_email("forged")).await;
+    let token = UnsubscribeToken::generate(subscriber_id, None, &test_app.app_state.hmac_secret);
+
+    // Act
+    let response = test_app
+        .app_server
+        .get("/subscriptions/preferences")
+        .add_query_param("subscriber_id", subscriber_id)
+        .add_query_param("token", token.as_str())
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test]
+async fn subscriber_can_update_their_name(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id = store_confirmed_subscriber(&test_app, &helpers::unique_email("name")).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    // Act
+    let response = post_preferences_form(&test_app, &link, "name", &[("name", "New Name")]).await;
+
+    // Assert
+    let page = &link.as_str()[link.as_str().find("/subscriptions").unwrap()..];
+    assert_is_redirect_to(&response, page);
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("Your name has been updated"));
+    let (name, _) = get_subscriber(&test_app, subscriber_id).await;
+    assert_eq!(name, "New Name");
+}
+
+#[sqlx::test]
+async fn invalid_name_is_not_saved(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id =
+        store_confirmed_subscriber(&test_app, &helpers::unique_email("invalid")).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    // Act
+    post_preferences_form(&test_app, &link, "name", &[("name", "Bad<Name>")]).await;
+
+    // Assert
+    let (name, _) = get_subscriber(&test_app, subscriber_id).await;
+    assert_eq!(name, "Pending Subscriber");
+}
+
+#[sqlx::test]
+async fn subscriber_can_leave_and_rejoin_a_list_from_preferences(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+    let subscriber_id =
+        store_confirmed_subscriber(&test_app, &helpers::unique_email("lists")).await;
+    test_app
+        .store_confirmed_list_subscription(subscriber_id, "release-notes")
+        .await;
+    let release_notes_id = test_app.get_list_id("release-notes").await.to_string();
+    let link = signed_preferences_link(&test_app, subscriber_id);
+    let get_statuses = || async {
+        sqlx::query!(
+            r#"
+            SELECT s.status, ls.status AS list_status
+            FROM subscriptions s
+            JOIN list_subscriptions ls ON ls.subscriber_id = s.id
+            JOIN lists l USING (list_id)
+            WHERE s.id = $1 AND l.slug = 'release-notes'
+            "#,
+            subscriber_id
+        )
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .map(|r| (r.status, r.list_status))
+        .unwrap()
+    };
+
+    // Act & Assert 1 - Both lists can be left from the page
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("Unsubscribe from Release Notes"));
+    assert!(html_page.contains("Unsubscribe from Newsletter"));
+
+    // Act & Assert 2 - Leave release-notes only
+    let response = post_preferences_form(
+        &test_app,
+        &link,
+        "lists",
+        &[("list_id", &release_notes_id), ("subscribed", "false")],
+    )
+    .await;
+    assert_eq!(response.status_code(), StatusCode::SEE_OTHER);
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("You have been unsubscribed from Release Notes"));
+    assert!(html_page.contains("Subscribe to Release Notes"));
+    assert_eq!(
+        get_statuses().await,
+        ("confirmed".to_string(), "unsubscribed".to_string())
+    );
+
+    // Act & Assert 3 - Rejoin it
+    post_preferences_form(
+        &test_app,
+        &link,
+        "lists",
+        &[("list_id", &release_notes_id), ("subscribed", "true")],
+    )
+    .await;
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("You are now subscribed to Release Notes"));
+    assert_eq!(
+        get_statuses().await,
+        ("confirmed".to_string(), "confirmed".to_string())
+    );
+}
+
+#[sqlx::test]
+async fn email_change_takes_effect_once_the_new_address_is_verified(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let old_email = helpers::unique_email("old");
+    let new_email = helpers::unique_email("new");
+    let subscriber_id = store_confirmed_subscriber(&test_app, &old_email).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act 1 - Request the change
+    post_preferences_form(&test_app, &link, "email", &[("email", &new_email)]).await;
+
+    // Assert 1 - Nothing changes before verification
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("A verification link has been sent"));
+    assert!(html_page.contains(&format!("Waiting for <b>{}</b>", new_email)));
+    let (_, email) = get_subscriber(&test_app, subscriber_id).await;
+    assert_eq!(email, old_email);
+
+    // Act 2 - Follow the link sent to the new address
+    let email_request = &test_app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], new_email);
+    let verification_link = test_app.confirmation_links_from_latest_email().await.html;
+    let response = test_app.query_link_with_params(&verification_link).await;
+
+    // Assert 2
+    response.assert_status(StatusCode::SEE_OTHER);
+    let (_, email) = get_subscriber(&test_app, subscriber_id).await;
+    assert_eq!(email, new_email);
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("Your email address has been updated"));
+}
+
+#[sqlx::test]
+async fn email_change_to_a_subscribed_address_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let other_email = helpers::unique_email("other");
+    store_confirmed_subscriber(&test_app, &other_email).await;
+    let subscriber_id = store_confirmed_subscriber(&test_app, &helpers::unique_email("me")).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    let response =
+        post_preferences_form(&test_app, &link, "email", &[("email", &other_email)]).await;
+
+    // Assert
+    assert_eq!(response.status_code(), StatusCode::SEE_OTHER);
+    let html_page = test_app.query_link_with_params(&link).await.text();
+    assert!(html_page.contains("This email address is already subscribed"));
+}
+
+#[sqlx::test]
+async fn expired_email_change_link_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let old_email = helpers::unique_email("old");
+    let subscriber_id = store_confirmed_subscriber(&test_app, &old_email).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    post_preferences_form(
+        &test_app,
+        &link,
+        "email",
+        &[("email", &helpers::unique_email("new"))],
+    )
+    .await;
+    sqlx::query!("UPDATE subscription_email_changes SET created_at = now() - interval '1 year'")
+        .execute(&*test_app.app_state.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let verification_link = test_app.confirmation_links_from_latest_email().await.html;
+    let response = test_app.query_link_with_params(&verification_link).await;
+
+    // Assert
+    response.assert_status(StatusCode::GONE);
+    let (_, email) = get_subscriber(&test_app, subscriber_id).await;
+    assert_eq!(email, old_email);
+}
+
+#[sqlx::test]
+async fn subscriber_can_download_their_data(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("download");
+    let subscriber_id = store_confirmed_subscriber(&test_app, &email).await;
+    store_delivered_newsletter(&test_app, &email).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    // Act
+    let response = test_app
+        .app_server
+        .get(&format!("{}/data", link.path()))
+        .add_query_params(link.query_params())
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    let data: serde_json::Value = response.json();
+    assert_eq!(data["email"], email);
+    assert_eq!(data["status"], "confirmed");
+    assert_eq!(data["deliveries"][0]["newsletter_title"], "Issue #1");
+    assert_eq!(data["queued_newsletters"][0], "Issue #1");
+}
+
+#[sqlx::test]
+async fn erased_subscriber_leaves_only_a_tombstone_that_blocks_reimport(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let email = helpers::unique_email("erased");
+    let subscriber_id = store_confirmed_subscriber(&test_app, &email).await;
+    store_delivered_newsletter(&test_app, &email).await;
+    let link = signed_preferences_link(&test_app, subscriber_id);
+    let db_pool = &*test_app.app_state.db_pool;
+
+    // Act 1 - Erase
+    let response = post_preferences_form(&test_app, &link, "delete", &[]).await;
+
+    // Assert 1
+    assert_is_redirect_to(&response, "/");
+    let html_page = test_app.get_index().await.text();
+    assert!(html_page.contains("all your data have been deleted"));
+    let remaining = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM subscriptions WHERE email = $1) AS "subscriptions!",
+            (SELECT count(*) FROM subscription_tokens WHERE subscriber_id = $2) AS "tokens!",
+            (SELECT count(*) FROM issue_delivery_queue WHERE subscriber_email = $1) AS "queued!",
+            (SELECT count(*) FROM newsletter_delivery WHERE subscriber_email = $1) AS "logged!",
+            (SELECT count(*) FROM erased_subscribers) AS "tombstones!"
+        "#,
+        email,
+        subscriber_id
+    )
+    .fetch_one(db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        (
+            remaining.subscriptions,
+            remaining.tokens,
+            remaining.queued,
+            remaining.logged,
+            remaining.tombstones
+        ),
+        (0, 0, 0, 0, 1)
+    );
+
+    // Act 2 - Import an old list containing the address
+    test_app.login_as_test_user().await;
+    let csv = format!("name,email\nErased,{}\n", email.to_uppercase());
+    let response = test_app
+        .post_admin_subscribers_import(&csv, "confirmed")
+        .await;
+
+    // Assert 2
+    assert!(response
+        .text()
+        .contains("Imported 0 subscribers, skipped 0 already"));
+    let count = sqlx::query!(
+        r#"SELECT count(*) AS "count!" FROM subscriptions WHERE lower(email) = lower($1)"#,
+        email
+    )
+    .fetch_one(db_pool)
+    .await
+    .unwrap()
+    .count;
+    assert_eq!(count, 0);
+}
+
+#[sqlx::test]
+async fn erasure_after_an_email_change_removes_logs_of_the_old_address(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let old_email = helpers::unique_email("old");
+    let new_email = helpers::unique_email("new");
+    let subscriber_id = store_confirmed_subscriber(&test_app, &old_email).await;
+    store_delivered_newsletter(&test_app, &old_email).await;
+    let db_pool = &*test_app.app_state.db_pool;
+    sqlx::query!(
+        r#"
+        INSERT INTO failed_deliveries
+            (newsletter_issue_id, subscriber_email, last_error, n_attempts, failed_at)
+        SELECT newsletter_issue_id, $1, 'Mailbox full', 3, now() FROM newsletter_issues
+        "#,
+        old_email
+    )
+    .execute(db_pool)
+    .await
+    .expect("Failed to store failed delivery.");
+    let link = signed_preferences_link(&test_app, subscriber_id);
+
+    Mock::given(matchers::path("/email"))
+        .and(matchers::method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    post_preferences_form(&test_app, &link, "email", &[("email", &new_email)]).await;
+    let verification_link = test_app.confirmation_links_from_latest_email().await.html;
+    test_app.query_link_with_params(&verification_link).await;
+
+    // Act & Assert 1 - The export includes the logs of the old address
+    let data: serde_json::Value = test_app
+        .app_server
+        .get(&format!("{}/data", link.path()))
+        .add_query_params(link.query_params())
+        .await
+        .json();
+    assert_eq!(data["email"], new_email);
+    assert_eq!(data["deliveries"][0]["newsletter_title"], "Issue #1");
+    assert_eq!(data["failed_deliveries"][0]["newsletter_title"], "Issue #1");
+
+    // Act & Assert 2 - Erasing removes them
+    let response = post_preferences_form(&test_app, &link, "delete", &[]).await;
+    assert_is_redirect_to(&response, "/");
+    let remaining = sqlx::query!(
+        r#"
+        SELECT
+            (SELECT count(*) FROM issue_delivery_queue) AS "queued!",
+            (SELECT count(*) FROM newsletter_delivery) AS "logged!",
+            (SELECT count(*) FROM failed_deliveries) AS "failed!"
+        "#
+    )
+    .fetch_one(db_pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        (remaining.queued, remaining.logged, remaining.failed),
+        (0, 0, 0)
+    );
+}
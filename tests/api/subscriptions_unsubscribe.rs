@@ -0,0 +1,391 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use uuid::Uuid;
+use wiremock::{matchers, Mock, ResponseTemplate};
+
+use crate::helpers::{self, create_subscriber};
+use zero2prod::{
+    domain::{ListSlug, SubscriptionStatus, UnsubscribeToken, Url},
+    routes::{preferences_link, unsubscribe_link},
+};
+
+async fn publish_newsletter(test_app: &helpers::TestApp) {
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text_content": "Newsletter body as plain text",
+            "html_content": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+        }))
+        .await;
+}
+
+async fn get_subscriber(test_app: &helpers::TestApp) -> (Uuid, String) {
+    let saved = sqlx::query!("SELECT id, status FROM subscriptions")
+        .fetch_one(&*test_app.app_state.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    (saved.id, saved.status)
+}
+
+async fn get_list_statuses(test_app: &helpers::TestApp, subscriber_id: Uuid) -> Vec<String> {
+    sqlx::query!(
+        r#"
+        SELECT ls.status
+        FROM list_subscriptions ls
+        JOIN lists l USING (list_id)
+        WHERE ls.subscriber_id = $1
+        ORDER BY l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&*test_app.app_state.db_pool)
+    .await
+    .expect("Failed to fetch list subscriptions.")
+    .into_iter()
+    .map(|r| r.status)
+    .collect()
+}
+
+/// Link to leave the default list, as included in the newsletters sent to it.
+async fn signed_unsubscribe_link(test_app: &helpers::TestApp, subscriber_id: Uuid) -> Url {
+    signed_list_unsubscribe_link(test_app, subscriber_id, ListSlug::DEFAULT).await
+}
+
+async fn signed_list_unsubscribe_link(
+    test_app: &helpers::TestApp,
+    subscriber_id: Uuid,
+    slug: &str,
+) -> Url {
+    unsubscribe_link(
+        &test_app.app_state.app_base_url,
+        subscriber_id,
+        test_app.get_list_id(slug).await,
+        &test_app.app_state.hmac_secret,
+    )
+}
+
+/// Stores a confirmed subscriber of both the default list and release-notes.
+async fn create_subscriber_of_two_lists(test_app: &helpers::TestApp) -> Uuid {
+    test_app.login_as_test_user().await;
+    test_app
+        .post_admin_lists("release-notes", "Release Notes")
+        .await;
+    create_subscriber(test_app, true).await;
+    let (subscriber_id, _) = get_subscriber(test_app).await;
+    test_app
+        .store_confirmed_list_subscription(subscriber_id, "release-notes")
+        .await;
+    subscriber_id
+}
+
+#[sqlx::test]
+async fn newsletters_include_unsubscribe_link_and_headers(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    let (subscriber_id, _) = get_subscriber(&test_app).await;
+    let link = signed_unsubscribe_link(&test_app, subscriber_id).await;
+
+    Mock::given(matchers::path("/email/batch"))
+        .and(matchers::method("POST"))
+        .respond_with(helpers::PostmarkBatchResponder::default())
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    publish_newsletter(&test_app).await;
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = test_app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let body = &body[0];
+    assert_eq!(
+        body["Headers"],
+        serde_json::json!([
+            { "Name": "List-Unsubscribe", "Value": format!("<{}>", link) },
+            { "Name": "List-Unsubscribe-Post", "Value": "List-Unsubscribe=One-Click" }
+        ])
+    );
+    assert!(body["TextBody"].as_str().unwrap().contains(link.as_str()));
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains(&link.as_str().replace('&', "&amp;")));
+    let preferences_link = preferences_link(
+        &test_app.app_state.app_base_url,
+        subscriber_id,
+        &test_app.app_state.hmac_secret,
+    );
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains(preferences_link.as_str()));
+}
+
+#[sqlx::test]
+async fn unsubscribe_link_shows_confirmation_form(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    let (subscriber_id, _) = get_subscriber(&test_app).await;
+    let link = signed_unsubscribe_link(&test_app, subscriber_id).await;
+
+    // Act
+    let response = test_app.query_link_with_params(&link).await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response.text().contains("Unsubscribe from Newsletter?"));
+    // Following the link alone must not unsubscribe, mail scanners do that
+    let (_, status) = get_subscriber(&test_app).await;
+    assert_eq!(status, SubscriptionStatus::Confirmed.to_string());
+}
+
+#[sqlx::test]
+async fn posting_to_unsubscribe_link_unsubscribes_a_subscriber(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    let (subscriber_id, _) = get_subscriber(&test_app).await;
+    let link = signed_unsubscribe_link(&test_app, subscriber_id).await;
+
+    // Act
+    // Mail clients send this body for RFC 8058 one-click unsubscribe
+    let response = test_app
+        .app_server
+        .post(link.path())
+        .add_query_params(link.query_params())
+        .form(&[("List-Unsubscribe", "One-Click")])
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    assert!(response.text().contains("You have been unsubscribed"));
+    let (_, status) = get_subscriber(&test_app).await;
+    assert_eq!(status, SubscriptionStatus::Unsubscribed.to_string());
+}
+
+#[sqlx::test]
+async fn unsubscribe_with_forged_token_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    let (subscriber_id, _) = get_subscriber(&test_app).await;
+    // A valid token for someone else must not work for this subscriber
+    let other_link = signed_unsubscribe_link(&test_app, Uuid::new_v4()).await;
+    let token = other_link
+        .query_params()
+        .into_iter()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1;
+
+    let test_cases = vec![
+        (token, "token of another subscriber"),
+        ("a".repeat(64), "wrong token"),
+        ("not-even-hex".to_string(), "malformed token"),
+    ];
+
+    for (token, description) in test_cases {
+        // Act
+        let response = test_app
+            .app_server
+            .post("/subscriptions/unsubscribe")
+            .add_query_param("subscriber_id", subscriber_id)
+            .add_query_param("token", token)
+            .await;
+
+        // Assert
+        assert_eq!(
+            response.status_code(),
+            StatusCode::UNAUTHORIZED,
+            "The API did not reject unsubscribe with {}",
+            description
+        );
+    }
+    let (_, status) = get_subscriber(&test_app).await;
+    assert_eq!(status, SubscriptionStatus::Confirmed.to_string());
+}
+
+#[sqlx::test]
+async fn unsubscribe_without_parameters_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app.app_server.get("/subscriptions/unsubscribe").await;
+
+    // Assert
+    response.assert_status_bad_request();
+}
+
+#[sqlx::test]
+async fn unsubscribed_subscribers_do_not_receive_queued_newsletters(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    create_subscriber(&test_app, true).await;
+    let (subscriber_id, _) = get_subscriber(&test_app).await;
+    let link = signed_unsubscribe_link(&test_app, subscriber_id).await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    // Unsubscribe after the issue has been queued but before it is delivered
+    publish_newsletter(&test_app).await;
+    test_app
+        .app_server
+        .post(link.path())
+        .add_query_params(link.query_params())
+        .await
+        .assert_status_ok();
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we haven't sent the newsletter email
+}
+
+#[sqlx::test]
+async fn unsubscribing_from_a_list_keeps_the_other_lists(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id = create_subscriber_of_two_lists(&test_app).await;
+    let link = signed_list_unsubscribe_link(&test_app, subscriber_id, "release-notes").await;
+
+    // Act & Assert 1 - Leave release-notes
+    let response = test_app.query_link_with_params(&link).await;
+    assert!(response.text().contains("Unsubscribe from Release Notes?"));
+    let response = test_app
+        .app_server
+        .post(link.path())
+        .add_query_params(link.query_params())
+        .await;
+    response.assert_status_ok();
+    assert!(response
+        .text()
+        .contains("You will no longer receive Release Notes"));
+    assert_eq!(
+        get_list_statuses(&test_app, subscriber_id).await,
+        vec!["confirmed", "unsubscribed"]
+    );
+    let (_, status) = get_subscriber(&test_app).await;
+    assert_eq!(status, SubscriptionStatus::Confirmed.to_string());
+
+    // Act & Assert 2 - Leave the last list
+    let link = signed_unsubscribe_link(&test_app, subscriber_id).await;
+    test_app
+        .app_server
+        .post(link.path())
+        .add_query_params(link.query_params())
+        .await
+        .assert_status_ok();
+    let (_, status) = get_subscriber(&test_app).await;
+    assert_eq!(status, SubscriptionStatus::Unsubscribed.to_string());
+}
+
+#[sqlx::test]
+async fn unsubscribe_link_without_a_list_unsubscribes_from_every_list(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id = create_subscriber_of_two_lists(&test_app).await;
+    // Links sent before there were several lists sign no list
+    let token = UnsubscribeToken::generate(subscriber_id, None, &test_app.app_state.hmac_secret);
+
+    // Act
+    let response = test_app
+        .app_server
+        .post("/subscriptions/unsubscribe")
+        .add_query_param("subscriber_id", subscriber_id)
+        .add_query_param("token", token.as_str())
+        .await;
+
+    // Assert
+    response.assert_status_ok();
+    assert_eq!(
+        get_list_statuses(&test_app, subscriber_id).await,
+        vec!["unsubscribed", "unsubscribed"]
+    );
+    let (_, status) = get_subscriber(&test_app).await;
+    assert_eq!(status, SubscriptionStatus::Unsubscribed.to_string());
+}
+
+#[sqlx::test]
+async fn unsubscribe_link_of_one_list_does_not_work_for_another(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id = create_subscriber_of_two_lists(&test_app).await;
+    let link = signed_unsubscribe_link(&test_app, subscriber_id).await;
+    let release_notes_id = test_app.get_list_id("release-notes").await;
+    let params: Vec<_> = link
+        .query_params()
+        .into_iter()
+        .map(|(k, v)| match k.as_str() {
+            "list_id" => (k, release_notes_id.to_string()),
+            _ => (k, v),
+        })
+        .collect();
+
+    // Act
+    let response = test_app
+        .app_server
+        .post(link.path())
+        .add_query_params(params)
+        .await;
+
+    // Assert
+    response.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        get_list_statuses(&test_app, subscriber_id).await,
+        vec!["confirmed", "confirmed"]
+    );
+}
+
+#[sqlx::test]
+async fn subscribers_who_left_a_list_do_not_receive_its_queued_issues(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let subscriber_id = create_subscriber_of_two_lists(&test_app).await;
+    let link = signed_list_unsubscribe_link(&test_app, subscriber_id, "release-notes").await;
+
+    Mock::given(matchers::any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+
+    // Act
+    // Leave the list after the issue has been queued but before it is delivered
+    test_app
+        .post_admin_newsletters(&serde_json::json!({
+            "title": "Release 1.0",
+            "text_content": "Release notes as plain text",
+            "html_content": "<p>Release notes as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string(),
+            "list": "release-notes",
+        }))
+        .await;
+    test_app
+        .app_server
+        .post(link.path())
+        .add_query_params(link.query_params())
+        .await
+        .assert_status_ok();
+    test_app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that we haven't sent the newsletter email, even though the
+    // subscriber is still confirmed on the default list
+}
@@ -0,0 +1,254 @@
+use axum::http::StatusCode;
+use sqlx::PgPool;
+
+use crate::helpers::{self, assert_is_redirect_to, TestUser};
+use zero2prod::domain::{TotpSecret, UserRole};
+
+/// Stores an editor who has not set up two-factor authentication yet.
+async fn store_editor_without_two_factor(test_app: &helpers::TestApp) -> TestUser {
+    let mut editor = TestUser::generate_with_role(UserRole::Editor);
+    editor.totp_secret = None;
+    editor.store(&test_app.app_state.db_pool).await;
+    editor
+}
+
+fn extract_between<'a>(html: &'a str, start: &str, end: &str) -> &'a str {
+    html.split(start)
+        .nth(1)
+        .and_then(|s| s.split(end).next())
+        .unwrap_or_else(|| panic!("{} not found in page", start))
+        .trim()
+}
+
+/// Goes through the enrollment pages, returning the recovery codes that are shown at the end.
+async fn enroll_two_factor(test_app: &helpers::TestApp, user: &mut TestUser) -> Vec<String> {
+    let html_page = test_app.get_admin_two_factor().await.text();
+    let secret = extract_between(&html_page, r#"<div class="secret">"#, "</div>");
+    user.totp_secret = Some(TotpSecret::parse_base32(secret).unwrap());
+
+    let code = test_app.two_factor_code(user).await;
+    let response = test_app.post_admin_two_factor(&code).await;
+    response.assert_status_ok();
+    let html_page = response.text();
+    assert!(html_page.contains("Two-factor authentication enabled"));
+    extract_between(&html_page, r#"<div class="recovery-codes">"#, "<form")
+        .lines()
+        .filter_map(|l| {
+            l.trim()
+                .strip_prefix("<div>")
+                .and_then(|l| l.strip_suffix("</div>"))
+        })
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Shifts every digit, so that the code is valid for no period.
+fn wrong_code(code: &str) -> String {
+    code.chars()
+        .map(|c| char::from_digit((c.to_digit(10).unwrap() + 5) % 10, 10).unwrap())
+        .collect()
+}
+
+fn sample_newsletter_request_body() -> impl serde::Serialize {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    })
+}
+
+#[sqlx::test]
+async fn password_alone_does_not_log_in_a_user_with_two_factor(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+    test_app.get_login_two_factor().await.assert_status_ok();
+    assert_is_redirect_to(&test_app.get_admin_dashboard().await, "/login");
+}
+
+#[sqlx::test]
+async fn two_factor_page_requires_a_verified_password(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+
+    // Act
+    let page = test_app.get_login_two_factor().await;
+    let submit = test_app.post_login_two_factor("123456").await;
+
+    // Assert
+    assert_is_redirect_to(&page, "/login");
+    assert_is_redirect_to(&submit, "/login");
+}
+
+#[sqlx::test]
+async fn invalid_code_is_rejected(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+
+    // Act
+    let response = test_app.post_login_two_factor(&wrong_code(&code)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+    let html_page = test_app.get_login_two_factor().await.text();
+    assert!(html_page.contains("Invalid authentication code"));
+    assert_is_redirect_to(&test_app.get_admin_dashboard().await, "/login");
+}
+
+#[sqlx::test]
+async fn correct_code_is_refused_after_too_many_invalid_ones(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let login_body = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": &test_app.test_user.password,
+    });
+    let max_failures = test_app.settings.login_rate_limit.max_failures_per_username;
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+    test_app.post_login(&login_body).await;
+    for _ in 1..max_failures {
+        let response = test_app.post_login_two_factor(&wrong_code(&code)).await;
+        assert_is_redirect_to(&response, "/login/two_factor");
+    }
+
+    // Act & Assert 1 - The last invalid code drops the pending login
+    let response = test_app.post_login_two_factor(&wrong_code(&code)).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = test_app.get_login().await.text();
+    assert!(html_page.contains("Too many failed login attempts, please try again in"));
+    let response = test_app.post_login_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act & Assert 2 - Entering the password again does not allow more guesses
+    let response = test_app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/login/two_factor");
+    let response = test_app.post_login_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&test_app.get_admin_dashboard().await, "/login");
+}
+
+#[sqlx::test]
+async fn code_cannot_be_used_twice(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let login_body = serde_json::json!({
+        "username": &test_app.test_user.username,
+        "password": &test_app.test_user.password,
+    });
+    test_app.post_login(&login_body).await;
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+    let response = test_app.post_login_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    test_app.post_admin_logout().await;
+
+    // Act
+    test_app.post_login(&login_body).await;
+    let response = test_app.post_login_two_factor(&code).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[sqlx::test]
+async fn editors_must_enable_two_factor_before_publishing(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let mut editor = store_editor_without_two_factor(&test_app).await;
+    let response = test_app.login_as(&editor).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = test_app.get_admin_dashboard().await.text();
+    assert!(html_page.contains("enable two-factor authentication"));
+
+    // Act & Assert 1 - Publishing is forbidden
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    response.assert_status(StatusCode::FORBIDDEN);
+    assert!(response
+        .text()
+        .contains("You need to enable two-factor authentication to do this"));
+
+    // Act & Assert 2 - Enroll
+    let recovery_codes = enroll_two_factor(&test_app, &mut editor).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    // Act & Assert 3 - Publishing is allowed
+    let response = test_app
+        .post_admin_newsletters(&sample_newsletter_request_body())
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[sqlx::test]
+async fn recovery_code_can_be_used_once_instead_of_a_code(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    let mut editor = store_editor_without_two_factor(&test_app).await;
+    test_app.login_as(&editor).await;
+    let recovery_codes = enroll_two_factor(&test_app, &mut editor).await;
+    test_app.post_admin_logout().await;
+    let login_body = serde_json::json!({
+        "username": &editor.username,
+        "password": &editor.password,
+    });
+
+    // Act & Assert 1 - Log in with a recovery code
+    test_app.post_login(&login_body).await;
+    let response = test_app.post_login_two_factor(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = test_app.get_admin_two_factor().await.text();
+    assert!(html_page.contains("You have 9 unused recovery codes left"));
+    test_app.post_admin_logout().await;
+
+    // Act & Assert 2 - The same recovery code is rejected
+    test_app.post_login(&login_body).await;
+    let response = test_app.post_login_two_factor(&recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/two_factor");
+}
+
+#[sqlx::test]
+async fn disabling_two_factor_requires_a_valid_code(pool: PgPool) {
+    // Arrange
+    let test_app = helpers::TestApp::setup(pool).await;
+    test_app.login_as_test_user().await;
+
+    // Act & Assert 1 - Invalid code
+    test_app.post_admin_disable_two_factor("not-a-code").await;
+    let html_page = test_app.get_admin_two_factor().await.text();
+    assert!(html_page.contains("Invalid authentication code"));
+
+    // Act & Assert 2 - Valid code
+    let code = test_app.two_factor_code(&test_app.test_user).await;
+    let response = test_app.post_admin_disable_two_factor(&code).await;
+    assert_is_redirect_to(&response, "/admin/two_factor");
+    let html_page = test_app.get_admin_two_factor().await.text();
+    assert!(html_page.contains("Two-factor authentication disabled"));
+
+    // Act & Assert 3 - Password alone is enough to log in again
+    test_app.post_admin_logout().await;
+    let response = test_app
+        .post_login(&serde_json::json!({
+            "username": &test_app.test_user.username,
+            "password": &test_app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}